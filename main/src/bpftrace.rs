@@ -0,0 +1,808 @@
+use anyhow::bail;
+use parser::{
+    BaseTypeEncoding, FileHash, Function, LayoutItem, NamespaceKind, Type, TypeKind,
+    TypeModifierKind, TypeName, TypeOffset,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::offsetof::type_display;
+use crate::select::{parse_selector, resolve_alias, resolve_definition};
+use crate::{
+    c_array_def, c_enum_def, c_forward_decl, c_func_pointer_def, c_record_def, c_typedef_def,
+    get_type_info, is_ident_char, is_valid_ident, ArrayDef, Backend, EnumDef, FuncPtrDef, Printer,
+    ProcessState, Record, TypeIndex, TypeInfo, TypeRef, TypedefDef, BITS_PER_BYTE,
+};
+
+// System V AMD64 ABI 中用来传递整数与浮点参数的寄存器个数.
+const INT_REGS: usize = 6;
+const SSE_REGS: usize = 8;
+const EIGHTBYTE_BITS: u64 = 64;
+
+// bpftrace 脚本中内联的类型定义, 与 C backend 生成的 .h 一致, 但不需要 layout 校验.
+struct BpftracePrinter {
+    file: std::fs::File,
+}
+
+impl BpftracePrinter {
+    fn add_type(&mut self, lines: &[String]) -> io::Result<()> {
+        for l in lines {
+            writeln!(self.file, "{}", l)?;
+        }
+        writeln!(self.file)?;
+        return Ok(());
+    }
+}
+
+impl Backend for BpftracePrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        let mut asserts = Vec::new();
+        self.add_type(&c_record_def(record, &mut asserts))
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        self.add_type(&[c_array_def(array)])
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        self.add_type(&c_enum_def(enum_def))
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        self.add_type(&[c_typedef_def(typedef)])
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        self.add_type(&[c_func_pointer_def(func)])
+    }
+
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()> {
+        self.add_type(&[c_forward_decl(ty)])
+    }
+
+    fn add_size_check(&mut self, _ty: &TypeRef, _size: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// eightbyte 的分类, 参见 System V AMD64 ABI 3.2.3.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Class {
+    Integer,
+    Sse,
+}
+
+// 一个参数或者返回值的传递方式.
+enum Pass {
+    // 整数, 指针等, 占用一个通用寄存器或者一个栈槽位.
+    Integer,
+    // float, double, 占用一个 xmm 寄存器或者一个栈槽位.
+    Sse,
+    // 不超过 16 字节的 struct/union, 每个 eightbyte 按照其分类占用一个寄存器.
+    Split(Vec<Class>),
+    // 整个值被复制到栈上.
+    Memory,
+    // 传递的是指向值的指针, 比如 C++ 中 non-trivial 的类型, Rust 中超过 16 字节的类型.
+    Indirect,
+    // 零大小的类型, 不占用任何寄存器.
+    Ignore,
+}
+
+// 记录着已经分配出去的寄存器与栈空间.
+#[derive(Default)]
+struct Regs {
+    gpr: usize,
+    sse: usize,
+    // 栈上参数的总大小, 第一个栈上参数位于 `sp + 8`, 即返回地址之上.
+    stack: u64,
+}
+
+impl Regs {
+    fn next_integer(&mut self) -> String {
+        if self.gpr < INT_REGS {
+            self.gpr += 1;
+            return format!("arg{}", self.gpr - 1);
+        }
+        let slot = self.stack / 8;
+        self.stack += 8;
+        return format!("sarg{}", slot);
+    }
+
+    // 返回 None 意味着位于 xmm 寄存器中, bpftrace 无法读取.
+    fn next_sse(&mut self) -> Option<String> {
+        if self.sse < SSE_REGS {
+            self.sse += 1;
+            return None;
+        }
+        let slot = self.stack / 8;
+        self.stack += 8;
+        return Some(format!("sarg{}", slot));
+    }
+
+    // 返回栈上 size 字节的区域的地址.
+    fn next_memory(&mut self, size: u64) -> String {
+        let off = self.stack;
+        self.stack += size.div_ceil(8) * 8;
+        return format!("reg(\"sp\") + {}", 8 + off);
+    }
+}
+
+// 脚本中的一个变量, `$var = expr;`.
+struct Var {
+    var: String,
+    // 形如 `y: int`, 输出到注释中.
+    desc: String,
+    // None 意味着 bpftrace 无法读取该参数, 此时 note 说明了原因.
+    expr: Option<String>,
+    fmt: &'static str,
+    note: String,
+}
+
+struct Script<'a, 'input> {
+    inputs_hash: &'a [FileHash<'input>],
+    type_db: &'a HashMap<TypeName<'a, 'input>, TypeIndex>,
+    // methods[input_id] 以全名, 比如 `::ns::S::~S`, 索引着 input 中各个类型的成员函数.
+    methods: Vec<HashMap<String, Vec<&'a Function<'input>>>>,
+    processed: ProcessState,
+    printer: Printer,
+}
+
+fn bt_int(size: u64, signed: bool) -> Option<String> {
+    if !matches!(size, 1 | 2 | 4 | 8) {
+        return None;
+    }
+    let sign = if signed { "" } else { "u" };
+    return Some(format!("{}int{}", sign, size * BITS_PER_BYTE));
+}
+
+fn is_signed(encoding: BaseTypeEncoding) -> bool {
+    matches!(
+        encoding,
+        BaseTypeEncoding::Signed | BaseTypeEncoding::SignedChar
+    )
+}
+
+// 返回值 (cast, fmt), cast 为 None 意味着直接使用寄存器的值.
+fn scalar_cast(ty: &TypeRef, size: u64) -> (Option<String>, &'static str) {
+    match ty {
+        TypeRef::Base { size, encoding, .. } => {
+            let signed = is_signed(*encoding);
+            (bt_int(*size, signed), if signed { "%d" } else { "%u" })
+        }
+        TypeRef::Pointer(Some(tyinfo)) => (pointer_cast(&tyinfo.ty), "%p"),
+        TypeRef::Pointer(None) => (None, "%p"),
+        // enum 等.
+        _ => (bt_int(size, true), "%d"),
+    }
+}
+
+// 指向 ty 的指针在 bpftrace 中的类型.
+fn pointer_cast(ty: &TypeRef) -> Option<String> {
+    match ty {
+        TypeRef::Struct(_) | TypeRef::Union(_) => Some(format!("{} *", ty.c_name())),
+        TypeRef::Base { size, encoding, .. } => {
+            bt_int(*size, is_signed(*encoding)).map(|v| format!("{} *", v))
+        }
+        _ => None,
+    }
+}
+
+fn cast_expr(cast: Option<String>, expr: String) -> String {
+    match cast {
+        Some(cast) => format!("({}){}", cast, expr),
+        None => expr,
+    }
+}
+
+fn probe_target(func: &Function) -> Option<String> {
+    let target = func.linkage_name().or(func.symbol_name()).or(func.name())?;
+    if target.chars().all(|v| is_ident_char(v) || v == '.') {
+        return Some(target.to_string());
+    }
+    return Some(format!("\"{}\"", target));
+}
+
+impl<'a, 'input> Script<'a, 'input> {
+    fn new(
+        inputs_hash: &'a [FileHash<'input>],
+        type_db: &'a HashMap<TypeName<'a, 'input>, TypeIndex>,
+        printer: Printer,
+    ) -> Self {
+        let mut methods = Vec::new();
+        for hash in inputs_hash {
+            let mut input_methods = HashMap::<String, Vec<&'a Function<'input>>>::new();
+            for unit in hash.file.units() {
+                for func in unit.functions() {
+                    let Some(ns) = func.namespace() else {
+                        continue;
+                    };
+                    if ns.kind() != NamespaceKind::Type {
+                        continue;
+                    }
+                    let fullname = TypeName {
+                        namespace: func.namespace(),
+                        name: func.name(),
+                    };
+                    input_methods
+                        .entry(fullname.to_string())
+                        .or_default()
+                        .push(func);
+                }
+            }
+            methods.push(input_methods);
+        }
+        return Script {
+            inputs_hash,
+            type_db,
+            methods,
+            processed: ProcessState::new(false),
+            printer,
+        };
+    }
+
+    // C++ 中用户声明了拷贝/移动构造函数或者析构函数的类型是 non-trivial 的, 需要通过指针传递.
+    fn has_user_copy_or_dtor(&self, input_id: usize, ty: &Type<'input>) -> bool {
+        let hash = &self.inputs_hash[input_id];
+        let Some(tyname) = TypeName::try_from(ty) else {
+            return false;
+        };
+        let Some(name) = tyname.name else {
+            return false;
+        };
+        let base = name.split('<').next().unwrap_or(name);
+        let fullname = tyname.to_string();
+        let methods = &self.methods[input_id];
+        if let Some(dtors) = methods.get(&format!("{}::~{}", fullname, base)) {
+            if dtors.iter().any(|v| !v.is_artificial()) {
+                return true;
+            }
+        }
+        let Some(ctors) = methods.get(&format!("{}::{}", fullname, base)) else {
+            return false;
+        };
+        for ctor in ctors {
+            if ctor.is_artificial() {
+                continue;
+            }
+            // 第一个参数为 this.
+            let [_, param] = ctor.parameters() else {
+                continue;
+            };
+            let Some(pty) = param.ty(hash) else {
+                continue;
+            };
+            let TypeKind::Modifier(v) = pty.kind() else {
+                continue;
+            };
+            if !matches!(
+                v.kind(),
+                TypeModifierKind::Reference | TypeModifierKind::RvalueReference
+            ) {
+                continue;
+            }
+            let Some(pointee) = v.ty(hash).and_then(|v| resolve_alias(hash, v)) else {
+                continue;
+            };
+            let Some(pointee_name) = TypeName::try_from(&pointee) else {
+                continue;
+            };
+            if pointee_name.to_string() == fullname {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // 对应着 Itanium C++ ABI 中的 trivial for the purposes of calls.
+    // 优先使用 DW_AT_calling_convention, gcc 并不会生成该属性, 此时根据成员函数与虚表推断.
+    fn is_trivial(&self, input_id: usize, ty: Cow<'a, Type<'input>>) -> bool {
+        let (input_id, ty) = resolve_definition(self.inputs_hash, self.type_db, input_id, ty);
+        let hash = &self.inputs_hash[input_id];
+        match ty.kind() {
+            TypeKind::Struct(v) => {
+                if let Some(by_ref) = v.is_pass_by_reference() {
+                    return !by_ref;
+                }
+                if self.has_user_copy_or_dtor(input_id, &ty) {
+                    return false;
+                }
+                for l in v.layout(hash) {
+                    let subty = match l.item {
+                        LayoutItem::Member(m) => {
+                            if m.name().is_some_and(|v| v.starts_with("_vptr")) {
+                                return false;
+                            }
+                            m.ty(hash)
+                        }
+                        LayoutItem::Inherit(i) => i.ty(hash),
+                        LayoutItem::Padding | LayoutItem::VariantPart(_) => None,
+                    };
+                    if let Some(subty) = subty {
+                        if !self.is_trivial(input_id, subty) {
+                            return false;
+                        }
+                    }
+                }
+                return true;
+            }
+            TypeKind::Union(v) => {
+                if let Some(by_ref) = v.is_pass_by_reference() {
+                    return !by_ref;
+                }
+                for m in v.members() {
+                    if let Some(mty) = m.ty(hash) {
+                        if !self.is_trivial(input_id, mty) {
+                            return false;
+                        }
+                    }
+                }
+                return true;
+            }
+            TypeKind::Array(v) => match v.element_type(hash) {
+                Some(elem) => self.is_trivial(input_id, elem),
+                None => true,
+            },
+            _ => true,
+        }
+    }
+
+    // 将 [bit_off, bit_off + bit_size) 覆盖到的 eightbyte 标记为 class, INTEGER 优先于 SSE.
+    fn mark(
+        classes: &mut [Option<Class>],
+        bit_off: u64,
+        bit_size: u64,
+        class: Class,
+    ) -> Option<()> {
+        if bit_size == 0 {
+            return Some(());
+        }
+        let first = bit_off / EIGHTBYTE_BITS;
+        let last = (bit_off + bit_size - 1) / EIGHTBYTE_BITS;
+        for idx in first..=last {
+            let slot = classes.get_mut(idx as usize)?;
+            *slot = match (*slot, class) {
+                (Some(Class::Integer), _) | (_, Class::Integer) => Some(Class::Integer),
+                _ => Some(Class::Sse),
+            };
+        }
+        return Some(());
+    }
+
+    // 返回 None 意味着存在未对齐的字段或者 long double 等, 此时整个值通过 MEMORY 传递.
+    fn classify_fields(
+        &self,
+        input_id: usize,
+        ty: Cow<'a, Type<'input>>,
+        bit_off: u64,
+        classes: &mut [Option<Class>],
+    ) -> Option<()> {
+        let (input_id, ty) = resolve_definition(self.inputs_hash, self.type_db, input_id, ty);
+        let hash = &self.inputs_hash[input_id];
+        let class = match ty.kind() {
+            TypeKind::Struct(v) => {
+                for l in v.layout(hash) {
+                    let off = bit_off + l.bit_offset;
+                    match l.item {
+                        LayoutItem::Padding => {}
+                        LayoutItem::Member(m) if m.is_bitfield() => {
+                            Self::mark(classes, off, m.bit_size(hash)?, Class::Integer)?;
+                        }
+                        LayoutItem::Member(m) => {
+                            self.classify_fields(input_id, m.ty(hash)?, off, classes)?;
+                        }
+                        LayoutItem::Inherit(i) => {
+                            self.classify_fields(input_id, i.ty(hash)?, off, classes)?;
+                        }
+                        LayoutItem::VariantPart(_) => {
+                            Self::mark(classes, off, l.bit_size.get()?, Class::Integer)?;
+                        }
+                    }
+                }
+                return Some(());
+            }
+            TypeKind::Union(v) => {
+                for m in v.members() {
+                    let off = bit_off + m.bit_offset();
+                    self.classify_fields(input_id, m.ty(hash)?, off, classes)?;
+                }
+                return Some(());
+            }
+            TypeKind::Array(v) => {
+                let elem = v.element_type(hash)?;
+                let elem_size = elem.byte_size(hash)?;
+                if elem_size == 0 {
+                    return Some(());
+                }
+                for idx in 0..v.count(hash)? {
+                    let off = bit_off + idx * elem_size * BITS_PER_BYTE;
+                    self.classify_fields(input_id, elem.clone(), off, classes)?;
+                }
+                return Some(());
+            }
+            TypeKind::Base(v) if v.encoding() == BaseTypeEncoding::Float => {
+                if v.byte_size()? > 8 {
+                    return None;
+                }
+                Class::Sse
+            }
+            _ => Class::Integer,
+        };
+        let size = ty.byte_size(hash)?;
+        if size == 0 {
+            return Some(());
+        }
+        if !bit_off.is_multiple_of(std::cmp::min(size, 8) * BITS_PER_BYTE) {
+            return None;
+        }
+        return Self::mark(classes, bit_off, size * BITS_PER_BYTE, class);
+    }
+
+    // rust 为 true 时按照 Rust ABI 处理, Rust ABI 并不稳定, 这里只是尽力而为.
+    fn classify(&self, input_id: usize, ty: Cow<'a, Type<'input>>, rust: bool) -> Option<Pass> {
+        let (input_id, ty) = resolve_definition(self.inputs_hash, self.type_db, input_id, ty);
+        let hash = &self.inputs_hash[input_id];
+        match ty.kind() {
+            TypeKind::Base(v) => {
+                let size = v.byte_size()?;
+                if v.encoding() == BaseTypeEncoding::Float {
+                    return Some(if size <= 8 { Pass::Sse } else { Pass::Memory });
+                }
+                if size <= 8 {
+                    return Some(Pass::Integer);
+                }
+                return Some(Pass::Split(vec![Class::Integer, Class::Integer]));
+            }
+            TypeKind::Enumeration(_) => Some(Pass::Integer),
+            TypeKind::Modifier(v)
+                if matches!(
+                    v.kind(),
+                    TypeModifierKind::Pointer
+                        | TypeModifierKind::Reference
+                        | TypeModifierKind::RvalueReference
+                ) =>
+            {
+                Some(Pass::Integer)
+            }
+            TypeKind::PointerToMember(_) => {
+                if ty.byte_size(hash)? <= 8 {
+                    return Some(Pass::Integer);
+                }
+                return Some(Pass::Split(vec![Class::Integer, Class::Integer]));
+            }
+            TypeKind::Struct(_) | TypeKind::Union(_) | TypeKind::Array(_) => {
+                let size = ty.byte_size(hash)?;
+                if size == 0 {
+                    return Some(Pass::Ignore);
+                }
+                if !rust && !self.is_trivial(input_id, ty.clone()) {
+                    return Some(Pass::Indirect);
+                }
+                if size > 16 {
+                    return Some(if rust { Pass::Indirect } else { Pass::Memory });
+                }
+                let mut classes = vec![None; size.div_ceil(8) as usize];
+                if self
+                    .classify_fields(input_id, ty, 0, &mut classes)
+                    .is_none()
+                {
+                    return Some(if rust { Pass::Indirect } else { Pass::Memory });
+                }
+                // 只有 padding 的 eightbyte 按照 INTEGER 处理.
+                let classes = classes
+                    .into_iter()
+                    .map(|v| v.unwrap_or(Class::Integer))
+                    .collect();
+                return Some(Pass::Split(classes));
+            }
+            _ => None,
+        }
+    }
+
+    // 首次查询时会将类型定义输出到脚本中.
+    fn type_info(&mut self, tyidx: TypeIndex) -> io::Result<Option<Rc<TypeInfo>>> {
+        get_type_info(
+            &mut self.processed,
+            &mut self.printer,
+            tyidx,
+            None,
+            self.inputs_hash,
+            self.type_db,
+        )
+    }
+
+    // 指向 tyidx 对应值的指针在 bpftrace 中的类型.
+    fn value_pointer_cast(&mut self, tyidx: TypeIndex) -> io::Result<Option<String>> {
+        let tyinfo = self.type_info(tyidx)?;
+        return Ok(tyinfo.and_then(|v| pointer_cast(&v.ty)));
+    }
+
+    fn param_vars(
+        &mut self,
+        regs: &mut Regs,
+        input_id: usize,
+        var: String,
+        desc: String,
+        ty: Option<Cow<'a, Type<'input>>>,
+        rust: bool,
+    ) -> io::Result<Vec<Var>> {
+        let unavailable = |note: &str| Var {
+            var: var.clone(),
+            desc: desc.clone(),
+            expr: None,
+            fmt: "",
+            note: note.to_string(),
+        };
+        let Some(ty) = ty else {
+            regs.next_integer();
+            return Ok(vec![unavailable("unknown type")]);
+        };
+        let tyidx = TypeIndex {
+            input_id,
+            typoff: ty.offset(),
+        };
+        let Some(pass) = self.classify(input_id, ty, rust) else {
+            regs.next_integer();
+            return Ok(vec![unavailable("unsupported type")]);
+        };
+        let pointer_var = |expr: String, cast: Option<String>, note: &str| Var {
+            var: var.clone(),
+            desc: desc.clone(),
+            expr: Some(cast_expr(cast, expr)),
+            fmt: "%p",
+            note: note.to_string(),
+        };
+        let vars = match pass {
+            Pass::Ignore => vec![unavailable("zero-sized, not passed")],
+            Pass::Integer => {
+                let expr = regs.next_integer();
+                let Some(tyinfo) = self.type_info(tyidx)? else {
+                    return Ok(vec![pointer_var(expr, None, "raw value")]);
+                };
+                let (cast, fmt) = scalar_cast(&tyinfo.ty, tyinfo.size);
+                vec![Var {
+                    var,
+                    desc,
+                    expr: Some(cast_expr(cast, expr)),
+                    fmt,
+                    note: String::new(),
+                }]
+            }
+            Pass::Sse => match regs.next_sse() {
+                None => vec![unavailable("passed in xmm register")],
+                Some(expr) => vec![Var {
+                    var,
+                    desc,
+                    expr: Some(expr),
+                    fmt: "0x%lx",
+                    note: "raw bits".to_string(),
+                }],
+            },
+            Pass::Indirect => {
+                let expr = regs.next_integer();
+                let cast = self.value_pointer_cast(tyidx)?;
+                vec![pointer_var(expr, cast, "passed by reference")]
+            }
+            Pass::Memory => {
+                let size = tyidx_size(self.inputs_hash, tyidx);
+                let expr = format!("({})", regs.next_memory(size));
+                let cast = self.value_pointer_cast(tyidx)?;
+                vec![pointer_var(expr, cast, "copied to stack")]
+            }
+            Pass::Split(classes) => {
+                let n_int = classes.iter().filter(|v| **v == Class::Integer).count();
+                let n_sse = classes.len() - n_int;
+                if regs.gpr + n_int > INT_REGS || regs.sse + n_sse > SSE_REGS {
+                    let size = tyidx_size(self.inputs_hash, tyidx);
+                    let expr = format!("({})", regs.next_memory(size));
+                    let cast = self.value_pointer_cast(tyidx)?;
+                    return Ok(vec![pointer_var(expr, cast, "copied to stack")]);
+                }
+                // 确保类型定义被输出.
+                self.type_info(tyidx)?;
+                let mut vars = Vec::new();
+                for (idx, class) in classes.iter().enumerate() {
+                    let var = format!("{}_{}", var, idx);
+                    let desc = format!("{}, eightbyte {}", desc, idx);
+                    vars.push(match class {
+                        Class::Integer => Var {
+                            var,
+                            desc,
+                            expr: Some(regs.next_integer()),
+                            fmt: "0x%lx",
+                            note: String::new(),
+                        },
+                        Class::Sse => {
+                            regs.sse += 1;
+                            Var {
+                                var,
+                                desc,
+                                expr: None,
+                                fmt: "",
+                                note: "passed in xmm register".to_string(),
+                            }
+                        }
+                    });
+                }
+                vars
+            }
+        };
+        return Ok(vars);
+    }
+
+    fn probe(
+        &mut self,
+        input_id: usize,
+        input_path: &str,
+        func: &'a Function<'input>,
+        rust: bool,
+    ) -> io::Result<Vec<String>> {
+        let hash = &self.inputs_hash[input_id];
+        let fullname = TypeName {
+            namespace: func.namespace(),
+            name: func.name(),
+        }
+        .to_string();
+        let fullname = fullname.trim_start_matches("::");
+        let mut lines = Vec::new();
+        lines.push(format!("// {}", fullname));
+        if rust {
+            lines.push(
+                "// Rust ABI is not stable, the argument mapping is best-effort.".to_string(),
+            );
+        }
+        let Some(target) = probe_target(func) else {
+            lines.push("// no symbol to attach".to_string());
+            return Ok(lines);
+        };
+
+        let mut regs = Regs::default();
+        let mut vars = Vec::new();
+        if let Some(ret) = func.return_type(hash) {
+            let hidden = matches!(
+                self.classify(input_id, ret.clone(), rust),
+                Some(Pass::Memory | Pass::Indirect)
+            );
+            if hidden {
+                let expr = regs.next_integer();
+                let tyidx = TypeIndex {
+                    input_id,
+                    typoff: ret.offset(),
+                };
+                let cast = self.value_pointer_cast(tyidx)?;
+                vars.push(Var {
+                    var: "__ret".to_string(),
+                    desc: format!("return {}", type_display(hash, &ret)),
+                    expr: Some(cast_expr(cast, expr)),
+                    fmt: "%p",
+                    note: "hidden struct-return pointer".to_string(),
+                });
+            }
+        }
+        for (idx, param) in func.parameters().iter().enumerate() {
+            let ty = param.ty(hash);
+            let name = param.name().unwrap_or("");
+            let var = if !name.is_empty() && is_valid_ident(name) {
+                name.to_string()
+            } else {
+                format!("arg{}", idx)
+            };
+            let tydesc = ty
+                .as_ref()
+                .map(|v| type_display(hash, v))
+                .unwrap_or_else(|| "<unknown>".to_string());
+            let desc = format!("{}: {}", var, tydesc);
+            vars.extend(self.param_vars(&mut regs, input_id, var, desc, ty, rust)?);
+        }
+
+        lines.push(format!("uprobe:{}:{}", input_path, target));
+        lines.push("{".to_string());
+        let mut fmts = Vec::new();
+        let mut names = Vec::new();
+        for v in &vars {
+            let comment = if v.note.is_empty() {
+                format!("// {}", v.desc)
+            } else {
+                format!("// {}, {}", v.desc, v.note)
+            };
+            let Some(expr) = &v.expr else {
+                lines.push(format!("  {}", comment));
+                continue;
+            };
+            lines.push(format!("  ${} = {}; {}", v.var, expr, comment));
+            fmts.push(format!("{}={}", v.var, v.fmt));
+            names.push(format!(", ${}", v.var));
+        }
+        lines.push(format!(
+            "  printf(\"{} {}\\n\"{});",
+            fullname,
+            fmts.join(" "),
+            names.join("")
+        ));
+        lines.push("}".to_string());
+        return Ok(lines);
+    }
+}
+
+fn tyidx_size(inputs_hash: &[FileHash], tyidx: TypeIndex) -> u64 {
+    let hash = &inputs_hash[tyidx.input_id];
+    Type::from_offset(hash, tyidx.typoff)
+        .and_then(|v| v.byte_size(hash))
+        .unwrap_or(0)
+}
+
+// 为所有匹配 function 的函数生成 uprobe, 输出到 `{out_path}.bt`.
+// function 可以是 C++/Rust 的全名, 也可以是 linkage name.
+pub(crate) fn bpftrace(
+    inputs_hash: &[FileHash],
+    type_db: &HashMap<TypeName, TypeIndex>,
+    input_paths: &[String],
+    function: &str,
+    out_path: &str,
+) -> anyhow::Result<()> {
+    let selector = parse_selector(function)?;
+    let mut funcs = Vec::new();
+    let mut seen = HashSet::new();
+    for (input_id, hash) in inputs_hash.iter().enumerate() {
+        let Some(void) = Type::from_offset(hash, TypeOffset::default()) else {
+            continue;
+        };
+        for unit in hash.file.units() {
+            let rust = unit.language() == Some(gimli::DW_LANG_Rust);
+            for func in unit.functions() {
+                if func.is_declaration() {
+                    continue;
+                }
+                let Some(address) = func.address() else {
+                    continue;
+                };
+                let fullname = TypeName {
+                    namespace: func.namespace(),
+                    name: func.name(),
+                };
+                let matched = func.linkage_name() == Some(function)
+                    || func.symbol_name() == Some(function)
+                    || selector.matches(&fullname, &void, hash);
+                if matched && seen.insert((input_id, address)) {
+                    funcs.push((input_id, address, func, rust));
+                }
+            }
+        }
+    }
+    if funcs.is_empty() {
+        bail!("no function matches '{}'", function);
+    }
+    funcs.sort_by_key(|v| (v.0, v.1));
+
+    let mut bt_file = std::fs::File::create(format!("{}.bt", out_path))?;
+    writeln!(bt_file, "#!/usr/bin/env bpftrace")?;
+    writeln!(bt_file, "// Generated by hidva/clayout! 大吉大利!")?;
+    writeln!(bt_file, "#include <linux/types.h>")?;
+    writeln!(bt_file)?;
+    let backend = BpftracePrinter {
+        file: bt_file.try_clone()?,
+    };
+    let printer = Printer::with_backends(vec![Box::new(backend)]);
+    let mut script = Script::new(inputs_hash, type_db, printer);
+    let mut probes = Vec::new();
+    for (input_id, _, func, rust) in funcs {
+        // uprobe 需要绝对路径.
+        let input_path = std::fs::canonicalize(&input_paths[input_id])
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| input_paths[input_id].clone());
+        probes.push(script.probe(input_id, &input_path, func, rust)?);
+    }
+    script.printer.finish()?;
+    for lines in probes {
+        for l in lines {
+            writeln!(bt_file, "{}", l)?;
+        }
+        writeln!(bt_file)?;
+    }
+    return Ok(());
+}
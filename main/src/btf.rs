@@ -0,0 +1,430 @@
+use log::warn;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::{
+    ArrayDef, Backend, EnumDef, FuncPtrDef, Member, MemberKind, Qualifier, Record, RecordKind,
+    TypeRef, TypedefDef,
+};
+
+// 参见 https://docs.kernel.org/bpf/btf.html
+const BTF_MAGIC: u16 = 0xeb9f;
+const BTF_VERSION: u8 = 1;
+const BTF_HEADER_LEN: u32 = 24;
+
+const BTF_KIND_INT: u32 = 1;
+const BTF_KIND_PTR: u32 = 2;
+const BTF_KIND_ARRAY: u32 = 3;
+const BTF_KIND_STRUCT: u32 = 4;
+const BTF_KIND_UNION: u32 = 5;
+const BTF_KIND_ENUM: u32 = 6;
+const BTF_KIND_FWD: u32 = 7;
+const BTF_KIND_TYPEDEF: u32 = 8;
+const BTF_KIND_VOLATILE: u32 = 9;
+const BTF_KIND_CONST: u32 = 10;
+const BTF_KIND_FUNC_PROTO: u32 = 13;
+const BTF_KIND_FLOAT: u32 = 16;
+const BTF_KIND_ENUM64: u32 = 19;
+
+const BTF_INT_SIGNED: u32 = 1 << 0;
+const BTF_INT_CHAR: u32 = 1 << 1;
+const BTF_INT_BOOL: u32 = 1 << 2;
+
+// type id 0 表示 void.
+const BTF_VOID: u32 = 0;
+
+fn btf_info(kind: u32, kind_flag: bool, vlen: usize) -> u32 {
+    debug_assert!(vlen <= 0xffff);
+    ((kind_flag as u32) << 31) | (kind << 24) | (vlen as u32)
+}
+
+// 生成的 BTF 总是 little endian 的, 目前 clayout 的用户都跑在 x86_64/aarch64 上.
+// 由于 BTF 中 member offset 是显式指定的, 所以不需要 packed 这种概念, struct size 即为 packed_size.
+pub(crate) struct BtfPrinter {
+    path: String,
+    // 为 true 时额外输出一个仅包含 .BTF section 的 ELF 文件.
+    elf: bool,
+    types: Vec<u8>,
+    strings: Vec<u8>,
+    string_offs: HashMap<String, u32>,
+    // 最后一个分配出去的 type id.
+    last_id: u32,
+    // alloc_ident 分配的标识符 --> type id.
+    idents: HashMap<String, u32>,
+    bases: HashMap<String, u32>,
+    // pointee type id --> pointer type id.
+    pointers: HashMap<u32, u32>,
+    // (elem type id, count) --> array type id.
+    arrays: HashMap<(u32, u64), u32>,
+    // (BTF_KIND_CONST/BTF_KIND_VOLATILE, type id) --> qualified type id.
+    qualifiers: HashMap<(u32, u32), u32>,
+}
+
+impl BtfPrinter {
+    pub(crate) fn try_open(path: &str, elf: bool) -> io::Result<BtfPrinter> {
+        let mut printer = BtfPrinter {
+            path: path.to_string(),
+            elf,
+            types: Vec::new(),
+            strings: Vec::new(),
+            string_offs: HashMap::new(),
+            last_id: BTF_VOID,
+            idents: HashMap::new(),
+            bases: HashMap::new(),
+            pointers: HashMap::new(),
+            arrays: HashMap::new(),
+            qualifiers: HashMap::new(),
+        };
+        // 按照约定, string section 第一个字符串为空串.
+        printer.add_string("");
+        // 提前试一下能不能创建, 避免最后 finish 时才发现路径有问题.
+        std::fs::File::create(printer.btf_path())?;
+        Ok(printer)
+    }
+
+    fn btf_path(&self) -> String {
+        format!("{}.btf", self.path)
+    }
+
+    fn add_string(&mut self, s: &str) -> u32 {
+        if let Some(&off) = self.string_offs.get(s) {
+            return off;
+        }
+        let off = self.strings.len() as u32;
+        self.strings.extend_from_slice(s.as_bytes());
+        self.strings.push(0);
+        self.string_offs.insert(s.to_string(), off);
+        return off;
+    }
+
+    fn push_u32(&mut self, val: u32) {
+        self.types.extend_from_slice(&val.to_le_bytes());
+    }
+
+    // 写入 struct btf_type, 返回新分配的 type id.
+    fn add_btf_type(&mut self, name: &str, info: u32, size_or_type: u32) -> u32 {
+        let name_off = self.add_string(name);
+        self.push_u32(name_off);
+        self.push_u32(info);
+        self.push_u32(size_or_type);
+        self.last_id += 1;
+        return self.last_id;
+    }
+
+    fn base_type_id(&mut self, name: &str, size: u64, encoding: parser::BaseTypeEncoding) -> u32 {
+        if let Some(&id) = self.bases.get(name) {
+            return id;
+        }
+        let id = match encoding {
+            parser::BaseTypeEncoding::Float => {
+                self.add_btf_type(name, btf_info(BTF_KIND_FLOAT, false, 0), size as u32)
+            }
+            _ => {
+                let int_encoding = match encoding {
+                    parser::BaseTypeEncoding::Signed => BTF_INT_SIGNED,
+                    parser::BaseTypeEncoding::SignedChar => BTF_INT_SIGNED | BTF_INT_CHAR,
+                    parser::BaseTypeEncoding::UnsignedChar => BTF_INT_CHAR,
+                    parser::BaseTypeEncoding::Boolean => BTF_INT_BOOL,
+                    _ => 0,
+                };
+                let id = self.add_btf_type(name, btf_info(BTF_KIND_INT, false, 0), size as u32);
+                self.push_u32((int_encoding << 24) | (size as u32 * 8));
+                id
+            }
+        };
+        self.bases.insert(name.to_string(), id);
+        return id;
+    }
+
+    fn u8_type_id(&mut self) -> u32 {
+        self.base_type_id("__u8", 1, parser::BaseTypeEncoding::Unsigned)
+    }
+
+    fn pointer_type_id(&mut self, pointee: u32) -> u32 {
+        if let Some(&id) = self.pointers.get(&pointee) {
+            return id;
+        }
+        let id = self.add_btf_type("", btf_info(BTF_KIND_PTR, false, 0), pointee);
+        self.pointers.insert(pointee, id);
+        return id;
+    }
+
+    fn qualified_type_id(&mut self, kind: u32, ty: u32) -> u32 {
+        if let Some(&id) = self.qualifiers.get(&(kind, ty)) {
+            return id;
+        }
+        let id = self.add_btf_type("", btf_info(kind, false, 0), ty);
+        self.qualifiers.insert((kind, ty), id);
+        return id;
+    }
+
+    fn array_type_id(&mut self, elem: u32, count: u64) -> u32 {
+        if let Some(&id) = self.arrays.get(&(elem, count)) {
+            return id;
+        }
+        // 与内核一致, 使用 __ARRAY_SIZE_TYPE__ 作为 index type.
+        let index_type =
+            self.base_type_id("__ARRAY_SIZE_TYPE__", 4, parser::BaseTypeEncoding::Unsigned);
+        let id = self.add_btf_type("", btf_info(BTF_KIND_ARRAY, false, 0), 0);
+        self.push_u32(elem);
+        self.push_u32(index_type);
+        self.push_u32(count as u32);
+        self.arrays.insert((elem, count), id);
+        return id;
+    }
+
+    // Struct/Union 理论上总是在被引用之前定义的, 若不是, 则使用 BTF_KIND_FWD.
+    fn type_id(&mut self, ty: &TypeRef) -> u32 {
+        match ty {
+            TypeRef::Base {
+                name,
+                size,
+                encoding,
+            } => self.base_type_id(name, *size, *encoding),
+            TypeRef::Struct(ident) | TypeRef::Union(ident) => {
+                if let Some(&id) = self.idents.get(ident) {
+                    return id;
+                }
+                let is_union = matches!(ty, TypeRef::Union(_));
+                let id = self.add_btf_type(ident, btf_info(BTF_KIND_FWD, is_union, 0), 0);
+                self.idents.insert(ident.clone(), id);
+                id
+            }
+            TypeRef::Alias(ident) | TypeRef::Typedef { ident, .. } => {
+                let Some(&id) = self.idents.get(ident) else {
+                    warn!("BtfPrinter: unknown alias. ident={}", ident);
+                    return BTF_VOID;
+                };
+                id
+            }
+            TypeRef::Pointer(None) => self.pointer_type_id(BTF_VOID),
+            TypeRef::Pointer(Some(tyinfo)) => {
+                let pointee = self.type_id(&tyinfo.ty);
+                self.pointer_type_id(pointee)
+            }
+            TypeRef::Qualified { qualifier, real } => {
+                let kind = match qualifier {
+                    Qualifier::Const => BTF_KIND_CONST,
+                    Qualifier::Volatile => BTF_KIND_VOLATILE,
+                };
+                let real = self.type_id(&real.ty);
+                self.qualified_type_id(kind, real)
+            }
+        }
+    }
+
+    fn member_type_id(&mut self, member: &Member) -> u32 {
+        match member.tyinfo() {
+            Some(tyinfo) => self.type_id(&tyinfo.ty),
+            None => {
+                let u8_type = self.u8_type_id();
+                self.array_type_id(u8_type, member.len)
+            }
+        }
+    }
+
+    fn write_btf(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&BTF_MAGIC.to_le_bytes())?;
+        out.write_all(&[BTF_VERSION, 0])?;
+        out.write_all(&BTF_HEADER_LEN.to_le_bytes())?;
+        // type_off, type_len, str_off, str_len. offset 均相对于 header 结尾.
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&(self.types.len() as u32).to_le_bytes())?;
+        out.write_all(&(self.types.len() as u32).to_le_bytes())?;
+        out.write_all(&(self.strings.len() as u32).to_le_bytes())?;
+        out.write_all(&self.types)?;
+        out.write_all(&self.strings)
+    }
+
+    // 最小的 ELF64 relocatable 文件, 只包含 .BTF, .shstrtab 两个 section.
+    fn write_elf(&self, btf: &[u8], out: &mut impl Write) -> io::Result<()> {
+        const EHDR_SIZE: u64 = 64;
+        const SHDR_SIZE: u64 = 64;
+        const EM_BPF: u16 = 247;
+        let shstrtab = b"\0.BTF\0.shstrtab\0";
+        let btf_off = EHDR_SIZE;
+        let shstrtab_off = btf_off + btf.len() as u64;
+        let shdr_off = (shstrtab_off + shstrtab.len() as u64 + 7) / 8 * 8;
+
+        let mut ehdr = Vec::<u8>::with_capacity(EHDR_SIZE as usize);
+        // EI_CLASS=ELFCLASS64, EI_DATA=ELFDATA2LSB, EI_VERSION=EV_CURRENT.
+        ehdr.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        ehdr.extend_from_slice(&[0; 8]);
+        ehdr.extend_from_slice(&1u16.to_le_bytes()); // e_type=ET_REL
+        ehdr.extend_from_slice(&EM_BPF.to_le_bytes());
+        ehdr.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        ehdr.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+        ehdr.extend_from_slice(&0u64.to_le_bytes()); // e_phoff
+        ehdr.extend_from_slice(&shdr_off.to_le_bytes());
+        ehdr.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        ehdr.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend_from_slice(&0u16.to_le_bytes()); // e_phentsize
+        ehdr.extend_from_slice(&0u16.to_le_bytes()); // e_phnum
+        ehdr.extend_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend_from_slice(&3u16.to_le_bytes()); // e_shnum
+        ehdr.extend_from_slice(&2u16.to_le_bytes()); // e_shstrndx
+        debug_assert_eq!(ehdr.len() as u64, EHDR_SIZE);
+        out.write_all(&ehdr)?;
+        out.write_all(btf)?;
+        out.write_all(shstrtab)?;
+        let padding = shdr_off - shstrtab_off - shstrtab.len() as u64;
+        out.write_all(&vec![0u8; padding as usize])?;
+
+        let mut write_shdr =
+            |name: u32, ty: u32, off: u64, size: u64, align: u64| -> io::Result<()> {
+                let mut shdr = Vec::<u8>::with_capacity(SHDR_SIZE as usize);
+                shdr.extend_from_slice(&name.to_le_bytes());
+                shdr.extend_from_slice(&ty.to_le_bytes());
+                shdr.extend_from_slice(&0u64.to_le_bytes()); // sh_flags
+                shdr.extend_from_slice(&0u64.to_le_bytes()); // sh_addr
+                shdr.extend_from_slice(&off.to_le_bytes());
+                shdr.extend_from_slice(&size.to_le_bytes());
+                shdr.extend_from_slice(&0u32.to_le_bytes()); // sh_link
+                shdr.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+                shdr.extend_from_slice(&align.to_le_bytes());
+                shdr.extend_from_slice(&0u64.to_le_bytes()); // sh_entsize
+                out.write_all(&shdr)
+            };
+        write_shdr(0, 0, 0, 0, 0)?;
+        // 1 = SHT_PROGBITS, 3 = SHT_STRTAB.
+        write_shdr(1, 1, btf_off, btf.len() as u64, 4)?;
+        write_shdr(6, 3, shstrtab_off, shstrtab.len() as u64, 1)?;
+        return Ok(());
+    }
+}
+
+impl Backend for BtfPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        // kind_flag 为 true 时, member offset 高 8 位为 bitfield size, 低 24 位为 bit offset.
+        let mut kind_flag = false;
+        let mut members = Vec::with_capacity(record.members.len());
+        for member in record.members {
+            if let MemberKind::Bitfield(bitfields) = &member.kind {
+                kind_flag = true;
+                for bitfield in bitfields {
+                    let name_off = self.add_string(&bitfield.name);
+                    let type_id = self.type_id(&bitfield.tyinfo.ty);
+                    let bit_off = member.off * 8 + bitfield.bit_off;
+                    members.push((name_off, type_id, (bitfield.bit_size << 24) | bit_off));
+                }
+                continue;
+            }
+            let name_off = self.add_string(&member.field_name);
+            let type_id = self.member_type_id(member);
+            members.push((name_off, type_id, member.off * 8));
+        }
+        let kind = match record.kind {
+            RecordKind::Struct => BTF_KIND_STRUCT,
+            RecordKind::Union => BTF_KIND_UNION,
+        };
+        let info = btf_info(kind, kind_flag, members.len());
+        let id = self.add_btf_type(record.ident, info, record.packed_size as u32);
+        for (name_off, type_id, bit_off) in members {
+            self.push_u32(name_off);
+            self.push_u32(type_id);
+            self.push_u32(bit_off as u32);
+        }
+        self.idents.insert(record.ident.to_string(), id);
+        return Ok(());
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        let elem = self.type_id(array.elem);
+        let array_id = self.array_type_id(elem, array.count);
+        let id = self.add_btf_type(array.ident, btf_info(BTF_KIND_TYPEDEF, false, 0), array_id);
+        self.idents.insert(array.ident.to_string(), id);
+        return Ok(());
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        let real = self.type_id(typedef.real);
+        let id = self.add_btf_type(typedef.ident, btf_info(BTF_KIND_TYPEDEF, false, 0), real);
+        self.idents.insert(typedef.ident.to_string(), id);
+        return Ok(());
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        // 先计算出所有的 type id, 此时可能会插入新的类型.
+        let ret = func.ret.map_or(BTF_VOID, |v| self.type_id(v));
+        let mut params: Vec<u32> = func.params.iter().map(|v| self.type_id(v)).collect();
+        // 参见内核文档, 最后一个参数为 {0, 0} 意味着 `...`.
+        if func.variadic {
+            params.push(BTF_VOID);
+        }
+        let proto = self.add_btf_type("", btf_info(BTF_KIND_FUNC_PROTO, false, params.len()), ret);
+        for param in params {
+            self.push_u32(0);
+            self.push_u32(param);
+        }
+        let pointer = self.pointer_type_id(proto);
+        let id = self.add_btf_type(func.ident, btf_info(BTF_KIND_TYPEDEF, false, 0), pointer);
+        self.idents.insert(func.ident.to_string(), id);
+        return Ok(());
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        let ident = enum_def.ident;
+        let size = enum_def.size;
+        let is_signed = matches!(
+            enum_def.repr,
+            TypeRef::Base {
+                encoding: parser::BaseTypeEncoding::Signed,
+                ..
+            }
+        );
+        let mut items = Vec::with_capacity(enum_def.enumerators.len());
+        for enum_item in enum_def.enumerators {
+            // 与 C 头文件一致使用带前缀的名字, 这样同一个 bpftrace 脚本在两种方式下都能使用.
+            let val = enum_item.value.unwrap_or(0) as u64;
+            items.push((self.add_string(&enum_item.ident), val));
+        }
+        // 与 C 中 `typedef __s32 Foo;` 对应, 这里是 `typedef enum Foo Foo;`.
+        let enum_id = if size == 8 {
+            let id = self.add_btf_type(ident, btf_info(BTF_KIND_ENUM64, is_signed, items.len()), 8);
+            for (name_off, val) in items {
+                self.push_u32(name_off);
+                self.push_u32(val as u32);
+                self.push_u32((val >> 32) as u32);
+            }
+            id
+        } else {
+            let id = self.add_btf_type(
+                ident,
+                btf_info(BTF_KIND_ENUM, is_signed, items.len()),
+                size as u32,
+            );
+            for (name_off, val) in items {
+                self.push_u32(name_off);
+                self.push_u32(val as u32);
+            }
+            id
+        };
+        let id = self.add_btf_type(ident, btf_info(BTF_KIND_TYPEDEF, false, 0), enum_id);
+        self.idents.insert(ident.to_string(), id);
+        return Ok(());
+    }
+
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()> {
+        // 即 BTF_KIND_FWD, 之后 add_record 会使用真正的定义.
+        self.type_id(ty);
+        return Ok(());
+    }
+
+    fn add_size_check(&mut self, ty: &TypeRef, _size: u64) -> io::Result<()> {
+        // BTF 没有校验这一说, 这里仅确保 base type 等类型按照引用顺序出现.
+        self.type_id(ty);
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let mut btf = Vec::<u8>::new();
+        self.write_btf(&mut btf)?;
+        std::fs::write(self.btf_path(), &btf)?;
+        if self.elf {
+            let mut elf = Vec::<u8>::new();
+            self.write_elf(&btf, &mut elf)?;
+            std::fs::write(format!("{}.btf.o", self.path), &elf)?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,535 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::{
+    ArrayDef, Backend, EnumDef, FuncPtrDef, Member, MemberKind, Record, RecordKind, TypeRef,
+    TypedefDef, DISCRIMINANT_NAME,
+};
+
+// C/C++ 中合法的标识符在 Go 中可能是关键字, 此时加上 `_` 后缀. 仅用于包名, 其他标识符都会通过 exported_ident 导出.
+fn go_ident(ident: &str) -> Cow<'_, str> {
+    match ident {
+        "break" | "case" | "chan" | "const" | "continue" | "default" | "defer" | "else"
+        | "fallthrough" | "for" | "func" | "go" | "goto" | "if" | "import" | "interface"
+        | "map" | "package" | "range" | "return" | "select" | "struct" | "switch" | "type"
+        | "var" => Cow::Owned(format!("{}_", ident)),
+        _ => Cow::Borrowed(ident),
+    }
+}
+
+// 首字母大写使得标识符在包外可见, 与 `go tool cgo -godefs` 一致, 不以字母开头时加上 `X` 前缀, 比如 `X__pad`.
+// 大写之后可能与已有的标识符冲突, 此时加上 `_` 后缀, used 为当前作用域中已有的标识符.
+fn exported_ident(ident: &str, used: &mut HashSet<String>) -> String {
+    let mut chars = ident.chars();
+    let mut name = match chars.next() {
+        Some(ch) if ch.is_uppercase() => ident.to_string(),
+        Some(ch) if ch.is_alphabetic() => ch.to_uppercase().chain(chars).collect(),
+        _ => format!("X{}", ident),
+    };
+    while !used.insert(name.clone()) {
+        name.push('_');
+    }
+    return name;
+}
+
+// 与 rust.rs 一致, bool 也使用整数表示.
+fn base_type_name(size: u64, encoding: parser::BaseTypeEncoding) -> Cow<'static, str> {
+    use parser::BaseTypeEncoding::*;
+    let name = match (encoding, size) {
+        (Signed | SignedChar, 1) => "int8",
+        (Signed | SignedChar, 2) => "int16",
+        (Signed | SignedChar, 4) => "int32",
+        (Signed | SignedChar, 8) => "int64",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 1) => "uint8",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 2) => "uint16",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 4) => "uint32",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 8) => "uint64",
+        (Float, 4) => "float32",
+        (Float, 8) => "float64",
+        // 比如 long double, __int128.
+        _ => return Cow::Owned(format!("[{}]byte", size)),
+    };
+    Cow::Borrowed(name)
+}
+
+// `[0]T` 不占空间, 仅用来使 union 对齐.
+fn align_type_name(align: u64) -> Option<&'static str> {
+    match align {
+        2 => Some("uint16"),
+        4 => Some("uint32"),
+        8 => Some("uint64"),
+        _ => None,
+    }
+}
+
+// Go 中的类型.
+#[derive(Clone)]
+struct GoType {
+    name: String,
+    size: u64,
+    align: u64,
+}
+
+impl GoType {
+    fn bytes(size: u64) -> GoType {
+        GoType {
+            name: format!("[{}]byte", size),
+            size,
+            align: 1,
+        }
+    }
+}
+
+enum Line {
+    // `_ [N]byte`.
+    Padding(u64),
+    Field {
+        name: String,
+        off: u64,
+        ty: GoType,
+        comments: Vec<String>,
+    },
+    // 无法输出的字段, 仅保留注释.
+    Omitted(Vec<String>),
+}
+
+// 与 gofmt 一致, 连续的若干行中除最后一列之外的每一列都使用空格对齐, 注释行 (即 Err) 会打断对齐.
+fn align_rows(rows: &[Result<Vec<String>, String>], indent: &str, out: &mut Vec<String>) {
+    let mut start = 0;
+    while start < rows.len() {
+        if let Err(comment) = &rows[start] {
+            out.push(format!("{}// {}", indent, comment));
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        let mut widths = Vec::<usize>::new();
+        while let Some(Ok(row)) = rows.get(end) {
+            widths.resize(std::cmp::max(widths.len(), row.len()), 0);
+            for (idx, cell) in row.iter().enumerate() {
+                widths[idx] = std::cmp::max(widths[idx], cell.chars().count());
+            }
+            end += 1;
+        }
+        for row in rows[start..end].iter().flatten() {
+            let mut line = indent.to_string();
+            for (idx, cell) in row.iter().enumerate() {
+                line.push_str(cell);
+                if idx + 1 < row.len() {
+                    let pad = widths[idx] - cell.chars().count() + 1;
+                    line.extend(std::iter::repeat_n(' ', pad));
+                }
+            }
+            out.push(line);
+        }
+        start = end;
+    }
+}
+
+// Go 没有 packed, 这里按照 Go 的对齐规则排列字段, 通过 `_ [N]byte` 使每个字段都位于 dwarf 中记录的位置.
+// 对于无法对齐或者放不下的字段, 比如 tail padding 被复用的情况, 使用 `[N]byte` 代替, 并在注释中给出原类型.
+// union 被翻译为仅包含 `_` 字段的 struct, 各个成员通过同名方法访问. 类型, 字段, 方法以及常量都会导出.
+// 生成的 `_test.go` 会像 .c 那样校验每个字段的 offset 与 size.
+pub(crate) struct GoPrinter {
+    path: String,
+    package: String,
+    defs: Vec<String>,
+    checks: Vec<(String, u64)>,
+    // 需要 import "unsafe", 即存在 union.
+    use_unsafe: bool,
+    // alloc_ident 分配的标识符 --> Go 类型.
+    types: HashMap<String, GoType>,
+    // 包中已经使用的标识符, 即类型与常量.
+    idents: HashSet<String>,
+}
+
+impl GoPrinter {
+    pub(crate) fn try_open(path: &str) -> io::Result<GoPrinter> {
+        // 提前试一下能不能创建, 避免最后 finish 时才发现路径有问题.
+        std::fs::File::create(format!("{}.go", path))?;
+        let stem = std::path::Path::new(path)
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut package: String = stem
+            .chars()
+            .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+            .collect();
+        if !package.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+            package.insert_str(0, "clayout");
+        }
+        Ok(GoPrinter {
+            path: path.to_string(),
+            package: go_ident(&package).into_owned(),
+            defs: Vec::new(),
+            checks: Vec::new(),
+            use_unsafe: false,
+            types: HashMap::new(),
+            // _test.go 中定义的函数.
+            idents: HashSet::from(["TestClayoutLayout".to_string()]),
+        })
+    }
+
+    fn go_type(&self, ty: &TypeRef) -> GoType {
+        match ty {
+            TypeRef::Base { size, encoding, .. } => {
+                let name = base_type_name(*size, *encoding);
+                let align = if name.starts_with('[') { 1 } else { *size };
+                GoType {
+                    name: name.into_owned(),
+                    size: *size,
+                    align,
+                }
+            }
+            TypeRef::Struct(ident)
+            | TypeRef::Union(ident)
+            | TypeRef::Alias(ident)
+            | TypeRef::Typedef { ident, .. } => {
+                match self.types.get(ident) {
+                    Some(ty) => ty.clone(),
+                    // 不会发生, 成员的类型总是先于其所在的 record 定义.
+                    None => GoType::bytes(0),
+                }
+            }
+            // 离线解析时指针的值只是一个地址.
+            TypeRef::Pointer(_) => GoType {
+                name: "uintptr".to_string(),
+                size: 8,
+                align: 8,
+            },
+            // Go 中没有 const/volatile.
+            TypeRef::Qualified { real, .. } => self.go_type(&real.ty),
+        }
+    }
+
+    fn member_comments(member: &Member, comments: &mut Vec<String>) {
+        if let MemberKind::Bitfield(bitfields) = &member.kind {
+            for bitfield in bitfields {
+                comments.push(format!(
+                    "{}: {} bit_off={} bit_size={}",
+                    bitfield.name,
+                    bitfield.tyinfo.ty.c_name(),
+                    bitfield.bit_off,
+                    bitfield.bit_size
+                ));
+            }
+        }
+        if let MemberKind::Variant { discr_value, .. } = &member.kind {
+            comments.push(match discr_value {
+                Some(v) => format!("{} == {}", DISCRIMINANT_NAME, v),
+                None => "otherwise".to_string(),
+            });
+        }
+    }
+
+    // 对齐超过 max_align 的字段也会使用 `[N]byte` 代替, 返回值中的 u64 为 struct 的对齐.
+    fn struct_lines(&self, record: &Record, max_align: u64) -> (Vec<Line>, u64) {
+        let members: Vec<&Member> = record.members.iter().filter(|m| !m.is_padding()).collect();
+        let mut lines = Vec::new();
+        let mut struct_align = 1;
+        let mut next_off = 0;
+        let mut names = HashSet::new();
+        for (idx, member) in members.iter().enumerate() {
+            let slot_end = members.get(idx + 1).map_or(record.size, |m| m.off);
+            let mut comments = Vec::new();
+            Self::member_comments(member, &mut comments);
+            let ty = match member.tyinfo() {
+                Some(tyinfo) => {
+                    let ty = self.go_type(&tyinfo.ty);
+                    if ty.align <= max_align
+                        && member.off.is_multiple_of(ty.align)
+                        && member.off + ty.size <= slot_end
+                    {
+                        ty
+                    } else {
+                        comments.push(format!("{}: {}", member.field_name, tyinfo.ty.c_name()));
+                        GoType::bytes(member.len)
+                    }
+                }
+                None => GoType::bytes(member.len),
+            };
+            if ty.size == 0 && member.off >= record.size {
+                // Go 会在以 0 大小字段结尾的 struct 末尾加上 padding, 比如 C 中的 flexible array member.
+                comments.push(format!(
+                    "{}: {}, zero size at the end",
+                    member.field_name, ty.name
+                ));
+                lines.push(Line::Omitted(comments));
+                continue;
+            }
+            if member.off > next_off {
+                lines.push(Line::Padding(member.off - next_off));
+            }
+            next_off = member.off + ty.size;
+            struct_align = std::cmp::max(struct_align, ty.align);
+            lines.push(Line::Field {
+                name: exported_ident(&member.field_name, &mut names),
+                off: member.off,
+                ty,
+                comments,
+            });
+        }
+        if record.size > next_off {
+            lines.push(Line::Padding(record.size - next_off));
+        }
+        return (lines, struct_align);
+    }
+
+    fn add_struct(&mut self, record: &Record, ident: &str, def: &mut Vec<String>) -> GoType {
+        let (mut lines, mut align) = self.struct_lines(record, 8);
+        if !record.size.is_multiple_of(align) {
+            // 比如 `__attribute__((packed))` 的 struct, 此时降低对齐使得 Go 中的 size 与 dwarf 一致.
+            let max_align = 1 << record.size.trailing_zeros().min(3);
+            (lines, align) = self.struct_lines(record, max_align);
+        }
+        let mut rows = Vec::new();
+        for line in lines {
+            match line {
+                Line::Padding(size) => {
+                    rows.push(Ok(vec!["_".to_string(), format!("[{}]byte", size)]))
+                }
+                Line::Field {
+                    name,
+                    off,
+                    ty,
+                    comments,
+                } => {
+                    rows.extend(comments.into_iter().map(Err));
+                    let expr = |f: &str| format!("unsafe.{}({}{{}}.{})", f, ident, name);
+                    self.checks.push((expr("Offsetof"), off));
+                    self.checks.push((expr("Sizeof"), ty.size));
+                    rows.push(Ok(vec![name, ty.name]));
+                }
+                Line::Omitted(comments) => rows.extend(comments.into_iter().map(Err)),
+            }
+        }
+        def.push(format!("type {} struct {{", ident));
+        align_rows(&rows, "\t", def);
+        def.push("}".to_string());
+        return GoType {
+            name: ident.to_string(),
+            size: record.size,
+            align,
+        };
+    }
+
+    fn add_union(&mut self, record: &Record, ident: &str, def: &mut Vec<String>) -> GoType {
+        let mut accessors = Vec::new();
+        let mut align = 1;
+        let mut names = HashSet::new();
+        for member in record.members {
+            let Some(tyinfo) = member.tyinfo() else {
+                continue;
+            };
+            let ty = self.go_type(&tyinfo.ty);
+            if member.off != 0 || ty.size > record.size {
+                continue;
+            }
+            let mut comments = Vec::new();
+            Self::member_comments(member, &mut comments);
+            align = std::cmp::max(align, ty.align);
+            let name = exported_ident(&member.field_name, &mut names);
+            accessors.push((name, ty, comments));
+        }
+        while !record.size.is_multiple_of(align) {
+            align /= 2;
+        }
+        accessors.retain(|(_, ty, _)| align.is_multiple_of(ty.align));
+
+        let mut rows = Vec::new();
+        if let Some(align_ty) = align_type_name(align) {
+            rows.push(Ok(vec!["_".to_string(), format!("[0]{}", align_ty)]));
+        }
+        rows.push(Ok(vec!["_".to_string(), format!("[{}]byte", record.size)]));
+        def.push(format!("type {} struct {{", ident));
+        align_rows(&rows, "\t", def);
+        def.push("}".to_string());
+        for (name, ty, comments) in accessors {
+            def.push(String::new());
+            def.extend(comments.iter().map(|v| format!("// {}", v)));
+            def.push(format!(
+                "func (u *{}) {}() *{} {{ return (*{})(unsafe.Pointer(u)) }}",
+                ident, name, ty.name, ty.name
+            ));
+            self.use_unsafe = true;
+        }
+        return GoType {
+            name: ident.to_string(),
+            size: record.size,
+            align,
+        };
+    }
+}
+
+impl Backend for GoPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        let ident = exported_ident(record.ident, &mut self.idents);
+        let mut def = Vec::new();
+        def.push(format!(
+            "// tyname={} tyidx={:?}",
+            record.tyname, record.tyidx
+        ));
+        let ty = match record.kind {
+            RecordKind::Struct => self.add_struct(record, &ident, &mut def),
+            RecordKind::Union => self.add_union(record, &ident, &mut def),
+        };
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ident), ty.size));
+        self.types.insert(record.ident.to_string(), ty);
+        self.defs.push(def.join("\n"));
+        Ok(())
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        let ident = exported_ident(array.ident, &mut self.idents);
+        let elem = self.go_type(array.elem);
+        self.defs
+            .push(format!("type {} [{}]{}", ident, array.count, elem.name));
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ident), array.size));
+        self.types.insert(
+            array.ident.to_string(),
+            GoType {
+                name: ident,
+                size: elem.size * array.count,
+                align: elem.align,
+            },
+        );
+        Ok(())
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        let ident = exported_ident(typedef.ident, &mut self.idents);
+        let real = self.go_type(typedef.real);
+        self.defs.push(format!("type {} = {}", ident, real.name));
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ident), typedef.size));
+        self.types.insert(
+            typedef.ident.to_string(),
+            GoType {
+                name: ident,
+                ..real
+            },
+        );
+        Ok(())
+    }
+
+    // 与指针一样只保存地址.
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        let ident = exported_ident(func.ident, &mut self.idents);
+        self.defs.push(format!("type {} = uintptr", ident));
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ident), func.size));
+        self.types.insert(
+            func.ident.to_string(),
+            GoType {
+                name: ident,
+                size: func.size,
+                align: func.size,
+            },
+        );
+        Ok(())
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        let ident = exported_ident(enum_def.ident, &mut self.idents);
+        let repr = self.go_type(enum_def.repr);
+        let mut def = Vec::new();
+        def.push(format!(
+            "// tyname={} tyidx={:?}",
+            enum_def.tyname, enum_def.tyidx
+        ));
+        def.push(format!("type {} {}", ident, repr.name));
+        // 与 C 一致, 常量名为 EnumItem::ident.
+        let mut rows = Vec::new();
+        for v in enum_def.enumerators {
+            let Some(value) = v.value else {
+                continue;
+            };
+            rows.push(Ok(vec![
+                exported_ident(&v.ident, &mut self.idents),
+                ident.clone(),
+                format!("= {}", value),
+            ]));
+        }
+        if !rows.is_empty() {
+            def.push(String::new());
+            def.push("const (".to_string());
+            align_rows(&rows, "\t", &mut def);
+            def.push(")".to_string());
+        }
+        self.defs.push(def.join("\n"));
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ident), enum_def.size));
+        self.types.insert(
+            enum_def.ident.to_string(),
+            GoType {
+                name: ident,
+                size: repr.size,
+                align: repr.align,
+            },
+        );
+        Ok(())
+    }
+
+    // Go 中类型定义的顺序无关紧要.
+    fn add_forward_decl(&mut self, _ty: &TypeRef) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()> {
+        let ty = self.go_type(ty);
+        self.checks
+            .push((format!("unsafe.Sizeof(*new({}))", ty.name), size));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let mut file = std::fs::File::create(format!("{}.go", self.path))?;
+        writeln!(file, "// Code generated by hidva/clayout. DO NOT EDIT.")?;
+        writeln!(file, "// 大吉大利!")?;
+        writeln!(file)?;
+        writeln!(file, "package {}", self.package)?;
+        if self.use_unsafe {
+            writeln!(file)?;
+            writeln!(file, "import \"unsafe\"")?;
+        }
+        for def in &self.defs {
+            writeln!(file)?;
+            writeln!(file, "{}", def)?;
+        }
+        file.flush()?;
+
+        let mut file = std::fs::File::create(format!("{}_test.go", self.path))?;
+        writeln!(file, "// Code generated by hidva/clayout. DO NOT EDIT.")?;
+        writeln!(file)?;
+        writeln!(file, "package {}", self.package)?;
+        writeln!(file)?;
+        writeln!(file, "import (")?;
+        writeln!(file, "\t\"testing\"")?;
+        if !self.checks.is_empty() {
+            writeln!(file, "\t\"unsafe\"")?;
+        }
+        writeln!(file, ")")?;
+        writeln!(file)?;
+        writeln!(
+            file,
+            "func clayoutAssertEq(t *testing.T, expr string, actual, expect uintptr) {{"
+        )?;
+        writeln!(file, "\tif actual != expect {{")?;
+        writeln!(
+            file,
+            "\t\tt.Errorf(\"ASSERT FAILED! actual: %s, which is %d; expect: %d\", expr, actual, expect)"
+        )?;
+        writeln!(file, "\t}}")?;
+        writeln!(file, "}}")?;
+        writeln!(file)?;
+        writeln!(file, "func TestClayoutLayout(t *testing.T) {{")?;
+        for (expr, val) in &self.checks {
+            writeln!(file, "\tclayoutAssertEq(t, {:?}, {}, {})", expr, expr, val)?;
+        }
+        writeln!(file, "}}")?;
+        file.flush()
+    }
+}
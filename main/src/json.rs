@@ -0,0 +1,354 @@
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::{
+    ArrayDef, Backend, EnumDef, FuncPtrDef, Member, MemberKind, Record, RecordKind, TypeIndex,
+    TypeRef, TypedefDef,
+};
+
+// 输出格式有任何不兼容的变更时都需要递增 JSON_VERSION, 新增字段不算不兼容.
+// 2: enumerator 的 name 不再为 null, value 按照 repr 的符号解释, 可能超出 i64.
+const JSON_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct JsonInput {
+    id: usize,
+    path: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JsonTypeRef {
+    Base {
+        name: String,
+        size: u64,
+        encoding: &'static str,
+    },
+    Struct {
+        ident: String,
+    },
+    Union {
+        ident: String,
+    },
+    Alias {
+        ident: String,
+    },
+    // pointee 为 null 意味着 `void*`.
+    Pointer {
+        pointee: Option<Box<JsonTypeRef>>,
+    },
+}
+
+#[derive(Serialize)]
+struct JsonBitfield {
+    name: String,
+    // 相对于 member offset 的偏移.
+    bit_offset: u64,
+    bit_size: u64,
+    #[serde(rename = "type")]
+    ty: JsonTypeRef,
+}
+
+#[derive(Serialize)]
+struct JsonMember {
+    name: String,
+    offset: u64,
+    size: u64,
+    kind: &'static str,
+    // padding/bitfield/placeholder 没有对应的类型, 为 null.
+    #[serde(rename = "type")]
+    ty: Option<JsonTypeRef>,
+    // 仅 bitfield 有.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bitfields: Option<Vec<JsonBitfield>>,
+    // 仅 variant 有, 为 null 意味着 discriminant 不匹配其他 variant 时选择该 variant.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discriminant_value: Option<Option<u64>>,
+}
+
+#[derive(Serialize)]
+struct JsonRecord {
+    ident: String,
+    name: String,
+    input: usize,
+    type_offset: Option<usize>,
+    size: u64,
+    packed_size: u64,
+    align: u64,
+    members: Vec<JsonMember>,
+}
+
+#[derive(Serialize)]
+struct JsonEnumerator {
+    name: String,
+    // C 头文件中的常量名.
+    ident: String,
+    value: Option<i128>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JsonType {
+    Struct(JsonRecord),
+    Union(JsonRecord),
+    Array {
+        ident: String,
+        input: usize,
+        type_offset: Option<usize>,
+        element: JsonTypeRef,
+        count: u64,
+        size: u64,
+    },
+    Enum {
+        ident: String,
+        name: String,
+        input: usize,
+        type_offset: Option<usize>,
+        repr: JsonTypeRef,
+        size: u64,
+        enumerators: Vec<JsonEnumerator>,
+    },
+    FuncPointer {
+        ident: String,
+        input: usize,
+        type_offset: Option<usize>,
+        // 为 null 意味着返回 void.
+        #[serde(rename = "return")]
+        ret: Option<JsonTypeRef>,
+        parameters: Vec<JsonTypeRef>,
+        variadic: bool,
+        size: u64,
+    },
+    // 仅 --typedefs 时出现.
+    Typedef {
+        ident: String,
+        name: String,
+        input: usize,
+        type_offset: Option<usize>,
+        #[serde(rename = "type")]
+        ty: JsonTypeRef,
+        size: u64,
+        packed_size: u64,
+    },
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    version: u32,
+    inputs: &'a [JsonInput],
+    types: &'a [JsonType],
+}
+
+fn encoding_name(encoding: parser::BaseTypeEncoding) -> &'static str {
+    use parser::BaseTypeEncoding::*;
+    match encoding {
+        Other => "other",
+        Boolean => "boolean",
+        Address => "address",
+        Signed => "signed",
+        SignedChar => "signed_char",
+        Unsigned => "unsigned",
+        UnsignedChar => "unsigned_char",
+        Float => "float",
+    }
+}
+
+fn json_type_ref(ty: &TypeRef) -> JsonTypeRef {
+    match ty {
+        TypeRef::Base {
+            name,
+            size,
+            encoding,
+        } => JsonTypeRef::Base {
+            name: name.clone(),
+            size: *size,
+            encoding: encoding_name(*encoding),
+        },
+        TypeRef::Struct(ident) => JsonTypeRef::Struct {
+            ident: ident.clone(),
+        },
+        TypeRef::Union(ident) => JsonTypeRef::Union {
+            ident: ident.clone(),
+        },
+        TypeRef::Alias(ident) | TypeRef::Typedef { ident, .. } => JsonTypeRef::Alias {
+            ident: ident.clone(),
+        },
+        // json 只描述 layout, const/volatile 与之无关.
+        TypeRef::Qualified { real, .. } => json_type_ref(&real.ty),
+        TypeRef::Pointer(tyinfo) => JsonTypeRef::Pointer {
+            pointee: tyinfo.as_ref().map(|v| Box::new(json_type_ref(&v.ty))),
+        },
+    }
+}
+
+fn json_member(member: &Member) -> JsonMember {
+    let kind = match member.kind {
+        MemberKind::Field(_) => "field",
+        MemberKind::Parent(_) => "parent",
+        MemberKind::Variant { .. } => "variant",
+        MemberKind::Padding => "padding",
+        MemberKind::Bitfield(_) => "bitfield",
+        MemberKind::Placeholder => "placeholder",
+    };
+    let bitfields = match &member.kind {
+        MemberKind::Bitfield(bitfields) => Some(
+            bitfields
+                .iter()
+                .map(|v| JsonBitfield {
+                    name: v.name.clone(),
+                    bit_offset: v.bit_off,
+                    bit_size: v.bit_size,
+                    ty: json_type_ref(&v.tyinfo.ty),
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+    let discriminant_value = match &member.kind {
+        MemberKind::Variant { discr_value, .. } => Some(*discr_value),
+        _ => None,
+    };
+    JsonMember {
+        name: member.field_name.clone(),
+        offset: member.off,
+        size: member.len,
+        kind,
+        ty: member.tyinfo().map(|v| json_type_ref(&v.ty)),
+        bitfields,
+        discriminant_value,
+    }
+}
+
+// 所有类型都先保存在内存中, finish 时一次性写入 `${out}.json`.
+// types 中类型的顺序与 C 头文件中的定义顺序一致, 即按值引用的类型总是在前面, 指针指向的 struct/union 可能在后面,
+// 对应于 C 头文件中的前向声明, 前向声明本身不会出现在 types 中.
+pub(crate) struct JsonPrinter {
+    file: std::fs::File,
+    inputs: Vec<JsonInput>,
+    types: Vec<JsonType>,
+}
+
+impl JsonPrinter {
+    pub(crate) fn try_open(path: &str, input_paths: &[String]) -> io::Result<JsonPrinter> {
+        let mut file_name = path.to_string();
+        file_name.push_str(".json");
+        let file = std::fs::File::create(file_name)?;
+        let inputs = input_paths
+            .iter()
+            .enumerate()
+            .map(|(id, path)| JsonInput {
+                id,
+                path: path.clone(),
+            })
+            .collect();
+        Ok(JsonPrinter {
+            file,
+            inputs,
+            types: Vec::new(),
+        })
+    }
+}
+
+fn type_offset(tyidx: TypeIndex) -> Option<usize> {
+    tyidx.typoff.get()
+}
+
+impl Backend for JsonPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        let json_record = JsonRecord {
+            ident: record.ident.to_string(),
+            name: record.tyname.clone(),
+            input: record.tyidx.input_id,
+            type_offset: type_offset(record.tyidx),
+            size: record.size,
+            packed_size: record.packed_size,
+            align: record.align,
+            members: record.members.iter().map(json_member).collect(),
+        };
+        self.types.push(match record.kind {
+            RecordKind::Struct => JsonType::Struct(json_record),
+            RecordKind::Union => JsonType::Union(json_record),
+        });
+        return Ok(());
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        self.types.push(JsonType::Array {
+            ident: array.ident.to_string(),
+            input: array.tyidx.input_id,
+            type_offset: type_offset(array.tyidx),
+            element: json_type_ref(array.elem),
+            count: array.count,
+            size: array.size,
+        });
+        return Ok(());
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        self.types.push(JsonType::Enum {
+            ident: enum_def.ident.to_string(),
+            name: enum_def.tyname.clone(),
+            input: enum_def.tyidx.input_id,
+            type_offset: type_offset(enum_def.tyidx),
+            repr: json_type_ref(enum_def.repr),
+            size: enum_def.size,
+            enumerators: enum_def
+                .enumerators
+                .iter()
+                .map(|v| JsonEnumerator {
+                    name: v.name.clone(),
+                    ident: v.ident.clone(),
+                    value: v.value,
+                })
+                .collect(),
+        });
+        return Ok(());
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        self.types.push(JsonType::Typedef {
+            ident: typedef.ident.to_string(),
+            name: typedef.tyname.clone(),
+            input: typedef.tyidx.input_id,
+            type_offset: type_offset(typedef.tyidx),
+            ty: json_type_ref(typedef.real),
+            size: typedef.size,
+            packed_size: typedef.packed_size,
+        });
+        return Ok(());
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        self.types.push(JsonType::FuncPointer {
+            ident: func.ident.to_string(),
+            input: func.tyidx.input_id,
+            type_offset: type_offset(func.tyidx),
+            ret: func.ret.map(json_type_ref),
+            parameters: func.params.iter().map(json_type_ref).collect(),
+            variadic: func.variadic,
+            size: func.size,
+        });
+        return Ok(());
+    }
+
+    // 之后 add_record 会给出定义.
+    fn add_forward_decl(&mut self, _ty: &TypeRef) -> io::Result<()> {
+        return Ok(());
+    }
+
+    // base type, pointer 的信息已经包含在 type ref 中了.
+    fn add_size_check(&mut self, _ty: &TypeRef, _size: u64) -> io::Result<()> {
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let output = JsonOutput {
+            version: JSON_VERSION,
+            inputs: &self.inputs,
+            types: &self.types,
+        };
+        serde_json::to_writer_pretty(&mut self.file, &output)?;
+        writeln!(self.file, "")?;
+        self.file.flush()
+    }
+}
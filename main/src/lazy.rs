@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+
+// --lazy 时各个 input 只解析了定义 dest 的 unit 及其依赖. 其中的声明可能定义在其他 input 中, 见 type_db,
+// 所以这里不断地让所有 input 加载定义了这些声明的 unit, 直至不再有新的 unit.
+pub(crate) fn resolve_declarations(inputs: &mut [parser::FileContext]) -> anyhow::Result<()> {
+    let mut scanned = vec![0; inputs.len()];
+    let mut defined = HashSet::new();
+    let mut declared = HashSet::new();
+    loop {
+        for (input, scanned) in inputs.iter().zip(scanned.iter_mut()) {
+            let units = input.file().units();
+            for ty in units[*scanned..].iter().flat_map(|v| v.types()) {
+                let is_declaration = match ty.kind() {
+                    parser::TypeKind::Struct(s) => s.is_declaration(),
+                    parser::TypeKind::Union(s) => s.is_declaration(),
+                    parser::TypeKind::Enumeration(s) => s.is_declaration(),
+                    _ => continue,
+                };
+                let Some(tyname) = parser::TypeName::try_from(ty) else {
+                    continue;
+                };
+                if tyname.is_anon() {
+                    continue;
+                }
+                if is_declaration {
+                    declared.insert(tyname.to_string());
+                } else {
+                    defined.insert(tyname.to_string());
+                }
+            }
+            *scanned = units.len();
+        }
+        // 在所有 input 中都找不到定义的声明会被 parser 记住, 再次加载时直接忽略.
+        let pending: Vec<String> = declared.difference(&defined).cloned().collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut loaded = 0;
+        for input in inputs.iter_mut() {
+            loaded += input.load_declarations(&pending)?;
+        }
+        if loaded == 0 {
+            return Ok(());
+        }
+    }
+}
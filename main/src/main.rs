@@ -1,11 +1,23 @@
-use anyhow::ensure;
 use clap::Parser;
 use log::{info, warn};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::{self, BufRead, Write};
 use std::rc::Rc;
-use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
+use std::time::Instant;
+
+mod bpftrace;
+mod btf;
+mod go;
+mod json;
+mod lazy;
+mod natural;
+mod offsetof;
+mod python;
+mod rust;
+mod select;
+mod vmlinux;
 
 const BITS_PER_BYTE: u64 = 8;
 
@@ -27,77 +39,186 @@ fn ident_part(name: &str) -> &str {
     return &name[0..bad_idx];
 }
 
-fn consume_ident_chars(out: &mut String, input: &mut impl Iterator<Item = char>) -> Option<char> {
-    while let Some(ch) = input.next() {
-        if is_ident_char(ch) {
-            out.push(ch);
-        } else {
-            return Some(ch);
-        }
-    }
-    return None;
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum Format {
+    /// `.h` with C types, `.c` that checks the layout at runtime
+    C,
+    /// `.rs` with `#[repr(C, packed)]` types, layout checked at compile time
+    Rust,
+    /// `.btf` with raw BTF, can be used by `BPFTRACE_BTF` or libbpf
+    Btf,
+    /// same as `btf`, plus a `.btf.o` ELF file which only contains a `.BTF` section
+    BtfElf,
+    /// `.json` which describes the layout of all emitted types, for scripts
+    Json,
+    /// `.go` with Go structs padded to the dwarf layout, and a `_test.go` that checks it
+    Go,
+    /// `.py` with `ctypes.Structure`/`ctypes.Union` types, layout checked when imported
+    Python,
+    /// `.vmlinux.h` for BPF CO-RE programs, natural layout with `preserve_access_index`
+    Vmlinux,
 }
 
-// '::std::_Rb_tree_key_compare<std::less<niagara::TabletEventListener *> >' -> ['std', '_Rb_tree_key_compare<std::less<niagara::TabletEventListener *> >'].
-fn parse_typename(input: &str) -> anyhow::Result<Vec<String>> {
-    let mut ret = Vec::<String>::new();
-    let mut add_part = |p: String| {
-        if !p.is_empty() {
-            ret.push(p);
-        }
-    };
-    let mut iter = input.chars();
-    loop {
-        let mut part = String::new();
-        let next_ch = consume_ident_chars(&mut part, &mut iter);
-        let Some(next_ch) = next_ch else {
-            add_part(part);
-            return Ok(ret);
-        };
-        if next_ch == ':' {
-            add_part(part);
-            let next_ch = iter.next();
-            ensure!(next_ch == Some(':'), "invalid input symbol");
-            continue;
-        }
-        part.push(next_ch);
-        while let Some(ch) = iter.next() {
-            part.push(ch);
-        }
-        add_part(part);
-        return Ok(ret);
-    }
+#[derive(clap::Subcommand)]
+enum Command {
+    /// print the offset, size and type of each step of a member path, such as 'S::a.b[3]->c'
+    Offsetof {
+        /// member path, offsets after '->' are relative to the pointer
+        path: String,
+    },
+    /// write a bpftrace script to '{out}.bt' which attaches a uprobe to a function and casts its
+    /// arguments, including 'this' and the hidden struct-return pointer, to the generated types
+    Bpftrace {
+        /// function name, such as 'ns::S::f', 'mycrate::module::func', or a linkage name
+        function: String,
+    },
 }
 
 #[derive(Parser)]
-#[clap(author, version, about)]
+#[clap(author, version, about, subcommand_negates_reqs = true)]
 struct Args {
     /// input so path, can specify more than once
-    #[arg(short = 'i')]
+    #[arg(short = 'i', global = true)]
     so_path: Vec<String>,
 
     /// input file path, each line represents a so path, can specify more than once
-    #[arg(short = 'I')]
+    #[arg(short = 'I', global = true)]
     so_file_path: Vec<String>,
 
-    /// output file path
-    #[arg(short)]
-    out_path: String,
-
-    /// type name, such as 'namespace1::namespace2::TypeName'
-    #[arg(value_parser=parse_typename)]
-    dest: Vec<Vec<String>>,
+    /// directory of separate debug files of stripped inputs, which are found by build id or
+    /// .gnu_debuglink, /usr/lib/debug is always searched, can specify more than once
+    #[arg(long, global = true)]
+    debug_dir: Vec<String>,
+
+    /// output file path, not needed by --dry-run
+    #[arg(short, required_unless_present = "dry_run")]
+    out_path: Option<String>,
+
+    /// output format, can specify more than once
+    #[arg(short, long, value_enum, default_value = "c")]
+    format: Vec<Format>,
+
+    /// print the matched types without generating anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// make the `c` format emit types with their natural layout and dwarf alignment instead of
+    /// packed ones, only types which can't be expressed this way, such as those whose tail padding
+    /// is reused, are still packed
+    #[arg(long)]
+    natural: bool,
+
+    /// keep typedefs of the source as typedefs, and `const`/`volatile` on members and pointers,
+    /// instead of replacing them with the underlying types
+    #[arg(long)]
+    typedefs: bool,
+
+    /// only parse the units which define the dest types and the types they depend on, found by
+    /// .debug_names, .gdb_index, or the type names of all units, instead of parsing all units,
+    /// not supported by subcommands. Without an index, reading the type names still walks every
+    /// entry unless the compiler emits DW_AT_sibling (GCC does, LLVM doesn't), so it mostly saves
+    /// memory
+    #[arg(long)]
+    lazy: bool,
+
+    /// print the time and peak memory of each phase, and the number of parsed units and types
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// type name, such as 'namespace1::namespace2::TypeName', 'ns::Foo<int, 3>', glob 'ns::*',
+    /// regex 're:^ns::.*Request$', prefixed by '!' to exclude
+    #[arg(value_parser=select::parse_selector)]
+    dest: Vec<select::Selector>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 impl Args {
-    fn is_dest(&self, tyn: &parser::TypeName) -> bool {
+    // 匹配任意一个非排除的选择器, 且不匹配任何排除的选择器.
+    fn is_dest(&self, tyn: &parser::TypeName, ty: &parser::Type, hash: &parser::FileHash) -> bool {
+        let mut selected = false;
         for d in &self.dest {
-            if tyn.ends_with(&d) {
-                return true;
+            if d.matches(tyn, ty, hash) {
+                if d.exclude {
+                    return false;
+                }
+                selected = true;
             }
         }
-        return false;
+        return selected;
+    }
+}
+
+// --lazy 时仅解析可能定义了 dest 的 unit, filter 拿到的是不带 namespace 的类型名.
+fn parse_input(args: &Args, path: String) -> anyhow::Result<parser::FileContext> {
+    info!("load so. path={}", &path);
+    if !args.lazy {
+        return Ok(parser::File::parse_with_debug_dirs(path, &args.debug_dir)?);
+    }
+    let mut filter = |name: &str| {
+        args.dest
+            .iter()
+            .any(|v| !v.exclude && v.may_match_name(name))
+    };
+    let file = parser::File::parse_lazy(path, &args.debug_dir, &mut filter)?;
+    return Ok(file);
+}
+
+// 进程目前为止的峰值内存, 单位 kB.
+fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|v| v.starts_with("VmHWM:"))?;
+    return line.split_whitespace().nth(1)?.parse().ok();
+}
+
+// -v 时向 stderr 输出当前阶段的耗时以及峰值内存, 并开始计时下一阶段.
+fn report_phase(args: &Args, phase: &str, started: &mut Instant) {
+    if args.verbose {
+        let peak = match peak_rss_kb() {
+            Some(v) => format!("{} kB", v),
+            None => "unknown".to_string(),
+        };
+        eprintln!(
+            "{}: {:.3}s, peak rss {}",
+            phase,
+            started.elapsed().as_secs_f64(),
+            peak
+        );
     }
+    *started = Instant::now();
+}
+
+// 在所有 CPU 上对 items 执行 f, 返回值的顺序与 items 一致, 所以结果与串行执行时相同.
+fn parallel_map<'a, T: Sync, R: Send>(items: &'a [T], f: impl Fn(&'a T) -> R + Sync) -> Vec<R> {
+    let threads = std::thread::available_parallelism().map_or(1, |v| v.get());
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut results = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Relaxed);
+            let Some(item) = items.get(i) else {
+                return results;
+            };
+            results.push((i, f(item)));
+        }
+    };
+    let mut results = std::thread::scope(|scope| {
+        // 当前线程也参与执行.
+        let workers: Vec<_> = (1..threads.min(items.len()))
+            .map(|_| scope.spawn(work))
+            .collect();
+        let mut results = work();
+        for worker in workers {
+            match worker.join() {
+                Ok(v) => results.extend(v),
+                Err(e) => std::panic::resume_unwind(e),
+            }
+        }
+        return results;
+    });
+    results.sort_unstable_by_key(|v| v.0);
+    return results.into_iter().map(|v| v.1).collect();
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<std::fs::File>>>
@@ -141,84 +262,291 @@ struct TypeIndex {
 // }
 #[derive(Debug)]
 struct TypeInfo {
-    // name 可以用在 C 语言作为变量类型名.
-    // 其格式必须满足 `[struct|union|enum] 标识符[*]*`.
-    name: String,
+    ty: TypeRef,
     // packed size 是使用 attribute packed 之后的 size,
     // size 为 dwarf 中记录的 type size.
     // 以 S1218 为例, packed_size = 9, size = 16.
     packed_size: u64,
     size: u64,
+    // 类型的对齐, dwarf 中有 DW_AT_alignment 时以其为准, 否则与 C 一样根据成员计算. 以 S1218 为例, align = 8.
+    align: u64,
 }
 
 impl TypeInfo {
     fn ident(&self) -> &str {
-        ident_part(self.name.split_whitespace().last().unwrap())
+        self.ty.ident()
     }
 }
 
-type ProcessState = HashMap<TypeIndex, Option<Rc<TypeInfo>>>;
+// TypeRef 描述了如何引用一个类型, 与输出语言无关. 各个 Backend 负责将其翻译为对应语言中的类型名.
+// Struct/Union/Alias 中存放的都是 alloc_ident 分配的标识符.
+#[derive(Debug, Clone)]
+enum TypeRef {
+    // name 为 dwarf 中记录的名字, 比如 `long unsigned int`.
+    Base {
+        name: String,
+        size: u64,
+        encoding: parser::BaseTypeEncoding,
+    },
+    Struct(String),
+    Union(String),
+    // 通过 typedef 定义的类型, 比如 array, enum.
+    Alias(String),
+    // None 意味着 `void*`.
+    Pointer(Option<Rc<TypeInfo>>),
+    // 仅 --typedefs 时出现, 源码中的 typedef, 比如 `typedef uint64_t LSN;`. layout 与 real 完全一致.
+    Typedef {
+        ident: String,
+        real: Rc<TypeInfo>,
+    },
+    // 仅 --typedefs 时出现, 带有 const/volatile 的类型. layout 与 real 完全一致.
+    Qualified {
+        qualifier: Qualifier,
+        real: Rc<TypeInfo>,
+    },
+}
 
-struct Printer {
-    h_file: std::fs::File,
-    c_file: std::fs::File,
-    used_idents: HashMap<String, u64>,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Qualifier {
+    Const,
+    Volatile,
 }
 
-impl Printer {
-    fn do_add_eq_assert(&mut self, expr: &str, size: u64) -> io::Result<()> {
-        writeln!(self.c_file, "  ZHANYI_HIDVA_ASSERT_EQ({}, {});", expr, size)
+impl Qualifier {
+    fn c_name(self) -> &'static str {
+        match self {
+            Qualifier::Const => "const",
+            Qualifier::Volatile => "volatile",
+        }
     }
 }
-impl Printer {
-    fn try_open(path: &str) -> io::Result<Printer> {
-        const ASSERT_EQ_DEF: &'static str = r###"
-#define ZHANYI_HIDVA_ASSERT_EQ(a, e) do {    \
-    int actual_size = (a);  \
-    int expect_size = (e);  \
-    if (actual_size != expect_size) {   \
-        fprintf(stderr, "ASSERT FAILED! actual: %s, which is %d; expect: %s, which is %d\n", #a, actual_size, #e, expect_size);    \
-        abort();    \
-    }   \
-} while(0)
-        "###;
-        let mut h_file_name = path.to_string();
-        h_file_name.push_str(".h");
-        let mut c_file_name = path.to_string();
-        c_file_name.push_str(".c");
-        let mut h_file = std::fs::File::create(&h_file_name)?;
-        let mut c_file = std::fs::File::create(c_file_name)?;
-        writeln!(h_file, "// Generated by hidva/clayout! 大吉大利!")?;
-        writeln!(h_file, "#pragma once")?;
-        writeln!(h_file, "#include <linux/types.h>")?;
-        writeln!(c_file, "// Generated by hidva/clayout! 大吉大利!")?;
-        writeln!(c_file, "#include <stdio.h>")?;
-        writeln!(c_file, "#include <stdlib.h>")?;
-        writeln!(c_file, "#include \"{}\"", &h_file_name)?;
-        writeln!(c_file, "\n\n\n")?;
-        writeln!(c_file, "{}", ASSERT_EQ_DEF)?;
-        writeln!(c_file, "\n\n\n")?;
-        writeln!(c_file, "int main() {{")?;
-        Ok(Printer {
-            h_file,
-            c_file,
-            used_idents: HashMap::new(),
-        })
+
+impl TypeRef {
+    fn ident(&self) -> &str {
+        match self {
+            TypeRef::Base { name, .. } => ident_part(name.split_whitespace().last().unwrap()),
+            TypeRef::Struct(ident)
+            | TypeRef::Union(ident)
+            | TypeRef::Alias(ident)
+            | TypeRef::Typedef { ident, .. } => ident,
+            TypeRef::Pointer(None) => "void",
+            TypeRef::Pointer(Some(tyinfo)) => tyinfo.ident(),
+            TypeRef::Qualified { real, .. } => real.ident(),
+        }
     }
 
-    fn add_eq_assert(&mut self, expr: &str, size: u64) -> io::Result<()> {
-        self.do_add_eq_assert(expr, size)?;
-        writeln!(self.c_file, "")
+    // 返回值可以用在 C 语言作为变量类型名.
+    // 其格式满足 `[const|volatile] [struct|union] 标识符[*]*`, 指针本身的 const/volatile 位于对应的 `*` 之后.
+    fn c_name(&self) -> String {
+        match self {
+            TypeRef::Base { name, .. } => name.clone(),
+            TypeRef::Struct(ident) => format!("struct {}", ident),
+            TypeRef::Union(ident) => format!("union {}", ident),
+            TypeRef::Alias(ident) | TypeRef::Typedef { ident, .. } => ident.clone(),
+            TypeRef::Pointer(None) => "void*".to_string(),
+            TypeRef::Pointer(Some(tyinfo)) => format!("{}*", tyinfo.ty.c_name()),
+            TypeRef::Qualified { qualifier, real } => {
+                if real.ty.is_pointer() {
+                    format!("{} {}", real.ty.c_name(), qualifier.c_name())
+                } else {
+                    format!("{} {}", qualifier.c_name(), real.ty.c_name())
+                }
+            }
+        }
     }
 
-    fn add_eq_asserts(&mut self, asserts: &[EqAssert]) -> io::Result<()> {
-        for eq_assert in asserts {
-            self.do_add_eq_assert(&eq_assert.expr, eq_assert.val)?;
+    fn is_pointer(&self) -> bool {
+        match self {
+            TypeRef::Pointer(_) => true,
+            TypeRef::Qualified { real, .. } => real.ty.is_pointer(),
+            _ => false,
+        }
+    }
+
+    fn unqualified(&self) -> &TypeRef {
+        match self {
+            TypeRef::Qualified { real, .. } => real.ty.unqualified(),
+            _ => self,
+        }
+    }
+
+    // 去掉 Typedef/Qualified, 返回其底层类型. 只关心 layout 的地方应该使用 strip 之后的类型.
+    fn strip(&self) -> &TypeRef {
+        match self {
+            TypeRef::Typedef { real, .. } | TypeRef::Qualified { real, .. } => real.ty.strip(),
+            _ => self,
+        }
+    }
+}
+
+struct ProcessState {
+    types: HashMap<TypeIndex, Option<Rc<TypeInfo>>>,
+    // 正在处理中的 struct/union, 即其成员还未处理完. 成员中的指针可能会指向这些类型, 比如
+    // `struct Node { Node* next; }`, 此时会提前为其分配标识符并输出前向声明, 详见 forward_decl.
+    pending: HashSet<TypeIndex>,
+    // 已经输出前向声明的 struct/union 的标识符, 定义时会使用同一个标识符, 详见 record_ident.
+    declared: HashMap<TypeIndex, String>,
+    // pending 不为空时, 指针指向的尚未处理的 struct/union 只会输出前向声明, 其定义推迟到 pending 为空之后输出.
+    deferred: VecDeque<TypeIndex>,
+    // 为 true 时保留 typedef 与 const/volatile, 详见 --typedefs.
+    typedefs: bool,
+    // 含有虚基类的类型作为基类子对象时的类型, 详见 process_non_virtual.
+    non_virtual: HashMap<TypeIndex, Option<Rc<TypeInfo>>>,
+    // vptr 的类型, 类型完整对象与其非虚部分共用, 详见 process_vtable.
+    vptrs: HashMap<TypeIndex, Option<Rc<TypeInfo>>>,
+    // 与子类共用 vptr 的 primary base 子对象的类型, key 为 (基类, vptr 所属的类型), 详见 process_primary_base.
+    primary_bases: HashMap<(TypeIndex, TypeIndex), Option<Rc<TypeInfo>>>,
+    // 函数指针 typedef, 签名相同的函数指针共用同一个 typedef, 详见 func_pointer_type_info.
+    func_pointers: HashMap<FuncPtrKey, Rc<TypeInfo>>,
+}
+
+impl ProcessState {
+    fn new(typedefs: bool) -> ProcessState {
+        ProcessState {
+            types: HashMap::new(),
+            pending: HashSet::new(),
+            declared: HashMap::new(),
+            deferred: VecDeque::new(),
+            typedefs,
+            non_virtual: HashMap::new(),
+            vptrs: HashMap::new(),
+            primary_bases: HashMap::new(),
+            func_pointers: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RecordKind {
+    Struct,
+    Union,
+}
+
+// Record 是一个 struct/union 的完整定义, members 最后一个元素 off + len 为 packed_size.
+struct Record<'a> {
+    kind: RecordKind,
+    ident: &'a str,
+    // tyname, tyidx 标识着 Record 对应着 dwarf 中的哪个类型, 主要用来生成注释.
+    tyname: String,
+    tyidx: TypeIndex,
+    members: &'a [Member],
+    packed_size: u64,
+    // dwarf 中记录的 size. 对于 `_Padded` 这种 clayout 自己生成的类型, 为 packed_size.
+    size: u64,
+    // 同 TypeInfo::align.
+    align: u64,
+}
+
+struct ArrayDef<'a> {
+    ident: &'a str,
+    tyidx: TypeIndex,
+    elem: &'a TypeRef,
+    count: u64,
+    // 整个数组的大小.
+    size: u64,
+}
+
+struct TypedefDef<'a> {
+    ident: &'a str,
+    tyname: String,
+    tyidx: TypeIndex,
+    real: &'a TypeRef,
+    // 同 Record::packed_size 与 Record::size, 即 real 在 packed 输出与 dwarf 中的大小.
+    packed_size: u64,
+    size: u64,
+}
+
+struct FuncPtrDef<'a> {
+    ident: &'a str,
+    tyidx: TypeIndex,
+    // None 意味着返回 void.
+    ret: Option<&'a TypeRef>,
+    params: &'a [TypeRef],
+    variadic: bool,
+    size: u64,
+}
+
+struct EnumDef<'a> {
+    ident: &'a str,
+    tyname: String,
+    tyidx: TypeIndex,
+    // enum 底层使用的整数类型.
+    repr: &'a TypeRef,
+    enumerators: &'a [EnumItem],
+    size: u64,
+}
+
+struct EnumItem {
+    // dwarf 中记录的名字.
+    name: String,
+    // 加上 enum 标识符前缀之后的名字, 比如 `ns_Color_RED`, 避免不同 namespace 中同名 enumerator 冲突.
+    ident: String,
+    // 按照 enum 底层类型的符号解释之后的值, i128 足以容纳 i64 与 u64.
+    value: Option<i128>,
+}
+
+// 每一种输出格式都对应着一个 Backend, 由 Printer 负责将各个类型分发给所有 Backend.
+// Backend 除了输出类型定义之外, 还需要输出对应的 layout 校验.
+trait Backend {
+    fn add_record(&mut self, record: &Record) -> io::Result<()>;
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()>;
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()>;
+
+    // 仅 --typedefs 时调用.
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()>;
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()>;
+
+    // ty 为 Struct/Union, 其定义之后才会通过 add_record 给出, 用于递归引用自身的类型, 比如 `struct Node { struct Node* next; }`.
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()>;
+
+    // 对于 base type, pointer 这些无需定义的类型, 仅校验其大小.
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()>;
+
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+struct Printer {
+    backends: Vec<Box<dyn Backend>>,
+    used_idents: HashMap<String, u64>,
+}
+
+impl Printer {
+    // input_paths[input_id] 为对应输入文件的路径.
+    fn try_open(
+        path: &str,
+        formats: &[Format],
+        input_paths: &[String],
+        natural: bool,
+    ) -> io::Result<Printer> {
+        let mut backends = Vec::<Box<dyn Backend>>::new();
+        for format in formats {
+            match format {
+                Format::C => backends.push(Box::new(CPrinter::try_open(path, natural)?)),
+                Format::Rust => backends.push(Box::new(rust::RustPrinter::try_open(path)?)),
+                Format::Btf => backends.push(Box::new(btf::BtfPrinter::try_open(path, false)?)),
+                Format::BtfElf => backends.push(Box::new(btf::BtfPrinter::try_open(path, true)?)),
+                Format::Json => {
+                    backends.push(Box::new(json::JsonPrinter::try_open(path, input_paths)?))
+                }
+                Format::Go => backends.push(Box::new(go::GoPrinter::try_open(path)?)),
+                Format::Python => backends.push(Box::new(python::PythonPrinter::try_open(path)?)),
+                Format::Vmlinux => backends.push(Box::new(vmlinux::VmlinuxPrinter::try_open(path)?)),
+            }
+        }
+        Ok(Printer::with_backends(backends))
+    }
+
+    fn with_backends(backends: Vec<Box<dyn Backend>>) -> Printer {
+        Printer {
+            backends,
+            used_idents: HashMap::new(),
         }
-        writeln!(self.c_file, "")
     }
 
-    // 输出到 .h 文件的所有标识符, 都是经过 alloc_ident 生成的. 比如 add_type 就是如此.
+    // 输出的所有标识符, 都是经过 alloc_ident 生成的. 比如 add_record 就是如此.
     fn alloc_ident(&mut self, tyname: &parser::TypeName) -> String {
         // return val may be empty
         fn get_ident_part(name: Option<&str>) -> &str {
@@ -262,130 +590,869 @@ impl Printer {
         }
     }
 
-    fn add_type(&mut self, lines: &[String]) -> io::Result<()> {
-        for l in lines {
-            writeln!(self.h_file, "{}", l)?;
+    // enumerator 与类型位于 C 中同一个命名空间, 所以也需要经过 used_idents.
+    fn alloc_enumerator_ident(&mut self, enum_ident: &str, name: &str) -> String {
+        let ident = format!("{}_{}", enum_ident, ident_part(name));
+        let Some(used) = self.used_idents.get_mut(&ident) else {
+            self.used_idents.insert(ident.clone(), 0);
+            return ident;
+        };
+        *used += 1;
+        return format!("{}_{}", ident, *used);
+    }
+
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_record(record)?;
         }
-        writeln!(self.h_file, "")?;
-        writeln!(self.h_file, "")?;
         return Ok(());
     }
 
-    fn finish(&mut self) -> io::Result<()> {
-        writeln!(self.c_file, "  return 0;")?;
-        writeln!(self.c_file, "}}")?;
-        Ok(())
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_array(array)?;
+        }
+        return Ok(());
     }
-}
-
-struct Member {
-    off: u64,
-    len: u64,
-    // def, 形如 `__u8 __padding33[3]`; field_name 形如 __padding33.
-    field_name: String,
-    // 没有包含结尾分号.
-    def: String,
-    is_padding: bool, // 仅有 new_padding() 可以设置为 true.
-}
-
-impl Member {
-    // 输出到 def 时会 2 个空格缩进.
-    fn print(&self, tyname: &str, def: &mut Vec<String>, asserts: &mut Vec<EqAssert>) {
-        def.push(format!("  {};", &self.def));
 
-        asserts.push(EqAssert {
-            expr: format!("(long int)(&((({}*)0)->{}))", tyname, self.field_name),
-            val: self.off,
-        });
-        asserts.push(EqAssert {
-            expr: format!("sizeof((({}*)0)->{})", tyname, self.field_name),
-            val: self.len,
-        });
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_enum(enum_def)?;
+        }
+        return Ok(());
     }
 
-    fn new_padding(off: u64, len: u64) -> Self {
-        let field_name = format!("__padding{}", uniq_id());
-        Self {
-            off,
-            len,
-            def: format!("__u8 {}[{}]", &field_name, len),
-            field_name,
-            is_padding: true,
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_typedef(typedef)?;
         }
+        return Ok(());
     }
 
-    fn new_placeholder(off: u64, len: u64, name: &str) -> Self {
-        let field_name = format!("{}{}", name, uniq_id());
-        Self {
-            off,
-            len,
-            def: format!("__u8 {}[{}]", &field_name, len),
-            field_name,
-            is_padding: false,
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_func_pointer(func)?;
         }
+        return Ok(());
     }
-}
 
-// 这个函数应该作为所有查询 process state 的入口点.
-fn get_type_info(
-    processed: &mut ProcessState,
-    printer: &mut Printer,
-    tyidx: TypeIndex,
-    ty_max_size: Option<u64>,
-    inputs_hash: &[parser::FileHash],
-    type_db: &HashMap<parser::TypeName, TypeIndex>,
-) -> io::Result<Option<Rc<TypeInfo>>> {
-    let tyinfo = match processed.get(&tyidx).map(|v| v.clone()) {
-        Some(i) => i,
-        None => {
-            process_type(processed, printer, tyidx, ty_max_size, inputs_hash, type_db)?;
-            processed.get(&tyidx).unwrap().clone()
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_forward_decl(ty)?;
         }
-    };
-    debug_assert!(tyinfo
-        .as_ref()
-        .map(|v| v.packed_size <= v.size)
-        .unwrap_or(true));
-    if let (Some(tyinfo), Some(max_size)) = (&tyinfo, ty_max_size) {
-        assert!(tyinfo.packed_size <= max_size);
+        return Ok(());
     }
-    return Ok(tyinfo);
-}
 
-// 这里 tyidx 是 real_tyidx 的符号链接, tyidx ---> real_tyidx.
-fn handle_sym_link(
-    processed: &mut ProcessState,
-    printer: &mut Printer,
-    tyidx: TypeIndex,
-    real_tyidx: TypeIndex,
-    ty_max_size: Option<u64>,
-    inputs_hash: &[parser::FileHash],
-    type_db: &HashMap<parser::TypeName, TypeIndex>,
-) -> io::Result<()> {
-    let tyinfo = get_type_info(
-        processed,
-        printer,
-        real_tyidx,
-        ty_max_size,
-        inputs_hash,
-        type_db,
-    )?;
-    processed.insert(tyidx, tyinfo);
-    return Ok(());
-}
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.add_size_check(ty, size)?;
+        }
+        return Ok(());
+    }
 
-// 找到 >= start 之后, 第一个 bit_offset 是 BYTE 边界的元素的下标, 不存在则返回 None.
-fn find_next_idx(tylayout: &Vec<parser::Layout>, start: usize) -> Option<usize> {
-    for idx in start..tylayout.len() {
-        if (tylayout[idx].bit_offset % BITS_PER_BYTE) == 0 {
-            return Some(idx);
+    fn finish(&mut self) -> io::Result<()> {
+        for backend in &mut self.backends {
+            backend.finish()?;
         }
+        return Ok(());
     }
-    return None;
 }
 
-// check ok return bit size.
-// #1 处对应的 C++ 示例:
+// 以下 c_*_def 生成类型的 C 定义, 每个元素为一行. 除了 CPrinter, bpftrace 脚本也会内联这些定义.
+fn c_record_def(record: &Record, asserts: &mut Vec<EqAssert>) -> Vec<String> {
+    let tydef = match record.kind {
+        RecordKind::Struct => format!("struct {}", record.ident),
+        RecordKind::Union => format!("union {}", record.ident),
+    };
+    let mut struct_def = Vec::<String>::new();
+
+    struct_def.push(format!("// tyname={} tyidx={:?}", record.tyname, record.tyidx));
+    struct_def.push(format!("{} {{", tydef));
+    for tymem in record.members {
+        tymem.print(&tydef, &mut struct_def, asserts);
+    }
+    struct_def.push("} __attribute__((__packed__));".to_string());
+    asserts.push(EqAssert {
+        expr: format!("sizeof({})", tydef),
+        val: record.packed_size,
+    });
+    return struct_def;
+}
+
+fn c_typedef_def(typedef: &TypedefDef) -> String {
+    format!("typedef {} {};", typedef.real.c_name(), typedef.ident)
+}
+
+fn c_func_pointer_def(func: &FuncPtrDef) -> String {
+    let mut params: Vec<String> = func.params.iter().map(|v| v.c_name()).collect();
+    if func.variadic && !params.is_empty() {
+        params.push("...".to_string());
+    }
+    // C 中 `()` 意味着参数未知, 没有参数时需要写成 `(void)`. C23 之前 `...` 前面至少需要一个参数, 此时只能使用 `()`.
+    if params.is_empty() && !func.variadic {
+        params.push("void".to_string());
+    }
+    let ret = func.ret.map_or("void".to_string(), |v| v.c_name());
+    format!("typedef {} (*{})({});", ret, func.ident, params.join(", "))
+}
+
+fn c_forward_decl(ty: &TypeRef) -> String {
+    format!("{};", ty.c_name())
+}
+
+fn c_array_def(array: &ArrayDef) -> String {
+    format!(
+        "typedef {} {}[{}];",
+        array.elem.c_name(),
+        array.ident,
+        array.count
+    )
+}
+
+fn c_enum_def(enum_def: &EnumDef) -> Vec<String> {
+    let ident = enum_def.ident;
+    let repr = enum_def.repr.c_name();
+    let mut struct_def = Vec::<String>::new();
+    struct_def.push(format!("// tyname={} tyidx={:?}", enum_def.tyname, enum_def.tyidx));
+    // enum 中的常量在 C17 中只能是 int, 超出范围的使用 #define.
+    let (items, defines): (Vec<_>, Vec<_>) = enum_def
+        .enumerators
+        .iter()
+        .filter(|v| v.value.is_some())
+        .partition(|v| i32::try_from(v.value.unwrap()).is_ok());
+    if !items.is_empty() {
+        // 指定底层类型是 C23 的特性, clang 很早便作为扩展支持了, bpftrace 与 BPF 程序都使用 clang.
+        struct_def.push("#if defined(__clang__) || (defined(__STDC_VERSION__) && __STDC_VERSION__ >= 202311L)".to_string());
+        struct_def.push(format!("enum {} : {} {{", ident, repr));
+        struct_def.push("#else".to_string());
+        struct_def.push(format!("enum {} {{", ident));
+        struct_def.push("#endif".to_string());
+        for item in items {
+            struct_def.push(format!("  {} = {},", item.ident, item.value.unwrap()));
+        }
+        struct_def.push("};".to_string());
+    }
+    let unsigned = matches!(
+        enum_def.repr,
+        TypeRef::Base {
+            encoding: parser::BaseTypeEncoding::Unsigned,
+            ..
+        }
+    );
+    for item in defines {
+        let value = item.value.unwrap();
+        let value = if unsigned {
+            format!("{}ULL", value)
+        } else if value == i64::MIN as i128 {
+            format!("({}LL - 1)", value + 1)
+        } else if value < 0 {
+            format!("({}LL)", value)
+        } else {
+            format!("{}LL", value)
+        };
+        struct_def.push(format!("#define {} {}", item.ident, value));
+    }
+    // dwarf 中没有 DW_AT_const_value, 仅保留名字.
+    for item in enum_def.enumerators.iter().filter(|v| v.value.is_none()) {
+        struct_def.push(format!("/* {}: unknown value */", item.ident));
+    }
+    struct_def.push(format!("typedef {} {};", repr, ident));
+    return struct_def;
+}
+
+struct CPrinter {
+    h_file: std::fs::File,
+    c_file: std::fs::File,
+    // `--natural` 时所有定义都在 finish 时才输出到 h_file.
+    natural: Option<natural::NaturalLayout>,
+}
+
+impl CPrinter {
+    fn do_add_eq_assert(&mut self, expr: &str, size: u64) -> io::Result<()> {
+        writeln!(self.c_file, "  ZHANYI_HIDVA_ASSERT_EQ({}, {});", expr, size)
+    }
+}
+impl CPrinter {
+    fn try_open(path: &str, natural: bool) -> io::Result<CPrinter> {
+        const ASSERT_EQ_DEF: &'static str = r###"
+#define ZHANYI_HIDVA_ASSERT_EQ(a, e) do {    \
+    int actual_size = (a);  \
+    int expect_size = (e);  \
+    if (actual_size != expect_size) {   \
+        fprintf(stderr, "ASSERT FAILED! actual: %s, which is %d; expect: %s, which is %d\n", #a, actual_size, #e, expect_size);    \
+        abort();    \
+    }   \
+} while(0)
+
+// 返回 p 中第一个(last 为 0)或者最后一个(last 为 1)值为 0 的 bit 的下标.
+static inline int zhanyi_hidva_find_zero_bit(const unsigned char *p, int size, int last) {
+    int ret = -1;
+    for (int i = 0; i < size * 8; ++i) {
+        if ((p[i / 8] >> (i % 8)) & 1) {
+            continue;
+        }
+        if (!last) {
+            return i;
+        }
+        ret = i;
+    }
+    return ret;
+}
+
+// 将 type 中所有 bit 置 1 之后再将 field 置 0, 此时值为 0 的 bit 便是 field 所在的位置.
+#define ZHANYI_HIDVA_BIT_POS(type, field, last) ({    \
+    type zhanyi_hidva_val;  \
+    memset(&zhanyi_hidva_val, 0xff, sizeof(type));  \
+    zhanyi_hidva_val.field = 0;  \
+    zhanyi_hidva_find_zero_bit((const unsigned char *)&zhanyi_hidva_val, sizeof(type), (last));  \
+})
+        "###;
+        let mut h_file_name = path.to_string();
+        h_file_name.push_str(".h");
+        let mut c_file_name = path.to_string();
+        c_file_name.push_str(".c");
+        let mut h_file = std::fs::File::create(&h_file_name)?;
+        let mut c_file = std::fs::File::create(c_file_name)?;
+        writeln!(h_file, "// Generated by hidva/clayout! 大吉大利!")?;
+        writeln!(h_file, "#pragma once")?;
+        writeln!(h_file, "#include <linux/types.h>")?;
+        writeln!(c_file, "// Generated by hidva/clayout! 大吉大利!")?;
+        writeln!(c_file, "#include <stdio.h>")?;
+        writeln!(c_file, "#include <stdlib.h>")?;
+        writeln!(c_file, "#include <string.h>")?;
+        writeln!(c_file, "#include \"{}\"", &h_file_name)?;
+        writeln!(c_file, "\n\n\n")?;
+        writeln!(c_file, "{}", ASSERT_EQ_DEF)?;
+        writeln!(c_file, "\n\n\n")?;
+        writeln!(c_file, "int main() {{")?;
+        Ok(CPrinter {
+            h_file,
+            c_file,
+            natural: natural.then(natural::NaturalLayout::new),
+        })
+    }
+
+    fn add_eq_assert(&mut self, expr: &str, size: u64) -> io::Result<()> {
+        self.do_add_eq_assert(expr, size)?;
+        writeln!(self.c_file, "")
+    }
+
+    fn add_eq_asserts(&mut self, asserts: &[EqAssert]) -> io::Result<()> {
+        for eq_assert in asserts {
+            self.do_add_eq_assert(&eq_assert.expr, eq_assert.val)?;
+        }
+        writeln!(self.c_file, "")
+    }
+
+    fn add_type(&mut self, lines: &[String]) -> io::Result<()> {
+        for l in lines {
+            writeln!(self.h_file, "{}", l)?;
+        }
+        writeln!(self.h_file, "")?;
+        writeln!(self.h_file, "")?;
+        return Ok(());
+    }
+}
+
+impl Backend for CPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        if let Some(layout) = &mut self.natural {
+            layout.add_record(record);
+            return Ok(());
+        }
+        let mut asserts = Vec::<EqAssert>::new();
+        let struct_def = c_record_def(record, &mut asserts);
+        self.add_type(&struct_def)?;
+        self.add_eq_asserts(&asserts)
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        match &mut self.natural {
+            Some(layout) => layout.add_array(array),
+            None => self.add_type(&[c_array_def(array)])?,
+        }
+        self.add_eq_assert(&format!("sizeof({})", array.ident), array.size)
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        let asserts = [EqAssert {
+            expr: format!("sizeof({})", enum_def.repr.c_name()),
+            val: enum_def.size,
+        }];
+        match &mut self.natural {
+            Some(layout) => layout.add_enum(enum_def),
+            None => self.add_type(&c_enum_def(enum_def))?,
+        }
+        self.add_eq_asserts(&asserts)
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        if let Some(layout) = &mut self.natural {
+            // 校验在 finish 时生成, 详见 NaturalLayout::finish.
+            layout.add_typedef(typedef);
+            return Ok(());
+        }
+        self.add_type(&[c_typedef_def(typedef)])?;
+        self.add_eq_assert(&format!("sizeof({})", typedef.ident), typedef.packed_size)
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        match &mut self.natural {
+            Some(layout) => layout.add_func_pointer(func),
+            None => self.add_type(&[c_func_pointer_def(func)])?,
+        }
+        self.add_eq_assert(&format!("sizeof({})", func.ident), func.size)
+    }
+
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()> {
+        match &mut self.natural {
+            Some(layout) => layout.add_forward_decl(ty),
+            None => self.add_type(&[c_forward_decl(ty)])?,
+        }
+        Ok(())
+    }
+
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()> {
+        self.add_eq_assert(&format!("sizeof({})", ty.c_name()), size)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(layout) = self.natural.take() {
+            for def in layout.finish() {
+                self.add_type(&def.lines)?;
+                if !def.asserts.is_empty() {
+                    self.add_eq_asserts(&def.asserts)?;
+                }
+            }
+        }
+        writeln!(self.c_file, "  return 0;")?;
+        writeln!(self.c_file, "}}")?;
+        Ok(())
+    }
+}
+
+enum MemberKind {
+    Field(Rc<TypeInfo>),
+    // 父类, C++ 中的 inherit.
+    Parent(Rc<TypeInfo>),
+    // Rust enum 中的一个 variant, 仅出现在 process_variant_part 生成的 union 中.
+    // discr_value 为 None 意味着 discriminant 不匹配其他 variant 时选择该 variant.
+    Variant {
+        tyinfo: Rc<TypeInfo>,
+        discr_value: Option<u64>,
+    },
+    // 仅有 new_padding() 会使用.
+    Padding,
+    // 一组连续的 bitfield, 按照 bit_off 递增排列, 其间未被使用的 bit 不会出现在这里.
+    Bitfield(Vec<Bitfield>),
+    // 无法识别的部分, 将使用 `__u8 name[len]` 占位.
+    Placeholder,
+}
+
+struct Bitfield {
+    name: String,
+    // 相对于所在 Member::off 的偏移, bit 为单位.
+    bit_off: u64,
+    bit_size: u64,
+    // bitfield 声明时使用的类型, strip 之后只会是 TypeRef::Base 或 TypeRef::Alias.
+    tyinfo: Rc<TypeInfo>,
+}
+
+struct Member {
+    off: u64,
+    len: u64,
+    // 对于 padding, field_name 形如 __padding33.
+    field_name: String,
+    kind: MemberKind,
+    // member 上的 DW_AT_alignment, 比如 `alignas(64) int i;`.
+    alignas: Option<u64>,
+    // 仅虚基类有, 输出时会在注释中说明运行时如何找到该虚基类.
+    vbase: Option<VirtualBase>,
+}
+
+impl Member {
+    fn new_field(off: u64, field_name: String, tyinfo: Rc<TypeInfo>) -> Self {
+        Self {
+            off,
+            len: tyinfo.packed_size,
+            field_name,
+            kind: MemberKind::Field(tyinfo),
+            alignas: None,
+            vbase: None,
+        }
+    }
+
+    fn new_parent(off: u64, field_name: String, tyinfo: Rc<TypeInfo>) -> Self {
+        Self {
+            off,
+            len: tyinfo.packed_size,
+            field_name,
+            kind: MemberKind::Parent(tyinfo),
+            alignas: None,
+            vbase: None,
+        }
+    }
+
+    // 仅 Field/Parent/Variant 有对应的类型, 其余的都使用 `__u8 name[len]` 占位.
+    fn tyinfo(&self) -> Option<&Rc<TypeInfo>> {
+        match &self.kind {
+            MemberKind::Field(tyinfo)
+            | MemberKind::Parent(tyinfo)
+            | MemberKind::Variant { tyinfo, .. } => Some(tyinfo),
+            MemberKind::Padding | MemberKind::Bitfield(_) | MemberKind::Placeholder => None,
+        }
+    }
+
+    fn is_padding(&self) -> bool {
+        matches!(self.kind, MemberKind::Padding)
+    }
+
+    // 不使用 packed 时成员的对齐, bitfield 与 C 一样按照声明类型对齐.
+    fn align(&self) -> u64 {
+        let ty_align = match &self.kind {
+            MemberKind::Field(tyinfo)
+            | MemberKind::Parent(tyinfo)
+            | MemberKind::Variant { tyinfo, .. } => tyinfo.align,
+            MemberKind::Bitfield(bitfields) => {
+                bitfields.iter().map(|v| v.tyinfo.align).max().unwrap_or(1)
+            }
+            MemberKind::Padding | MemberKind::Placeholder => 1,
+        };
+        return std::cmp::max(ty_align, self.alignas.unwrap_or(1));
+    }
+
+    // 形如 `__u8 __padding33[3]`, 没有包含结尾分号.
+    fn c_def(&self) -> String {
+        match self.tyinfo() {
+            Some(tyinfo) => format!("{} {}", tyinfo.ty.c_name(), &self.field_name),
+            None => format!("__u8 {}[{}]", &self.field_name, self.len),
+        }
+    }
+
+    // 输出到 def 时会 2 个空格缩进.
+    fn print(&self, tyname: &str, def: &mut Vec<String>, asserts: &mut Vec<EqAssert>) {
+        if let MemberKind::Bitfield(bitfields) = &self.kind {
+            return self.print_bitfields(bitfields, tyname, def, asserts);
+        }
+        if let MemberKind::Variant { discr_value, .. } = &self.kind {
+            def.push(match discr_value {
+                Some(v) => format!("  // {} == {}", DISCRIMINANT_NAME, v),
+                None => "  // otherwise".to_string(),
+            });
+        }
+        if let Some(vbase) = &self.vbase {
+            def.push(vbase.c_comment());
+        }
+        def.push(format!("  {};", self.c_def()));
+
+        asserts.push(EqAssert {
+            expr: format!("(long int)(&((({}*)0)->{}))", tyname, self.field_name),
+            val: self.off,
+        });
+        asserts.push(EqAssert {
+            expr: format!("sizeof((({}*)0)->{})", tyname, self.field_name),
+            val: self.len,
+        });
+    }
+
+    // 由于所有的 struct 都是 packed 的, 所以 bitfield 之间是紧挨着的, 不会因为声明类型而对齐.
+    // 因此只需要使用匿名 bitfield 填充未被使用的 bit, 使得每个 bitfield 都位于 dwarf 中记录的位置.
+    // bitfield 无法取地址, 所以在 .c 中通过将 bitfield 置 0 之后观察哪些 bit 发生了变化来校验其位置.
+    fn print_bitfields(
+        &self,
+        bitfields: &[Bitfield],
+        tyname: &str,
+        def: &mut Vec<String>,
+        asserts: &mut Vec<EqAssert>,
+    ) {
+        fn push_unused(def: &mut Vec<String>, mut bit_size: u64) {
+            while bit_size > 0 {
+                let (tyname, width) = match bit_size {
+                    1..=8 => ("__u8", bit_size),
+                    9..=16 => ("__u16", bit_size),
+                    17..=32 => ("__u32", bit_size),
+                    _ => ("__u64", std::cmp::min(bit_size, 64)),
+                };
+                def.push(format!("  {} :{};", tyname, width));
+                bit_size -= width;
+            }
+        }
+
+        let mut next_bit_off = 0;
+        for bitfield in bitfields {
+            debug_assert!(bitfield.bit_off >= next_bit_off);
+            push_unused(def, bitfield.bit_off - next_bit_off);
+            // ZHANYI_HIDVA_BIT_POS 需要给 bitfield 赋值, 所以去掉 const/volatile.
+            def.push(format!(
+                "  {} {}:{};",
+                bitfield.tyinfo.ty.unqualified().c_name(),
+                &bitfield.name,
+                bitfield.bit_size
+            ));
+            next_bit_off = bitfield.bit_off + bitfield.bit_size;
+
+            let first_bit = self.off * BITS_PER_BYTE + bitfield.bit_off;
+            asserts.push(EqAssert {
+                expr: format!("ZHANYI_HIDVA_BIT_POS({}, {}, 0)", tyname, &bitfield.name),
+                val: first_bit,
+            });
+            asserts.push(EqAssert {
+                expr: format!("ZHANYI_HIDVA_BIT_POS({}, {}, 1)", tyname, &bitfield.name),
+                val: first_bit + bitfield.bit_size - 1,
+            });
+        }
+        debug_assert!(self.len * BITS_PER_BYTE >= next_bit_off);
+        push_unused(def, self.len * BITS_PER_BYTE - next_bit_off);
+    }
+
+    fn new_variant(field_name: String, tyinfo: Rc<TypeInfo>, discr_value: Option<u64>) -> Self {
+        Self {
+            off: 0,
+            len: tyinfo.packed_size,
+            field_name,
+            kind: MemberKind::Variant {
+                tyinfo,
+                discr_value,
+            },
+            alignas: None,
+            vbase: None,
+        }
+    }
+
+    fn new_padding(off: u64, len: u64) -> Self {
+        let field_name = format!("__padding{}", uniq_id());
+        Self {
+            off,
+            len,
+            field_name,
+            kind: MemberKind::Padding,
+            alignas: None,
+            vbase: None,
+        }
+    }
+
+    fn new_placeholder(off: u64, len: u64, name: &str) -> Self {
+        let field_name = format!("{}{}", name, uniq_id());
+        Self {
+            off,
+            len,
+            field_name,
+            kind: MemberKind::Placeholder,
+            alignas: None,
+            vbase: None,
+        }
+    }
+
+    fn new_bitfield(off: u64, len: u64, name: &str, bitfields: Vec<Bitfield>) -> Self {
+        let field_name = format!("{}{}", name, uniq_id());
+        Self {
+            off,
+            len,
+            field_name,
+            kind: MemberKind::Bitfield(bitfields),
+            alignas: None,
+            vbase: None,
+        }
+    }
+}
+
+// 这个函数应该作为所有查询 process state 的入口点.
+fn get_type_info(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let tyinfo = match processed.types.get(&tyidx).map(|v| v.clone()) {
+        Some(i) => i,
+        None => {
+            let outermost = processed.pending.is_empty();
+            process_type(processed, printer, tyidx, ty_max_size, inputs_hash, type_db)?;
+            if outermost {
+                process_deferred(processed, printer, inputs_hash, type_db)?;
+            }
+            // handle_sym_link 可能会移除 tyidx, 详见其注释.
+            processed.types.get(&tyidx).cloned().flatten()
+        }
+    };
+    debug_assert!(tyinfo
+        .as_ref()
+        .map(|v| v.packed_size <= v.size)
+        .unwrap_or(true));
+    if let (Some(tyinfo), Some(max_size)) = (&tyinfo, ty_max_size) {
+        assert!(tyinfo.packed_size <= max_size);
+    }
+    return Ok(tyinfo);
+}
+
+// 输出 forward_decl 推迟的 struct/union, 此时 pending 为空, 它们的成员可以引用之前 pending 中的任何类型.
+fn process_deferred(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    while let Some(tyidx) = processed.deferred.pop_front() {
+        if !processed.types.contains_key(&tyidx) {
+            process_type(processed, printer, tyidx, None, inputs_hash, type_db)?;
+        }
+    }
+    return Ok(());
+}
+
+// 这里 tyidx 是 real_tyidx 的符号链接, tyidx ---> real_tyidx.
+fn handle_sym_link(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    real_tyidx: TypeIndex,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let tyinfo = get_type_info(
+        processed,
+        printer,
+        real_tyidx,
+        ty_max_size,
+        inputs_hash,
+        type_db,
+    )?;
+    if tyinfo.is_none() && is_pending(processed, real_tyidx, inputs_hash, type_db) {
+        // real_tyidx 还未处理完, 比如 `typedef struct Node Node_t; struct Node { Node_t* next; }`,
+        // 此时不能缓存 None, 否则之后所有对 Node_t 的引用都会失败.
+        processed.types.remove(&tyidx);
+        return Ok(());
+    }
+    processed.types.insert(tyidx, tyinfo);
+    return Ok(());
+}
+
+// handle_sym_link 之后, 将 tyidx 对应的 TypeInfo 换成 wrap 返回的类型, layout 保持不变.
+fn wrap_type_info(
+    processed: &mut ProcessState,
+    tyidx: TypeIndex,
+    wrap: impl FnOnce(Rc<TypeInfo>) -> TypeRef,
+) {
+    let Some(Some(real)) = processed.types.get(&tyidx).cloned() else {
+        return;
+    };
+    let tyinfo = TypeInfo {
+        packed_size: real.packed_size,
+        size: real.size,
+        align: real.align,
+        ty: wrap(real),
+    };
+    processed.types.insert(tyidx, Some(Rc::new(tyinfo)));
+}
+
+// --typedefs 时为 typedef 分配标识符并输出 `typedef real ident;`.
+fn process_typedef(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    typ: &parser::Type,
+) -> io::Result<()> {
+    let Some(Some(real)) = processed.types.get(&tyidx).cloned() else {
+        return Ok(());
+    };
+    let Some(tyname) = parser::TypeName::try_from(typ) else {
+        return Ok(());
+    };
+    let ident = printer.alloc_ident(&tyname);
+    printer.add_typedef(&TypedefDef {
+        ident: &ident,
+        tyname: tyname.to_string(),
+        tyidx,
+        real: &real.ty,
+        packed_size: real.packed_size,
+        size: real.size,
+    })?;
+    wrap_type_info(processed, tyidx, |real| TypeRef::Typedef { ident, real });
+    return Ok(());
+}
+
+// 沿着 typedef, const 等找到 tyidx 最终对应的 struct/union, 声明会通过 type_db 找到其定义.
+fn resolve_record(
+    mut tyidx: TypeIndex,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> Option<TypeIndex> {
+    loop {
+        let typ = parser::Type::from_offset(&inputs_hash[tyidx.input_id], tyidx.typoff)?;
+        let (tyname, is_declaration) = match typ.kind() {
+            parser::TypeKind::Def(ty) => {
+                tyidx.typoff = ty.ty;
+                continue;
+            }
+            parser::TypeKind::Modifier(ty)
+                if !matches!(
+                    ty.kind(),
+                    parser::TypeModifierKind::Pointer
+                        | parser::TypeModifierKind::Reference
+                        | parser::TypeModifierKind::RvalueReference
+                ) =>
+            {
+                tyidx.typoff = ty.ty;
+                continue;
+            }
+            parser::TypeKind::Struct(ty) => (ty.type_name(), ty.is_declaration()),
+            parser::TypeKind::Union(ty) => (ty.type_name(), ty.is_declaration()),
+            _ => return None,
+        };
+        if !is_declaration {
+            return Some(tyidx);
+        }
+        return type_db.get(&tyname).copied();
+    }
+}
+
+fn is_pending(
+    processed: &ProcessState,
+    tyidx: TypeIndex,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> bool {
+    let Some(record_tyidx) = resolve_record(tyidx, inputs_hash, type_db) else {
+        return false;
+    };
+    return processed.pending.contains(&record_tyidx);
+}
+
+// 指针指向的 tyidx 是否应该只输出前向声明, 即正在处理某个 struct/union 时 tyidx 是尚未处理的 struct/union.
+// 比如从 A 开始处理 `struct A { B* b; }; struct B { A* a; A inner; };` 时, 若此时定义 B, 那么 A 还未处理完,
+// B::inner 的类型是未知的. 这里 B 只输出前向声明, 在 A 之后再定义, 详见 forward_decl.
+fn should_defer(
+    processed: &ProcessState,
+    tyidx: TypeIndex,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> bool {
+    if processed.pending.is_empty() {
+        return false;
+    }
+    let Some(record_tyidx) = resolve_record(tyidx, inputs_hash, type_db) else {
+        return false;
+    };
+    return !processed.types.contains_key(&record_tyidx);
+}
+
+// 指针指向的 tyidx 还未处理完时, 若其为正在处理中的 struct/union, 则提前为其分配标识符并输出前向声明,
+// 之后 process_struct_type/process_union_type 会使用同一个标识符. 多个互相引用的类型也是如此.
+// 对于 should_defer 的 struct/union 也是如此, 并且将其加入 deferred, 在 pending 为空之后再定义.
+// 此时 record 的 packed_size 还未知, 返回的 TypeInfo 只有 ty 是准确的, 不过指针也只会用到 ty.
+fn forward_decl(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let Some(record_tyidx) = resolve_record(tyidx, inputs_hash, type_db) else {
+        return Ok(None);
+    };
+    let pending = processed.pending.contains(&record_tyidx);
+    if !pending && processed.types.contains_key(&record_tyidx) {
+        return Ok(None);
+    }
+    let typ = parser::Type::from_offset(&inputs_hash[record_tyidx.input_id], record_tyidx.typoff);
+    let typ = typ.unwrap();
+    let (kind, tyname, size, align) = match typ.kind() {
+        parser::TypeKind::Struct(ty) => (RecordKind::Struct, ty.type_name(), ty.byte_size(), ty.alignment()),
+        parser::TypeKind::Union(ty) => (RecordKind::Union, ty.type_name(), ty.byte_size(), ty.alignment()),
+        _ => unreachable!(),
+    };
+    let declared = processed.declared.get(&record_tyidx).cloned();
+    let ident = match &declared {
+        Some(ident) => ident.clone(),
+        None => printer.alloc_ident(&tyname),
+    };
+    let ty = match kind {
+        RecordKind::Struct => TypeRef::Struct(ident.clone()),
+        RecordKind::Union => TypeRef::Union(ident.clone()),
+    };
+    if declared.is_none() {
+        printer.add_forward_decl(&ty)?;
+        processed.declared.insert(record_tyidx, ident);
+        if !pending {
+            processed.deferred.push_back(record_tyidx);
+        }
+    }
+    let size = size.unwrap_or(0);
+    let mut tyinfo = Rc::new(TypeInfo {
+        ty,
+        packed_size: size,
+        size,
+        align: align.unwrap_or(1),
+    });
+    if processed.typedefs {
+        // 保留 `const Node* prev` 中的 const. 此时 typedef 可能还未定义, 只能直接使用 record.
+        for qualifier in path_qualifiers(tyidx, inputs_hash).into_iter().rev() {
+            tyinfo = Rc::new(TypeInfo {
+                packed_size: tyinfo.packed_size,
+                size: tyinfo.size,
+                align: tyinfo.align,
+                ty: TypeRef::Qualified {
+                    qualifier,
+                    real: tyinfo,
+                },
+            });
+        }
+    }
+    return Ok(Some(tyinfo));
+}
+
+// 从 tyidx 开始沿着 typedef, const 等直到 struct/union, 返回路径上的 const/volatile, 外层的在前.
+fn path_qualifiers(mut tyidx: TypeIndex, inputs_hash: &[parser::FileHash]) -> Vec<Qualifier> {
+    let mut qualifiers = Vec::new();
+    while let Some(typ) = parser::Type::from_offset(&inputs_hash[tyidx.input_id], tyidx.typoff) {
+        match typ.kind() {
+            parser::TypeKind::Def(ty) => tyidx.typoff = ty.ty,
+            parser::TypeKind::Modifier(ty) => {
+                match ty.kind() {
+                    parser::TypeModifierKind::Const => qualifiers.push(Qualifier::Const),
+                    parser::TypeModifierKind::Volatile => qualifiers.push(Qualifier::Volatile),
+                    parser::TypeModifierKind::Pointer
+                    | parser::TypeModifierKind::Reference
+                    | parser::TypeModifierKind::RvalueReference => break,
+                    _ => {}
+                }
+                tyidx.typoff = ty.ty;
+            }
+            _ => break,
+        }
+    }
+    return qualifiers;
+}
+
+// 成员中的指针可能已经通过 forward_decl 引用了 tyidx, 此时需使用同一个标识符.
+fn record_ident(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    tyname: &parser::TypeName,
+) -> String {
+    processed.pending.remove(&tyidx);
+    match processed.declared.remove(&tyidx) {
+        Some(ident) => ident,
+        None => printer.alloc_ident(tyname),
+    }
+}
+
+// 找到 >= start 之后, 第一个 bit_offset 是 BYTE 边界的元素的下标, 不存在则返回 None.
+fn find_next_idx(tylayout: &[parser::Layout], start: usize) -> Option<usize> {
+    for idx in start..tylayout.len() {
+        if tylayout[idx].bit_offset.is_multiple_of(BITS_PER_BYTE) {
+            return Some(idx);
+        }
+    }
+    return None;
+}
+
+// check ok return bit size.
+// #1 处对应的 C++ 示例:
 //   struct S {};
 //   struct F: public S { int i ; };  // 这里 F.i 与 S 具有相同的起始地址.
 // #2 处对应 C++ 示例:
@@ -393,650 +1460,2075 @@ fn find_next_idx(tylayout: &Vec<parser::Layout>, start: usize) -> Option<usize>
 //     long l;
 //     char ch[0];  // 这里 ch bit_size None.
 //   };
-// fn check_layout(tylayout: &Vec<parser::Layout>) -> Option<u64> {
-//     let iter = tylayout.iter();
-//     let Some(mut prev) = iter.next() else {
-//         return None;
-//     };
-//     while let Some(curr) = iter.next() {
-//         let Some(prevsize) = prev.bit_size.get() else {
-//             return None;
-//         };
-//         if curr.bit_offset == prev.bit_offset ||  // #1
-//            curr.bit_offset == prev.bit_offset + prevsize
-//         {
-//             prev = curr;
-//             continue;
-//         }
-//         return None;
-//     }
-//     let prevsize = match prev.bit_size.get() {
-//         Some(v) => v,
-//         None => 0, // #2
-//     };
-//     return Some(prev.bit_offset + prevsize);
-// }
+// fn check_layout(tylayout: &Vec<parser::Layout>) -> Option<u64> {
+//     let iter = tylayout.iter();
+//     let Some(mut prev) = iter.next() else {
+//         return None;
+//     };
+//     while let Some(curr) = iter.next() {
+//         let Some(prevsize) = prev.bit_size.get() else {
+//             return None;
+//         };
+//         if curr.bit_offset == prev.bit_offset ||  // #1
+//            curr.bit_offset == prev.bit_offset + prevsize
+//         {
+//             prev = curr;
+//             continue;
+//         }
+//         return None;
+//     }
+//     let prevsize = match prev.bit_size.get() {
+//         Some(v) => v,
+//         None => 0, // #2
+//     };
+//     return Some(prev.bit_offset + prevsize);
+// }
+
+// 需要以 bit 为单位处理的 layout. 注意 `long l:32` 这种 bit_size 是 BYTE 整数倍的 member 也是 bitfield.
+fn is_bitfield(l: &parser::Layout) -> bool {
+    if let parser::LayoutItem::Member(mem) = l.item {
+        return mem.is_bitfield();
+    }
+    let Some(s) = l.bit_size.get() else {
+        return false;
+    };
+    return !s.is_multiple_of(BITS_PER_BYTE);
+}
+
+// tylayout 中的元素共同占据了从 base_bit_off 开始的 member_size 个字节, 将其中的 bitfield 翻译为 Bitfield.
+// 返回 None 意味着无法使用 C bitfield 准确表示, 此时调用方应使用 placeholder 占位.
+fn process_bitfields(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    tylayout: &[parser::Layout],
+    base_bit_off: u64,
+    member_size: u64,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Vec<Bitfield>>> {
+    let mut bitfields = Vec::<Bitfield>::new();
+    let mut next_bit_off = 0;
+    for layout in tylayout {
+        let mem = match layout.item {
+            // 未被使用的 bit 在输出时会重新计算.
+            parser::LayoutItem::Padding => continue,
+            parser::LayoutItem::Member(mem) if mem.is_bitfield() => mem,
+            _ => {
+                warn!("process_bitfields: unexpected layout item. tyidx={:?} layout={:?}", tyidx, layout);
+                return Ok(None);
+            }
+        };
+        let bit_off = layout.bit_offset - base_bit_off;
+        let bit_size = layout.bit_size.get().unwrap();
+        if bit_off < next_bit_off || bit_off + bit_size > member_size * BITS_PER_BYTE {
+            warn!("process_bitfields: invalid bit offset. tyidx={:?} layout={:?}", tyidx, layout);
+            return Ok(None);
+        }
+        let mem_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: mem.type_offset(),
+        };
+        let mem_tyinfo = get_type_info(processed, printer, mem_tyidx, None, inputs_hash, type_db)?;
+        let Some(mem_tyinfo) = mem_tyinfo else {
+            return Ok(None);
+        };
+        if !matches!(mem_tyinfo.ty.strip(), TypeRef::Base { .. } | TypeRef::Alias(_))
+            || bit_size > mem_tyinfo.size * BITS_PER_BYTE
+        {
+            warn!("process_bitfields: invalid bitfield type. tyidx={:?} layout={:?}", tyidx, layout);
+            return Ok(None);
+        }
+        bitfields.push(Bitfield {
+            name: member_name(mem.name()).into_owned(),
+            bit_off,
+            bit_size,
+            tyinfo: mem_tyinfo,
+        });
+        next_bit_off = bit_off + bit_size;
+    }
+    return Ok(Some(bitfields));
+}
+
+fn is_valid_ident(input: &str) -> bool {
+    let ret = input.trim_end_matches(is_ident_char);
+    return ret.is_empty();
+}
+
+fn member_name(input: Option<&str>) -> Cow<str> {
+    let Some(input) = input else {
+        return Cow::Owned(format!("__anon{}", uniq_id()));
+    };
+    if is_valid_ident(input) {
+        return Cow::Borrowed(input);
+    }
+    return Cow::Owned(format!("__mem{}", uniq_id()));
+}
+
+#[derive(Clone)]
+struct EqAssert {
+    expr: String,
+    val: u64,
+}
+
+// dwarf 中没有记录 DW_AT_alignment 时, 与 C 一样取各个成员对齐的最大值.
+fn record_align(tymems: &[Member]) -> u64 {
+    return tymems.iter().map(|v| v.align()).max().unwrap_or(1);
+}
+
+// tymems 最后一个 tymem off + len 为 ty_size.
+// tyalign 为 dwarf 中记录的 DW_AT_alignment.
+fn process_members(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    tyname: &parser::TypeName,
+    tymems: &[Member],
+    kind: RecordKind,
+    ident: String,
+    tysize: Option<u64>,
+    tyalign: Option<u64>,
+) -> io::Result<()> {
+    let Some(packed_size) = tymems.last().map(|v|v.off + v.len) else {
+        return Ok(());
+    };
+    let align = tyalign.unwrap_or_else(|| record_align(tymems));
+    printer.add_record(&Record {
+        kind,
+        ident: &ident,
+        tyname: tyname.to_string(),
+        tyidx,
+        members: tymems,
+        packed_size,
+        size: tysize.unwrap_or(packed_size),
+        align,
+    })?;
+    if let Some(tysize) = tysize {
+        let ty = match kind {
+            RecordKind::Struct => TypeRef::Struct(ident),
+            RecordKind::Union => TypeRef::Union(ident),
+        };
+        processed.types.insert(
+            tyidx,
+            Some(Rc::new(TypeInfo {
+                ty,
+                packed_size,
+                size: tysize,
+                align,
+            })),
+        );
+    }
+    return Ok(());
+}
+
+fn process_union_type(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::UnionType,
+    // ty_max_size 是用来处理 C++ 中 A1218, S1218 示例展示的重用 padding 问题,
+    // union 没有这种问题, 所以可以忽略 ty_max_size.
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let tyname = ty.type_name();
+    if ty.is_declaration() {
+        let Some(&real_tyidx) = type_db.get(&tyname) else {
+            warn!("process_union_type: unknown declaration union. typidx={:?} typname={}", tyidx, &tyname);
+            return Ok(());
+        };
+        return handle_sym_link(
+            processed,
+            printer,
+            tyidx,
+            real_tyidx,
+            ty_max_size,
+            inputs_hash,
+            type_db,
+        );
+    }
+    let Some(ty_size) = ty.byte_size() else {
+        warn!("process_union_type: unknown byte size typidx={:?} typname={}", tyidx, ty.type_name());
+        return Ok(());
+    };
+    if let Some(ty_max_size) = ty_max_size {
+        if ty_size > ty_max_size {
+            warn!(
+                "process_union_type: invalid byte size typidx={:?} typname={} expect={} actual={}",
+                tyidx,
+                ty.type_name(),
+                ty_max_size,
+                ty_size
+            );
+            return Ok(());
+        }
+    }
+
+    let mut tymems = Vec::<Member>::new();
+    processed.pending.insert(tyidx);
+    for union_mem in ty.members() {
+        if union_mem.bit_offset() != 0 {
+            warn!(
+                "process_union_type: union_mem.bit_offset != 0! typidx={:?} typname={} member={:?}",
+                tyidx,
+                ty.type_name(),
+                union_mem
+            );
+            processed.pending.remove(&tyidx);
+            return Ok(());
+        }
+        let Some(union_mem_bit_size) = union_mem.bit_size(&inputs_hash[tyidx.input_id]) else {
+            warn!("process_union_type: unknown member size! typidx={:?} typname={} member={:?}", tyidx, ty.type_name(), union_mem);
+            processed.pending.remove(&tyidx);
+            return Ok(());
+        };
+        let member_size = bit2byte(union_mem_bit_size);
+        let tylayout = &parser::Layout {
+            bit_offset: 0,
+            bit_size: parser::Size::new(union_mem_bit_size),
+            item: parser::LayoutItem::Member(union_mem),
+        };
+
+        if is_bitfield(tylayout) {
+            let bitfields = process_bitfields(
+                processed,
+                printer,
+                tyidx,
+                std::slice::from_ref(tylayout),
+                0,
+                member_size,
+                inputs_hash,
+                type_db,
+            )?;
+            let Some(bitfields) = bitfields else {
+                tymems.push(Member::new_placeholder(0, member_size, "__bitfield"));
+                continue;
+            };
+            tymems.push(Member::new_bitfield(0, member_size, "__bitfield", bitfields));
+            continue;
+        }
+
+        let member_tyoff = union_mem.type_offset();
+        let member_name = member_name(union_mem.name());
+        let mem_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: member_tyoff,
+        };
+        let mem_tyinfo = get_type_info(
+            processed,
+            printer,
+            mem_tyidx,
+            Some(member_size),
+            inputs_hash,
+            type_db,
+        )?;
+        let Some(mem_tyinfo) = mem_tyinfo else {
+            tymems.push(Member::new_placeholder(0, member_size, "__unknown_type"));
+            continue;
+        };
+        debug_assert!(mem_tyinfo.packed_size <= member_size);
+
+        let mut member = Member::new_field(0, member_name.into_owned(), mem_tyinfo);
+        member.alignas = union_mem.alignment();
+        tymems.push(member);
+    }
+    tymems.push(Member::new_placeholder(0, ty_size, "__HIDVA_dont_use"));
+
+    let ident = record_ident(processed, printer, tyidx, &tyname);
+    return process_members(
+        processed,
+        printer,
+        tyidx,
+        &ty.type_name(),
+        &tymems,
+        RecordKind::Union,
+        ident,
+        Some(ty_size),
+        ty.alignment(),
+    );
+}
+
+// process_variant_part 生成的 union 中 discriminant 对应的字段名.
+const DISCRIMINANT_NAME: &str = "__discriminant";
+
+// Rust enum 中, discriminant 是 struct 的一个普通 member, 与 variant part 中的各个 variant 存在重叠.
+// 对于 niche 优化的 enum, discriminant 甚至可能位于 variant part 的中间. 这里将 discriminant 以及
+// 其与 variant part 之间的 padding 合入 variant part, 由 process_variant_part 统一处理.
+fn merge_discriminant<'input, 'item>(
+    mut tylayout: Vec<parser::Layout<'input, 'item>>,
+    members: &[parser::Member],
+    hash: &parser::FileHash,
+) -> Vec<parser::Layout<'input, 'item>> {
+    let mut merged = Vec::new();
+    for layout in tylayout.iter_mut() {
+        let parser::LayoutItem::VariantPart(variant_part) = layout.item else {
+            continue;
+        };
+        let Some(discr) = variant_part.discriminant(members) else {
+            continue;
+        };
+        let (Some(vp_bit_size), Some(discr_bit_size)) = (layout.bit_size.get(), discr.bit_size(hash)) else {
+            continue;
+        };
+        let start = std::cmp::min(layout.bit_offset, discr.bit_offset());
+        let end = std::cmp::max(
+            layout.bit_offset + vp_bit_size,
+            discr.bit_offset() + discr_bit_size,
+        );
+        layout.bit_offset = start;
+        layout.bit_size = parser::Size::new(end - start);
+        merged.push((discr as *const parser::Member, start, end));
+    }
+    if merged.is_empty() {
+        return tylayout;
+    }
+    tylayout.retain(|layout| {
+        for &(discr, start, end) in &merged {
+            let in_range = layout.bit_offset >= start
+                && layout.bit_offset + layout.bit_size.get().unwrap_or(0) <= end;
+            match layout.item {
+                parser::LayoutItem::Member(mem) if std::ptr::eq(mem, discr) => return false,
+                parser::LayoutItem::Padding if in_range => return false,
+                _ => {}
+            }
+        }
+        return true;
+    });
+    return tylayout;
+}
+
+// 生成一个 clayout 自己定义的 struct/union, 比如 variant part 对应的 union.
+// tymems 为空时不会生成任何类型, 此时返回 None.
+fn process_synthetic_record(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    name: &str,
+    tymems: &[Member],
+    kind: RecordKind,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let Some(packed_size) = tymems.last().map(|v| v.off + v.len) else {
+        return Ok(None);
+    };
+    let tyname = parser::TypeName {
+        namespace: None,
+        name: Some(name),
+    };
+    let ident = printer.alloc_ident(&tyname);
+    process_members(
+        processed,
+        printer,
+        tyidx,
+        &tyname,
+        tymems,
+        kind,
+        ident.clone(),
+        None,
+        None,
+    )?;
+    let ty = match kind {
+        RecordKind::Struct => TypeRef::Struct(ident),
+        RecordKind::Union => TypeRef::Union(ident),
+    };
+    return Ok(Some(Rc::new(TypeInfo {
+        ty,
+        packed_size,
+        size: packed_size,
+        align: record_align(tymems),
+    })));
+}
+
+// Rust enum 会被翻译为如下 union, 每一个 variant 都对应着一个 struct, 其内成员的 offset 均相对于 union 起始位置:
+//   union Shape_Variants {
+//     __u64 __discriminant;
+//     // __discriminant == 0
+//     struct Shape_Circle Circle;
+//     // __discriminant == 1
+//     struct Shape_Rect Rect;
+//     __u8 __HIDVA_dont_use3[16];
+//   };
+// 对于没有 discriminant 的 enum, union 中便只有各个 variant.
+fn process_variant_part(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    variant_part: &parser::VariantPart,
+    member_off: u64,
+    member_size: u64,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Member> {
+    let base_bit_off = member_off * BITS_PER_BYTE;
+    let prefix = match ty.name().map(|v| ident_part(v)) {
+        Some(v) if !v.is_empty() => v.to_string(),
+        _ => format!("AnonEnum{}", uniq_id()),
+    };
+    let mut union_mems = Vec::<Member>::new();
+
+    if let Some(discr) = variant_part.discriminant(ty.members()) {
+        let discr_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: discr.type_offset(),
+        };
+        let discr_tyinfo = get_type_info(processed, printer, discr_tyidx, None, inputs_hash, type_db)?;
+        let discr_bit_off = discr.bit_offset() - base_bit_off;
+        match discr_tyinfo {
+            Some(discr_tyinfo)
+                if discr_bit_off.is_multiple_of(BITS_PER_BYTE) && !discr.is_bitfield() =>
+            {
+                let discr_off = discr_bit_off / BITS_PER_BYTE;
+                if discr_off == 0 {
+                    union_mems.push(Member::new_field(0, DISCRIMINANT_NAME.to_string(), discr_tyinfo));
+                } else {
+                    // discriminant 不在起始位置时需要包一层 struct.
+                    let tymems = [
+                        Member::new_padding(0, discr_off),
+                        Member::new_field(discr_off, DISCRIMINANT_NAME.to_string(), discr_tyinfo),
+                    ];
+                    let name = format!("{}_Discriminant", prefix);
+                    let tyinfo = process_synthetic_record(
+                        processed,
+                        printer,
+                        tyidx,
+                        &name,
+                        &tymems,
+                        RecordKind::Struct,
+                    )?;
+                    union_mems.push(Member::new_field(0, DISCRIMINANT_NAME.to_string(), tyinfo.unwrap()));
+                }
+            }
+            _ => {
+                warn!("process_variant_part: invalid discriminant. tyidx={:?} discr={:?}", tyidx, discr);
+            }
+        }
+    }
+
+    for (idx, variant) in variant_part.variants().iter().enumerate() {
+        let variant_name = match variant.name() {
+            Some(v) if is_valid_ident(v) => v.to_string(),
+            _ => format!("__variant{}", idx),
+        };
+        let vlayout = variant.layout(base_bit_off, None, &inputs_hash[tyidx.input_id]);
+        let mut tymems = process_layout(
+            processed,
+            printer,
+            tyidx,
+            ty,
+            &vlayout,
+            member_size,
+            None,
+            inputs_hash,
+            type_db,
+        )?;
+        if tymems.is_empty() {
+            // 比如 `Shape::Empty` 这种没有任何字段的 variant.
+            tymems.push(Member::new_placeholder(0, 0, "__empty"));
+        }
+        let name = format!("{}_{}", prefix, variant_name);
+        let tyinfo =
+            process_synthetic_record(processed, printer, tyidx, &name, &tymems, RecordKind::Struct)?;
+        union_mems.push(Member::new_variant(
+            variant_name,
+            tyinfo.unwrap(),
+            variant.discriminant_value(),
+        ));
+    }
+    union_mems.push(Member::new_placeholder(0, member_size, "__HIDVA_dont_use"));
+
+    let name = format!("{}_Variants", prefix);
+    let tyinfo = process_synthetic_record(
+        processed,
+        printer,
+        tyidx,
+        &name,
+        &union_mems,
+        RecordKind::Union,
+    )?;
+    return Ok(Member::new_field(member_off, "__variant_part".to_string(), tyinfo.unwrap()));
+}
+
+// 将 tylayout 翻译为 Member, tylayout 中的元素均属于 ty, 其总大小不超过 ty_max_size.
+// vptr 为 (vptr 所属的类型, vptr 的类型), ty 及其 primary base 中的 vptr 均使用该类型. 返回值中不包含结尾的 padding.
+fn process_layout(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    tylayout: &[parser::Layout],
+    ty_max_size: u64,
+    vptr: Option<(TypeIndex, &Rc<TypeInfo>)>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Vec<Member>> {
+    let mut tymems = Vec::<Member>::with_capacity(tylayout.len());
+    let mut next_idx = find_next_idx(tylayout, 0);
+    debug_assert_eq!(next_idx.unwrap_or(0), 0);
+    while let Some(item_idx) = next_idx {
+        debug_assert_eq!(tylayout[item_idx].bit_offset % BITS_PER_BYTE, 0);
+        debug_assert!(item_idx != 0 || tylayout[item_idx].bit_offset == 0); // layout() 函数会确保从 offset: 0 开始.
+        next_idx = find_next_idx(tylayout, item_idx + 1);
+
+        let member_off = tylayout[item_idx].bit_offset / BITS_PER_BYTE;
+        // member_size item_idx 占用的空间, 字节为单位.
+        // 这里以 member_size 为准, 而不是 tylayout[item_idx].bit_size. 详见 S1218, A1218 例子.
+        let member_size = next_idx
+            .map(|v| tylayout[v].bit_offset / BITS_PER_BYTE)
+            .unwrap_or(ty_max_size)
+            - member_off;
+        // debug_assert!(tylayout[item_idx].bit_size.get().map(|v| v <= member_size).unwrap_or(true));
+        if member_size <= 0 && next_idx.is_some() {
+            continue;
+        }
+        // member_size == 0 && next_idx.is_none() 意味着 member 是最后一个元素, 如下示例所示:
+        //   struct S {int i; char ch[0];}
+        // 此时 ch member_size = 0.
+
+        // [item_idx, item_end) 之间的元素共同占据了 member_size 字节.
+        let item_end = next_idx.unwrap_or(tylayout.len());
+        if tylayout[item_idx..item_end].iter().any(is_bitfield) {
+            // 更合适的做法, 是将 tymem 拆分字段以及 padding 部分,
+            //   struct S { long i: 2; };
+            //   struct A: public S {char ch;};
+            // 不过实测这里 A.ch 并不会塞到 S padding 中, 所以拆不拆都行.
+            let bitfields = process_bitfields(
+                processed,
+                printer,
+                tyidx,
+                &tylayout[item_idx..item_end],
+                tylayout[item_idx].bit_offset,
+                member_size,
+                inputs_hash,
+                type_db,
+            )?;
+            match bitfields {
+                None => tymems.push(Member::new_placeholder(member_off, member_size, "_bitfield")),
+                Some(v) if v.is_empty() => tymems.push(Member::new_padding(member_off, member_size)),
+                Some(v) => tymems.push(Member::new_bitfield(member_off, member_size, "_bitfield", v)),
+            }
+            continue;
+        }
+
+        let (member_tyoff, member_name, is_parent, alignas) = match tylayout[item_idx].item {
+            parser::LayoutItem::Padding => {
+                tymems.push(Member::new_padding(member_off, member_size));
+                continue;
+            }
+            parser::LayoutItem::Member(mem) if is_vptr(mem) && vptr.is_some() => {
+                let vptr = vptr.unwrap().1.clone();
+                let vptr_size = vptr.packed_size;
+                tymems.push(Member::new_field(member_off, "__vptr".to_string(), vptr));
+                if vptr_size < member_size {
+                    tymems.push(Member::new_padding(member_off + vptr_size, member_size - vptr_size));
+                }
+                continue;
+            }
+            parser::LayoutItem::Member(mem) => (
+                mem.type_offset(),
+                member_name(mem.name()),
+                false,
+                mem.alignment(),
+            ),
+            parser::LayoutItem::Inherit(mem) => (
+                mem.type_offset(),
+                Cow::Owned(format!("__parent{}", uniq_id())),
+                true,
+                None,
+            ),
+            parser::LayoutItem::VariantPart(variant_part) => {
+                let member = process_variant_part(
+                    processed,
+                    printer,
+                    tyidx,
+                    ty,
+                    variant_part,
+                    member_off,
+                    member_size,
+                    inputs_hash,
+                    type_db,
+                )?;
+                tymems.push(member);
+                continue;
+            }
+        };
+        let mem_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: member_tyoff,
+        };
+        let mem_tyinfo = if is_parent {
+            parent_type_info(
+                processed,
+                printer,
+                mem_tyidx,
+                member_off,
+                Some(member_size),
+                vptr,
+                inputs_hash,
+                type_db,
+            )?
+        } else {
+            get_type_info(
+                processed,
+                printer,
+                mem_tyidx,
+                Some(member_size),
+                inputs_hash,
+                type_db,
+            )?
+        };
+        let Some(mem_tyinfo) = mem_tyinfo else {
+            tymems.push(Member::new_placeholder(member_off, member_size, "__unknown_type"));
+            continue;
+        };
+        debug_assert!(mem_tyinfo.packed_size <= member_size);
+
+        let mem_packed_size = mem_tyinfo.packed_size;
+        let member_name = member_name.into_owned();
+        let mut member = if is_parent {
+            Member::new_parent(member_off, member_name, mem_tyinfo)
+        } else {
+            Member::new_field(member_off, member_name, mem_tyinfo)
+        };
+        member.alignas = alignas;
+        tymems.push(member);
+        if mem_packed_size < member_size {
+            tymems.push(Member::new_padding(
+                member_off + mem_packed_size,
+                member_size - mem_packed_size,
+            ));
+        }
+    }
+    while let Some(member) = tymems.last() {
+        if member.is_padding() {
+            tymems.pop();
+        } else {
+            break;
+        }
+    }
+    return Ok(tymems);
+}
+
+fn process_struct_type(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let tyname = ty.type_name();
+    if ty.is_declaration() {
+        let Some(&real_tyidx) = type_db.get(&tyname) else {
+            warn!("process_struct_type: unknown declaration struct. typidx={:?} typname={}", tyidx, tyname);
+            return Ok(());
+        };
+        return handle_sym_link(
+            processed,
+            printer,
+            tyidx,
+            real_tyidx,
+            ty_max_size,
+            inputs_hash,
+            type_db,
+        );
+    }
+
+    let Some(mut ty_bit_size) = ty.bit_size() else {
+        warn!("process_struct_type: unknown type size: tyidx={:?} tyname={}", tyidx, ty.type_name());
+        return Ok(());
+    };
+    let ty_dwarf_size = ty.byte_size().unwrap();
+    // 虚基类位于 layout 结尾的 padding 中, 不能超过 ty_max_size.
+    let vbase_max_size = ty_max_size.map_or(ty_dwarf_size, |v| std::cmp::min(v, ty_dwarf_size));
+    let mut tylayout = ty.layout(&inputs_hash[tyidx.input_id]);
+    while let Some(lastlayout) = tylayout.last() {
+        if let parser::LayoutItem::Padding = lastlayout.item {
+            let s = lastlayout.bit_size.get().unwrap();
+            debug_assert!(ty_bit_size >= s);
+            ty_bit_size -= s;
+            tylayout.pop();
+        } else {
+            break;
+        }
+    }
+    let tysize = bit2byte(ty_bit_size);
+    let ty_max_size = match ty_max_size {
+        Some(v) => {
+            if v > tysize {
+                tysize
+            } else {
+                v
+            }
+        }
+        None => tysize,
+    };
+
+    let tylayout = merge_discriminant(tylayout, ty.members(), &inputs_hash[tyidx.input_id]);
+    processed.pending.insert(tyidx);
+    let vptr = process_vtable(processed, printer, tyidx, ty, inputs_hash, type_db)?;
+    let mut tymems = process_layout(
+        processed,
+        printer,
+        tyidx,
+        ty,
+        &tylayout,
+        ty_max_size,
+        vptr.as_ref().map(|v| (tyidx, v)),
+        inputs_hash,
+        type_db,
+    )?;
+    let mut vbases = Vec::new();
+    collect_virtual_bases(tyidx, ty, inputs_hash, type_db, &mut vbases);
+    if !vbases.is_empty() {
+        process_virtual_bases(
+            processed,
+            printer,
+            tyidx,
+            &tylayout,
+            &mut tymems,
+            vbases,
+            vbase_max_size,
+            vptr.as_ref().map(|v| (tyidx, v)),
+            inputs_hash,
+            type_db,
+        )?;
+    }
+
+    let ident = record_ident(processed, printer, tyidx, &tyname);
+    return process_members(
+        processed,
+        printer,
+        tyidx,
+        &ty.type_name(),
+        &tymems,
+        RecordKind::Struct,
+        ident,
+        Some(ty_dwarf_size),
+        ty.alignment(),
+    );
+}
+
+fn process_enum_type(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::EnumerationType,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let tyname = ty.type_name();
+    if ty.is_declaration() {
+        let Some(&real_tyidx) = type_db.get(&tyname) else {
+            warn!("process_enum_type: unknown declaration. typidx={:?} typname={}", tyidx, &tyname);
+            return Ok(());
+        };
+        return handle_sym_link(
+            processed,
+            printer,
+            tyidx,
+            real_tyidx,
+            ty_max_size,
+            inputs_hash,
+            type_db,
+        );
+    }
+    let Some(ty_size) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
+        warn!("process_enum_type: unknown byte size. typidx={:?} typname={}", tyidx, ty.type_name());
+        return Ok(());
+    };
+    if let Some(ty_max_size) = ty_max_size {
+        if ty_size > ty_max_size {
+            warn!(
+                "process_enum_type: invalid byte size. typidx={:?} typname={} expect={} actual={}",
+                tyidx,
+                ty.type_name(),
+                ty_max_size,
+                ty_size
+            );
+            return Ok(());
+        }
+    }
+
+    // EnumerationType::ty may be none, 此时与 C 一样认为是有符号的.
+    let hash = &inputs_hash[tyidx.input_id];
+    let signed = enum_underlying_signed(ty, hash).unwrap_or(true);
+    if ![1, 2, 4, 8].contains(&ty_size) {
+        warn!(
+            "process_enum_type: invalid byte size. typidx={:?} typname={} expect=8/4/2/1 actual={}",
+            tyidx,
+            ty.type_name(),
+            ty_size
+        );
+        return Ok(());
+    }
+    let repr = TypeRef::Base {
+        name: format!("__{}{}", if signed { 's' } else { 'u' }, ty_size * BITS_PER_BYTE),
+        size: ty_size,
+        encoding: if signed {
+            parser::BaseTypeEncoding::Signed
+        } else {
+            parser::BaseTypeEncoding::Unsigned
+        },
+    };
+    let tydef = printer.alloc_ident(&tyname);
+    let mut enumerators = Vec::new();
+    for enumerator in ty.enumerators(hash) {
+        let Some(name) = enumerator.name() else {
+            warn!("process_enum_type: anonymous enumerator. typidx={:?} typname={}", tyidx, &tyname);
+            continue;
+        };
+        let value = if signed {
+            enumerator.value().map(i128::from)
+        } else {
+            enumerator.udata_value().map(i128::from)
+        };
+        enumerators.push(EnumItem {
+            name: name.to_string(),
+            ident: printer.alloc_enumerator_ident(&tydef, name),
+            value,
+        });
+    }
+    printer.add_enum(&EnumDef {
+        ident: &tydef,
+        tyname: tyname.to_string(),
+        tyidx,
+        repr: &repr,
+        enumerators: &enumerators,
+        size: ty_size,
+    })?;
+
+    processed.types.insert(
+        tyidx,
+        Some(Rc::new(TypeInfo {
+            ty: TypeRef::Alias(tydef),
+            packed_size: ty_size,
+            size: ty_size,
+            align: ty_size,
+        })),
+    );
+    return Ok(());
+}
+
+// 返回 enum 底层类型是否有符号, 底层类型可能是 typedef, 比如 `enum E : int32_t`.
+fn enum_underlying_signed(ty: &parser::EnumerationType, hash: &parser::FileHash) -> Option<bool> {
+    let mut underlying = ty.ty(hash)?;
+    loop {
+        match underlying.kind() {
+            parser::TypeKind::Base(v) => {
+                use parser::BaseTypeEncoding::*;
+                return Some(matches!(v.encoding(), Signed | SignedChar));
+            }
+            parser::TypeKind::Def(v) => underlying = v.ty(hash)?,
+            parser::TypeKind::Modifier(v) => underlying = v.ty(hash)?,
+            _ => return None,
+        }
+    }
+}
+
+fn process_array_type(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::ArrayType,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let mem_tyidx = TypeIndex {
+        input_id: tyidx.input_id,
+        typoff: ty.ty,
+    };
+    let mem_tyinfo = get_type_info(processed, printer, mem_tyidx, None, inputs_hash, type_db)?;
+    let Some(mem_tyinfo) = mem_tyinfo else {
+        warn!("process_array_type: unknown element type: tyidx={:?} ty={:?}", tyidx, ty);
+        return Ok(());
+    };
+
+    let mem_tyident = mem_tyinfo.ident();
+    let mut elem_ty = mem_tyinfo.ty.clone();
+    if mem_tyinfo.size > mem_tyinfo.packed_size {
+        // 以 S1218 为例, 我们在生成 S1218 是 packed 的, 但如果 S1218 作为 array element, 则
+        // 需要保持其原有 padding.
+        let padded_ident = {
+            let name = format!("{}_Padded", mem_tyident);
+            printer.alloc_ident(&parser::TypeName {
+                namespace: None,
+                name: Some(&name),
+            })
+        };
+        let mut members = Vec::<Member>::new();
+        members.push(Member::new_field(0, "data".to_string(), mem_tyinfo.clone()));
+        members.push(Member::new_padding(
+            mem_tyinfo.packed_size,
+            mem_tyinfo.size - mem_tyinfo.packed_size,
+        ));
+        process_members(
+            processed,
+            printer,
+            tyidx,
+            &parser::TypeName {
+                namespace: None,
+                name: Some("padding struct"),
+            },
+            &members,
+            RecordKind::Struct,
+            padded_ident.clone(),
+            None,
+            None,
+        )?;
+        elem_ty = TypeRef::Struct(padded_ident);
+    }
+
+    let ele_cnt = if ty_max_size == Some(0) {
+        0
+    } else {
+        let Some(array_byte_size) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
+            warn!("process_array_type: unknown array size: tyidx={:?} ty={:?}", tyidx, ty);
+            return Ok(());
+        };
+        if let Some(max_size) = ty_max_size {
+            if max_size < array_byte_size {
+                return Ok(());
+            }
+        }
+        let Some(ele_count) = ty.count(&inputs_hash[tyidx.input_id]) else {
+            warn!("process_array_type: unknown element count: tyidx={:?} ty={:?}", tyidx, ty);
+            return Ok(());
+        };
+        if array_byte_size % ele_count != 0 || mem_tyinfo.size != array_byte_size / ele_count {
+            warn!(
+                "process_array_type: invalid array def: tyidx={:?} ty={:?}",
+                tyidx, ty
+            );
+            return Ok(());
+        }
+        ele_count
+    };
+    let array_name = {
+        let name = format!("{}_Array{}", mem_tyident, ele_cnt);
+        let tyname = parser::TypeName {
+            namespace: None,
+            name: Some(&name),
+        };
+        printer.alloc_ident(&tyname)
+    };
+    let array_size = mem_tyinfo.size * ele_cnt;
+
+    printer.add_array(&ArrayDef {
+        ident: &array_name,
+        tyidx,
+        elem: &elem_ty,
+        count: ele_cnt,
+        size: array_size,
+    })?;
+    processed.types.insert(
+        tyidx,
+        Some(Rc::new(TypeInfo {
+            ty: TypeRef::Alias(array_name),
+            packed_size: array_size,
+            size: array_size,
+            align: mem_tyinfo.align,
+        })),
+    );
+    return Ok(());
+}
+
+fn process_modifier_type(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::TypeModifier,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<()> {
+    let real_tyidx = TypeIndex {
+        input_id: tyidx.input_id,
+        typoff: ty.ty,
+    };
+    match ty.kind() {
+        parser::TypeModifierKind::Const | parser::TypeModifierKind::Volatile if processed.typedefs => {
+            handle_sym_link(
+                processed,
+                printer,
+                tyidx,
+                real_tyidx,
+                ty_max_size,
+                inputs_hash,
+                type_db,
+            )?;
+            let qualifier = match ty.kind() {
+                parser::TypeModifierKind::Const => Qualifier::Const,
+                _ => Qualifier::Volatile,
+            };
+            wrap_type_info(processed, tyidx, |real| TypeRef::Qualified { qualifier, real });
+        }
+        parser::TypeModifierKind::Const
+        | parser::TypeModifierKind::Packed
+        | parser::TypeModifierKind::Volatile
+        | parser::TypeModifierKind::Restrict
+        | parser::TypeModifierKind::Shared
+        | parser::TypeModifierKind::Atomic
+        | parser::TypeModifierKind::Other => {
+            return handle_sym_link(
+                processed,
+                printer,
+                tyidx,
+                real_tyidx,
+                ty_max_size,
+                inputs_hash,
+                type_db,
+            );
+        }
+        parser::TypeModifierKind::Pointer
+        | parser::TypeModifierKind::Reference
+        | parser::TypeModifierKind::RvalueReference => {
+            let Some(tysize) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
+                warn!("process_modifier_type: unknown byte size: tyidx={:?}", tyidx);
+                return Ok(());
+            };
+            if let Some(maxsize) = ty_max_size {
+                if maxsize < tysize {
+                    warn!(
+                        "process_modifier_type: invalid byte size: tyidx={:?} maxsize={} size={}",
+                        tyidx, maxsize, tysize
+                    );
+                    return Ok(());
+                }
+            }
 
-// 暂时还不支持 `long l:32` 这种情况...
-fn is_bitfield(l: &parser::Layout) -> bool {
-    let Some(s) = l.bit_size.get() else {
-        return false;
-    };
-    return s % BITS_PER_BYTE != 0;
-}
+            // 指向的类型可能会再次引用该指针, 比如从 `Node*` 开始处理 `struct Node { Node* next; }`, 此时 Node 已经在
+            // pending 中了, 应该通过 forward_decl 得到该指针, 而不是返回 process_type 中占坑用的 None.
+            processed.types.remove(&tyidx);
+            if let Some(func_tyidx) = resolve_function(real_tyidx, inputs_hash) {
+                return process_func_pointer(
+                    processed,
+                    printer,
+                    tyidx,
+                    func_tyidx,
+                    tysize,
+                    inputs_hash,
+                    type_db,
+                );
+            }
+            let mut real_tyinfo = if should_defer(processed, real_tyidx, inputs_hash, type_db) {
+                None
+            } else {
+                get_type_info(processed, printer, real_tyidx, None, inputs_hash, type_db)?
+            };
+            if real_tyinfo.is_none() {
+                real_tyinfo = forward_decl(processed, printer, real_tyidx, inputs_hash, type_db)?;
+            }
+            let ty = TypeRef::Pointer(real_tyinfo);
 
-fn is_valid_ident(input: &str) -> bool {
-    let ret = input.trim_end_matches(is_ident_char);
-    return ret.is_empty();
+            printer.add_size_check(&ty, tysize)?;
+            processed.types.insert(
+                tyidx,
+                Some(Rc::new(TypeInfo {
+                    ty,
+                    packed_size: tysize,
+                    size: tysize,
+                    align: tysize,
+                })),
+            );
+        }
+    }
+    return Ok(());
 }
 
-fn member_name(input: Option<&str>) -> Cow<str> {
-    let Some(input) = input else {
-        return Cow::Owned(format!("__anon{}", uniq_id()));
-    };
-    if is_valid_ident(input) {
-        return Cow::Borrowed(input);
+// 沿着 typedef, const 等找到 tyidx 最终对应的函数类型.
+fn resolve_function(mut tyidx: TypeIndex, inputs_hash: &[parser::FileHash]) -> Option<TypeIndex> {
+    loop {
+        let typ = parser::Type::from_offset(&inputs_hash[tyidx.input_id], tyidx.typoff)?;
+        match typ.kind() {
+            parser::TypeKind::Function(_) => return Some(tyidx),
+            parser::TypeKind::Def(ty) => tyidx.typoff = ty.ty,
+            parser::TypeKind::Modifier(ty)
+                if !matches!(
+                    ty.kind(),
+                    parser::TypeModifierKind::Pointer
+                        | parser::TypeModifierKind::Reference
+                        | parser::TypeModifierKind::RvalueReference
+                ) =>
+            {
+                tyidx.typoff = ty.ty
+            }
+            _ => return None,
+        }
     }
-    return Cow::Owned(format!("__mem{}", uniq_id()));
 }
 
-struct EqAssert {
-    expr: String,
-    val: u64,
+struct FuncSignature {
+    // None 意味着返回 void.
+    ret: Option<Rc<TypeInfo>>,
+    params: Vec<TypeRef>,
 }
 
-// tydef, 形如 `union U`, `struct S` 这种,
-// tymems 最后一个 tymem off + len 为 ty_size.
-fn process_members(
+// 返回值或者任意一个参数的类型无法处理时返回 None.
+fn func_signature(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    input_id: usize,
+    ret_typoff: parser::TypeOffset,
+    param_typoffs: &[parser::TypeOffset],
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<FuncSignature>> {
+    let ret_tyidx = TypeIndex {
+        input_id,
+        typoff: ret_typoff,
+    };
+    let ret = if ret_tyidx.typoff.is_none() {
+        None
+    } else {
+        let ret = get_type_info(processed, printer, ret_tyidx, None, inputs_hash, type_db)?;
+        let Some(ret) = ret else {
+            return Ok(None);
+        };
+        Some(ret)
+    };
+    let mut params = Vec::new();
+    for &param_typoff in param_typoffs {
+        let param_tyidx = TypeIndex {
+            input_id,
+            typoff: param_typoff,
+        };
+        let param = get_type_info(processed, printer, param_tyidx, None, inputs_hash, type_db)?;
+        let Some(param) = param else {
+            return Ok(None);
+        };
+        params.push(param.ty.clone());
+    }
+    return Ok(Some(FuncSignature { ret, params }));
+}
+
+// 函数指针, 输出为 `typedef ret (*ident)(params);`. 返回值或者参数的类型无法处理时退化为 `void*`.
+fn process_func_pointer(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
-    tyname: &parser::TypeName,
-    tymems: &[Member],
-    tydef: &str,
-    tysize: Option<u64>,
+    func_tyidx: TypeIndex,
+    tysize: u64,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
 ) -> io::Result<()> {
-    let mut asserts = Vec::<EqAssert>::new();
-    let mut struct_def = Vec::<String>::new();
+    let typ = parser::Type::from_offset(&inputs_hash[func_tyidx.input_id], func_tyidx.typoff);
+    let typ = typ.unwrap();
+    let parser::TypeKind::Function(func) = typ.kind() else {
+        unreachable!()
+    };
+    let param_typoffs: Vec<_> = func.parameters().iter().map(|v| v.type_offset()).collect();
+    let signature = func_signature(
+        processed,
+        printer,
+        func_tyidx.input_id,
+        func.return_type_offset(),
+        &param_typoffs,
+        inputs_hash,
+        type_db,
+    )?;
+    if signature.is_none() {
+        warn!("process_func_pointer: unknown signature. tyidx={:?}", tyidx);
+    }
+    let tyinfo = func_pointer_type_info(processed, printer, tyidx, None, signature, func.is_variadic(), tysize)?;
+    processed.types.insert(tyidx, Some(tyinfo));
+    return Ok(());
+}
 
-    struct_def.push(format!("// tyname={} tyidx={:?}", tyname, tyidx));
-    struct_def.push(format!("{} {{", tydef));
-    for tymem in tymems {
-        tymem.print(tydef, &mut struct_def, &mut asserts);
+// 函数指针的名字以及签名: 返回值, 各个参数的 c_name, 是否 variadic 以及函数指针的大小.
+type FuncPtrKey = (Option<String>, Option<String>, Vec<String>, bool, u64);
+
+// 函数指针 typedef 名字中返回值的部分, 基础类型使用完整的名字, 比如 `long unsigned int` 为 `long_unsigned_int`.
+fn func_pointer_ret_name(ret: Option<&TypeRef>) -> String {
+    match ret {
+        None => "void".to_string(),
+        Some(TypeRef::Base { name, .. }) => {
+            let words: Vec<_> = name.split_whitespace().map(ident_part).collect();
+            words.join("_")
+        }
+        Some(TypeRef::Pointer(Some(tyinfo))) => func_pointer_ret_name(Some(&tyinfo.ty)),
+        Some(TypeRef::Qualified { real, .. }) => func_pointer_ret_name(Some(&real.ty)),
+        Some(ty) => ty.ident().to_string(),
     }
-    struct_def.push("} __attribute__((__packed__));".to_string());
+}
 
-    let Some(packed_size) = tymems.last().map(|v|v.off + v.len) else {
-        return Ok(());
+// name 为 None 时 typedef 的名字为 `{ret}_FuncPtr{参数个数}`, 签名相同的函数指针共用同一个 typedef.
+// 否则为 `{name}_fn`, 比如 vtable 中的 `Derived_area_fn`, name 与签名均相同时才会共用.
+fn func_pointer_type_info(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    name: Option<&str>,
+    signature: Option<FuncSignature>,
+    variadic: bool,
+    tysize: u64,
+) -> io::Result<Rc<TypeInfo>> {
+    let ty = match signature {
+        Some(FuncSignature { ret, params }) => {
+            let ret = ret.as_ref().map(|v| &v.ty);
+            let key = (
+                name.map(|v| v.to_string()),
+                ret.map(|v| v.c_name()),
+                params.iter().map(|v| v.c_name()).collect(),
+                variadic,
+                tysize,
+            );
+            if let Some(tyinfo) = processed.func_pointers.get(&key) {
+                return Ok(tyinfo.clone());
+            }
+            let name = match name {
+                Some(name) => format!("{}_fn", name),
+                None => format!("{}_FuncPtr{}", func_pointer_ret_name(ret), params.len()),
+            };
+            let ident = printer.alloc_ident(&parser::TypeName {
+                namespace: None,
+                name: Some(&name),
+            });
+            printer.add_func_pointer(&FuncPtrDef {
+                ident: &ident,
+                tyidx,
+                ret,
+                params: &params,
+                variadic,
+                size: tysize,
+            })?;
+            let tyinfo = Rc::new(TypeInfo {
+                ty: TypeRef::Alias(ident),
+                packed_size: tysize,
+                size: tysize,
+                align: tysize,
+            });
+            processed.func_pointers.insert(key, tyinfo.clone());
+            return Ok(tyinfo);
+        }
+        None => {
+            let ty = TypeRef::Pointer(None);
+            printer.add_size_check(&ty, tysize)?;
+            ty
+        }
     };
-    asserts.push(EqAssert {
-        expr: format!("sizeof({})", tydef),
-        val: packed_size,
-    });
+    return Ok(Rc::new(TypeInfo {
+        ty,
+        packed_size: tysize,
+        size: tysize,
+        align: tysize,
+    }));
+}
 
-    printer.add_type(&struct_def)?;
-    printer.add_eq_asserts(&asserts)?;
-    if let Some(tysize) = tysize {
-        processed.insert(
-            tyidx,
-            Some(Rc::new(TypeInfo {
-                name: tydef.to_string(),
-                packed_size,
-                size: tysize,
-            })),
-        );
-    }
-    return Ok(());
+// Itanium C++ ABI 中 ptrdiff_t/函数地址对应的基础类型.
+fn itanium_word(
+    printer: &mut Printer,
+    size: u64,
+    signed: bool,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let name = match (size, signed) {
+        (8, true) => "long int",
+        (8, false) => "long unsigned int",
+        (4, true) => "int",
+        (4, false) => "unsigned int",
+        _ => return Ok(None),
+    };
+    let encoding = if signed {
+        parser::BaseTypeEncoding::Signed
+    } else {
+        parser::BaseTypeEncoding::Unsigned
+    };
+    let ty = TypeRef::Base {
+        name: name.to_string(),
+        size,
+        encoding,
+    };
+    printer.add_size_check(&ty, size)?;
+    return Ok(Some(Rc::new(TypeInfo {
+        ty,
+        packed_size: size,
+        size,
+        align: size,
+    })));
 }
 
-fn process_union_type(
+// 按照 Itanium C++ ABI, 数据成员指针为成员的偏移, 不指向任何成员时为 -1. 成员函数指针为 `{ptr, adj}`,
+// ptr 为函数地址, 对于虚函数则为 1 + 其在 vtable 中的偏移, adj 为调用时 this 需要调整的字节数.
+fn process_ptr_to_member(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
-    ty: &parser::UnionType,
-    // ty_max_size 是用来处理 C++ 中 A1218, S1218 示例展示的重用 padding 问题,
-    // union 没有这种问题, 所以可以忽略 ty_max_size.
+    ty: &parser::PointerToMemberType,
     ty_max_size: Option<u64>,
     inputs_hash: &[parser::FileHash],
-    type_db: &HashMap<parser::TypeName, TypeIndex>,
 ) -> io::Result<()> {
-    let tyname = ty.type_name();
-    if ty.is_declaration() {
-        let Some(&real_tyidx) = type_db.get(&tyname) else {
-            warn!("process_union_type: unknown declaration union. typidx={:?} typname={}", tyidx, &tyname);
-            return Ok(());
-        };
-        return handle_sym_link(
-            processed,
-            printer,
-            tyidx,
-            real_tyidx,
-            ty_max_size,
-            inputs_hash,
-            type_db,
-        );
-    }
-    let Some(ty_size) = ty.byte_size() else {
-        warn!("process_union_type: unknown byte size typidx={:?} typname={}", tyidx, ty.type_name());
+    let hash = &inputs_hash[tyidx.input_id];
+    let Some(tysize) = ty.byte_size(hash) else {
+        warn!("process_ptr_to_member: unknown byte size: tyidx={:?}", tyidx);
         return Ok(());
     };
-    if let Some(ty_max_size) = ty_max_size {
-        if ty_size > ty_max_size {
-            warn!(
-                "process_union_type: invalid byte size typidx={:?} typname={} expect={} actual={}",
-                tyidx,
-                ty.type_name(),
-                ty_max_size,
-                ty_size
-            );
+    if ty_max_size.is_some_and(|v| v < tysize) {
+        warn!(
+            "process_ptr_to_member: invalid byte size: tyidx={:?} maxsize={:?} size={}",
+            tyidx, ty_max_size, tysize
+        );
+        return Ok(());
+    }
+    let member_tyidx = ty.member_type(hash).map(|v| TypeIndex {
+        input_id: tyidx.input_id,
+        typoff: v.offset(),
+    });
+    let is_func = member_tyidx.is_some_and(|v| resolve_function(v, inputs_hash).is_some());
+    let mut members = Vec::new();
+    if is_func {
+        let word_size = tysize / 2;
+        let (Some(ptr), Some(adj)) = (
+            itanium_word(printer, word_size, false)?,
+            itanium_word(printer, word_size, true)?,
+        ) else {
+            warn!("process_ptr_to_member: invalid byte size: tyidx={:?} size={}", tyidx, tysize);
             return Ok(());
-        }
+        };
+        members.push(Member::new_field(0, "ptr".to_string(), ptr));
+        members.push(Member::new_field(word_size, "adj".to_string(), adj));
+    } else {
+        let Some(offset) = itanium_word(printer, tysize, true)? else {
+            warn!("process_ptr_to_member: invalid byte size: tyidx={:?} size={}", tyidx, tysize);
+            return Ok(());
+        };
+        members.push(Member::new_field(0, "offset".to_string(), offset));
     }
 
-    let mut tymems = Vec::<Member>::new();
-    for union_mem in ty.members() {
-        if union_mem.bit_offset() != 0 {
-            warn!(
-                "process_union_type: union_mem.bit_offset != 0! typidx={:?} typname={} member={:?}",
-                tyidx,
-                ty.type_name(),
-                union_mem
-            );
-            return Ok(());
+    let containing = ty.containing_type(hash);
+    let containing = containing.as_deref().and_then(parser::TypeName::try_from);
+    let name = format!(
+        "{}_{}",
+        containing.and_then(|v| v.name).map_or("", ident_part),
+        if is_func { "MemFuncPtr" } else { "MemberPtr" }
+    );
+    let ident = printer.alloc_ident(&parser::TypeName {
+        namespace: None,
+        name: Some(name.trim_start_matches('_')),
+    });
+    return process_members(
+        processed,
+        printer,
+        tyidx,
+        &parser::TypeName {
+            namespace: None,
+            name: Some("pointer to member"),
+        },
+        &members,
+        RecordKind::Struct,
+        ident,
+        Some(tysize),
+        None,
+    );
+}
+
+// vptr 成员, gcc 中名为 `_vptr.Base`, clang 中名为 `_vptr$Base`.
+fn is_vptr(mem: &parser::Member) -> bool {
+    return mem.is_artificial() && mem.name().is_some_and(|v| v.starts_with("_vptr"));
+}
+
+#[derive(Clone)]
+struct VirtualMethod {
+    input_id: usize,
+    // 声明该虚函数的类型的名字, 用于函数指针 typedef 的名字.
+    class: String,
+    name: Option<String>,
+    ret: parser::TypeOffset,
+    params: Vec<parser::TypeOffset>,
+    variadic: bool,
+    // gcc 中没有记录 slot 的虚析构函数.
+    dtor: bool,
+}
+
+// 按照 Itanium C++ ABI, 类与其 primary base 共用同一个 vptr, 类的 vtable 以 primary base 的 vtable 为前缀.
+// 这里将 offset 为 0 的第一个 dynamic base 视为 primary base, 沿之收集所有虚函数, 子类 override 的函数会覆盖父类中
+// 同一 slot 的函数. gcc 不会记录虚析构函数的 slot, 其保存在 dtor 中, 子类中的覆盖父类中的.
+// 返回 vptr 的大小, 为 None 意味着 tyidx 不是 dynamic class.
+fn collect_virtual_methods(
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+    slots: &mut BTreeMap<u64, VirtualMethod>,
+    dtor: &mut Option<VirtualMethod>,
+) -> Option<u64> {
+    let hash = &inputs_hash[tyidx.input_id];
+    let mut vptr_size = None;
+    for inherit in ty.inherits() {
+        if inherit.is_virtual() || inherit.bit_offset() != 0 {
+            continue;
         }
-        let Some(union_mem_bit_size) = union_mem.bit_size(&inputs_hash[tyidx.input_id]) else {
-            warn!("process_union_type: unknown member size! typidx={:?} typname={} member={:?}", tyidx, ty.type_name(), union_mem);
-            return Ok(());
+        let base_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: inherit.type_offset(),
         };
-        let member_size = bit2byte(union_mem_bit_size);
-        let tylayout = &parser::Layout {
-            bit_offset: 0,
-            bit_size: parser::Size::new(union_mem_bit_size),
-            item: parser::LayoutItem::Member(union_mem),
+        let Some(base_tyidx) = resolve_record(base_tyidx, inputs_hash, type_db) else {
+            continue;
+        };
+        let Some(typ) = parser::Type::from_offset(&inputs_hash[base_tyidx.input_id], base_tyidx.typoff) else {
+            continue;
+        };
+        let parser::TypeKind::Struct(base) = typ.kind() else {
+            continue;
+        };
+        vptr_size = collect_virtual_methods(base_tyidx, base, inputs_hash, type_db, slots, dtor);
+        if vptr_size.is_some() {
+            break;
+        }
+    }
+    if let Some(vptr) = ty.members().iter().find(|v| is_vptr(v)) {
+        vptr_size = vptr.bit_size(hash).map(bit2byte);
+    }
+    let class = ident_part(ty.name().unwrap_or("")).to_string();
+    for method in ty.methods(hash) {
+        let mut params: Vec<_> = method.parameters().iter().map(|v| v.type_offset()).collect();
+        let Some(slot) = method.vtable_elem_location() else {
+            if method.is_virtual() && method.name().is_some_and(|v| v.starts_with('~')) {
+                // vtable 中的 complete/deleting 析构函数只有 this 一个参数, 没有 gcc 为虚基类加上的 __in_chrg 等参数.
+                params.truncate(1);
+                *dtor = Some(VirtualMethod {
+                    input_id: tyidx.input_id,
+                    class: class.clone(),
+                    name: method.name().map(|v| v.to_string()),
+                    ret: method.return_type_offset(),
+                    params,
+                    variadic: false,
+                    dtor: true,
+                });
+            }
+            continue;
+        };
+        slots.insert(
+            slot,
+            VirtualMethod {
+                input_id: tyidx.input_id,
+                class: class.clone(),
+                name: method.name().map(|v| v.to_string()),
+                ret: method.return_type_offset(),
+                params,
+                variadic: method.is_variadic(),
+                dtor: false,
+            },
+        );
+    }
+    return vptr_size;
+}
+
+// vptr 指向 vtable 中第一个虚函数所在的位置, 之前的 offset-to-top, typeinfo 指针不包含在 `{Class}_VTable` 中.
+// 每一个虚函数对应一个函数指针成员, 其第一个参数为 this. gcc 不会记录虚析构函数的 slot, 其 complete 与 deleting
+// 两个 slot 为剩下的唯一一对相邻的空闲 slot, 没有空闲 slot 时位于最后, 输出为 `__dtor_complete`, `__dtor_deleting`.
+// 其他 dwarf 中没有记录的 slot 输出为 padding. 返回 vptr 的类型, 即指向 `{Class}_VTable` 的指针.
+fn process_vtable(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    if let Some(vptr) = processed.vptrs.get(&tyidx) {
+        return Ok(vptr.clone());
+    }
+    let vptr = do_process_vtable(processed, printer, tyidx, ty, inputs_hash, type_db)?;
+    processed.vptrs.insert(tyidx, vptr.clone());
+    return Ok(vptr);
+}
+
+fn do_process_vtable(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let mut slots = BTreeMap::new();
+    let mut dtor = None;
+    let Some(vptr_size) = collect_virtual_methods(tyidx, ty, inputs_hash, type_db, &mut slots, &mut dtor) else {
+        return Ok(None);
+    };
+    if let Some(dtor) = dtor {
+        let end = slots.keys().next_back().map_or(0, |v| v + 1);
+        let free: Vec<_> = (0..end).filter(|v| !slots.contains_key(v)).collect();
+        let dtor_slot = match free[..] {
+            [] => Some(end),
+            [complete, deleting] if deleting == complete + 1 => Some(complete),
+            _ => None,
         };
-
-        if is_bitfield(tylayout) {
-            tymems.push(Member::new_placeholder(0, member_size, "__bitfield"));
-            continue;
+        if let Some(slot) = dtor_slot {
+            slots.insert(slot, dtor.clone());
+            slots.insert(slot + 1, dtor);
         }
+    }
+    let mut vbases = Vec::new();
+    collect_virtual_bases(tyidx, ty, inputs_hash, type_db, &mut vbases);
+    if slots.is_empty() && vbases.is_empty() {
+        return Ok(None);
+    }
+    if !vbases.is_empty() {
+        process_vtable_prefix(
+            processed,
+            printer,
+            tyidx,
+            ty,
+            &vbases,
+            vptr_size,
+            inputs_hash,
+        )?;
+    }
 
-        let member_tyoff = union_mem.type_offset();
-        let member_name = member_name(union_mem.name());
-        let mem_tyidx = TypeIndex {
-            input_id: tyidx.input_id,
-            typoff: member_tyoff,
-        };
-        let mem_tyinfo = get_type_info(
+    let mut members = Vec::with_capacity(slots.len());
+    let mut used_names = HashSet::new();
+    let mut next_off = 0;
+    let mut dtor_names = ["__dtor_complete", "__dtor_deleting"].into_iter();
+    for (slot, method) in slots {
+        let off = slot * vptr_size;
+        if off > next_off {
+            members.push(Member::new_padding(next_off, off - next_off));
+        }
+        next_off = off + vptr_size;
+        let signature = func_signature(
             processed,
             printer,
-            mem_tyidx,
-            Some(member_size),
+            method.input_id,
+            method.ret,
+            &method.params,
             inputs_hash,
             type_db,
         )?;
-        let Some(mem_tyinfo) = mem_tyinfo else {
-            tymems.push(Member::new_placeholder(0, member_size, "__unknown_type"));
-            continue;
+        if signature.is_none() {
+            warn!("process_vtable: unknown signature. tyidx={:?} slot={}", tyidx, slot);
+        }
+        let fn_name = match &method.name {
+            _ if method.dtor => format!("{}_dtor", method.class),
+            Some(name) if is_valid_ident(name) => format!("{}_{}", method.class, name),
+            _ => format!("{}_slot{}", method.class, slot),
         };
-        debug_assert!(mem_tyinfo.packed_size <= member_size);
-
-        tymems.push(Member {
-            off: 0,
-            len: mem_tyinfo.packed_size,
-            def: format!("{} {}", &mem_tyinfo.name, &member_name),
-            field_name: member_name.into_owned(),
-            is_padding: false,
-        });
+        let fn_name = fn_name.trim_start_matches('_');
+        let slot_tyinfo = func_pointer_type_info(
+            processed,
+            printer,
+            tyidx,
+            Some(fn_name),
+            signature,
+            method.variadic,
+            vptr_size,
+        )?;
+        let name = match method.name {
+            _ if method.dtor => dtor_names.next().unwrap_or("__dtor").to_string(),
+            Some(name) if is_valid_ident(&name) && !used_names.contains(&name) => name,
+            // 重载的虚函数.
+            Some(name) if is_valid_ident(&name) => format!("{}{}", name, slot),
+            _ => format!("__slot{}", slot),
+        };
+        used_names.insert(name.clone());
+        members.push(Member::new_field(off, name, slot_tyinfo));
+    }
+    if members.is_empty() {
+        // 仅因为虚基类而含有 vptr, 此时 vptr 之后没有任何虚函数, `{Class}_VTable` 为空.
+        members.push(Member::new_padding(0, 0));
     }
-    tymems.push(Member::new_placeholder(0, ty_size, "__HIDVA_dont_use"));
 
-    let tydef = format!("union {}", printer.alloc_ident(&tyname));
-    return process_members(
+    let name = format!("{}_VTable", ty.name().map_or("", ident_part));
+    let vtable = process_synthetic_record(
         processed,
         printer,
         tyidx,
-        &ty.type_name(),
-        &tymems,
-        &tydef,
-        Some(ty_size),
-    );
+        name.trim_start_matches('_'),
+        &members,
+        RecordKind::Struct,
+    )?;
+    let ty = TypeRef::Pointer(vtable);
+    printer.add_size_check(&ty, vptr_size)?;
+    return Ok(Some(Rc::new(TypeInfo {
+        ty,
+        packed_size: vptr_size,
+        size: vptr_size,
+        align: vptr_size,
+    })));
 }
 
-fn process_struct_type(
+// vptr 指向的位置之前依次为 vbase offset, offset-to-top 与 typeinfo 指针, 输出为 `{Class}_VTablePrefix`, 其结束于
+// vptr 指向的位置, 即 `((struct S_VTablePrefix*)p->__vptr)[-1]`. 这里只包含 ty 直接声明的虚基类的 vbase offset,
+// 其他虚基类的 vbase offset 位于声明它的类型的 vtable 中, 其位置详见 VirtualBase::c_comment.
+fn process_vtable_prefix(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
     ty: &parser::StructType,
-    ty_max_size: Option<u64>,
+    vbases: &[VirtualBase],
+    vptr_size: u64,
     inputs_hash: &[parser::FileHash],
-    type_db: &HashMap<parser::TypeName, TypeIndex>,
 ) -> io::Result<()> {
-    let tyname = ty.type_name();
-    if ty.is_declaration() {
-        let Some(&real_tyidx) = type_db.get(&tyname) else {
-            warn!("process_struct_type: unknown declaration struct. typidx={:?} typname={}", tyidx, tyname);
-            return Ok(());
-        };
-        return handle_sym_link(
-            processed,
-            printer,
-            tyidx,
-            real_tyidx,
-            ty_max_size,
-            inputs_hash,
-            type_db,
-        );
-    }
-
-    let Some(mut ty_bit_size) = ty.bit_size() else {
-        warn!("process_struct_type: unknown type size: tyidx={:?} tyname={}", tyidx, ty.type_name());
+    let Some(word) = itanium_word(printer, vptr_size, true)? else {
         return Ok(());
     };
-    let ty_dwarf_size = ty.byte_size().unwrap();
-    let mut tylayout = ty.layout(&inputs_hash[tyidx.input_id]);
-    while let Some(lastlayout) = tylayout.last() {
-        if let parser::LayoutItem::Padding = lastlayout.item {
-            let s = lastlayout.bit_size.get().unwrap();
-            debug_assert!(ty_bit_size >= s);
-            ty_bit_size -= s;
-            tylayout.pop();
-        } else {
-            break;
+    let typeinfo = TypeRef::Pointer(None);
+    printer.add_size_check(&typeinfo, vptr_size)?;
+    let typeinfo = Rc::new(TypeInfo {
+        ty: typeinfo,
+        packed_size: vptr_size,
+        size: vptr_size,
+        align: vptr_size,
+    });
+    let word_size = vptr_size as i64;
+    // (相对于 vptr 指向位置的偏移, 名字, 类型).
+    let mut entries = vec![
+        (-2 * word_size, "offset_to_top".to_string(), word.clone()),
+        (-word_size, "typeinfo".to_string(), typeinfo),
+    ];
+    let mut used_names = HashSet::new();
+    let declarer = ty.type_name().to_string();
+    for vbase in vbases.iter().filter(|v| v.declarer == declarer) {
+        let Some(off) = vbase.vbase_offset_offset else {
+            continue;
+        };
+        if off >= -2 * word_size || off % word_size != 0 {
+            warn!(
+                "process_vtable_prefix: invalid vbase offset offset: tyidx={:?} off={}",
+                tyidx, off
+            );
+            continue;
+        }
+        let Some(typ) = struct_type_of(vbase.tyidx, inputs_hash) else {
+            continue;
+        };
+        let parser::TypeKind::Struct(base) = typ.kind() else {
+            unreachable!()
+        };
+        let base_name = base.name().map_or("", ident_part).trim_start_matches('_');
+        let mut name = format!("{}_offset", base_name);
+        if used_names.contains(&name) {
+            name = format!("{}{}_offset", base_name, used_names.len());
         }
+        used_names.insert(name.clone());
+        entries.push((off, name, word.clone()));
     }
-    let tylayout = tylayout;
-    let tysize = bit2byte(ty_bit_size);
-    let ty_max_size = match ty_max_size {
-        Some(v) => {
-            if v > tysize {
-                tysize
-            } else {
-                v
-            }
+    entries.sort_by_key(|v| v.0);
+    let start = entries[0].0;
+    let mut members = Vec::new();
+    let mut next_off = 0;
+    for (off, name, tyinfo) in entries {
+        let off = (off - start) as u64;
+        if off > next_off {
+            members.push(Member::new_padding(next_off, off - next_off));
         }
-        None => tysize,
-    };
+        next_off = off + tyinfo.packed_size;
+        members.push(Member::new_field(off, name, tyinfo));
+    }
+    let name = format!("{}_VTablePrefix", ty.name().map_or("", ident_part));
+    process_synthetic_record(
+        processed,
+        printer,
+        tyidx,
+        name.trim_start_matches('_'),
+        &members,
+        RecordKind::Struct,
+    )?;
+    return Ok(());
+}
 
-    let mut tymems = Vec::<Member>::with_capacity(tylayout.len());
-    let mut next_idx = find_next_idx(&tylayout, 0);
-    debug_assert_eq!(next_idx.unwrap_or(0), 0);
-    while let Some(item_idx) = next_idx {
-        debug_assert_eq!(tylayout[item_idx].bit_offset % BITS_PER_BYTE, 0);
-        debug_assert!(item_idx != 0 || tylayout[item_idx].bit_offset == 0); // layout() 函数会确保从 offset: 0 开始.
-        next_idx = find_next_idx(&tylayout, item_idx + 1);
+// 虚基类在对象中的位置取决于 most derived 类型, 运行时需要通过 vtable 中的 vbase offset 得到.
+#[derive(Clone)]
+struct VirtualBase {
+    tyidx: TypeIndex,
+    // 声明该虚基类的类型, 比如 `struct L : virtual B` 中的 L.
+    declarer: String,
+    // 详见 parser::Inherit::vbase_offset_offset.
+    vbase_offset_offset: Option<i64>,
+}
 
-        let member_off = tylayout[item_idx].bit_offset / BITS_PER_BYTE;
-        // member_size item_idx 占用的空间, 字节为单位.
-        // 这里以 member_size 为准, 而不是 tylayout[item_idx].bit_size. 详见 S1218, A1218 例子.
-        let member_size = next_idx
-            .map(|v| tylayout[v].bit_offset / BITS_PER_BYTE)
-            .unwrap_or(ty_max_size)
-            - member_off;
-        // debug_assert!(tylayout[item_idx].bit_size.get().map(|v| v <= member_size).unwrap_or(true));
-        if member_size <= 0 && next_idx.is_some() {
-            continue;
-        }
-        // member_size == 0 && next_idx.is_none() 意味着 member 是最后一个元素, 如下示例所示:
-        //   struct S {int i; char ch[0];}
-        // 此时 ch member_size = 0.
+impl VirtualBase {
+    // 形如 `  // virtual base of ::L, ...`, 输出到 C 头文件中虚基类成员之前.
+    fn c_comment(&self) -> String {
+        let comment = format!(
+            "  // virtual base of {}, only at this offset in a complete object",
+            self.declarer
+        );
+        let Some(off) = self.vbase_offset_offset else {
+            return comment;
+        };
+        return format!(
+            "{}, from any {}* p it is at (char*)p + *(ptrdiff_t*)(*(char**)p {} {})",
+            comment,
+            self.declarer,
+            if off < 0 { '-' } else { '+' },
+            off.unsigned_abs()
+        );
+    }
+}
 
-        if is_bitfield(&tylayout[item_idx]) {
-            // 更合适的做法, 是将 tymem 拆分字段以及 padding 部分,
-            //   struct S { long i: 2; };
-            //   struct A: public S {char ch;};
-            // 不过实测这里 A.ch 并不会塞到 S padding 中, 所以拆不拆都行.
-            tymems.push(Member::new_placeholder(
-                member_off,
-                member_size,
-                "_bitfield",
-            ));
-            continue;
-        }
+fn struct_type_of<'a, 'input>(
+    tyidx: TypeIndex,
+    inputs_hash: &'a [parser::FileHash<'input>],
+) -> Option<Cow<'a, parser::Type<'input>>> {
+    let typ = parser::Type::from_offset(&inputs_hash[tyidx.input_id], tyidx.typoff)?;
+    if !matches!(typ.kind(), parser::TypeKind::Struct(_)) {
+        return None;
+    }
+    return Some(typ);
+}
 
-        let (member_tyoff, member_name) = match tylayout[item_idx].item {
-            parser::LayoutItem::Padding => {
-                tymems.push(Member::new_padding(member_off, member_size));
-                continue;
-            }
-            parser::LayoutItem::Member(mem) => (mem.type_offset(), member_name(mem.name())),
-            parser::LayoutItem::Inherit(mem) => (
-                mem.type_offset(),
-                Cow::Owned(format!("__parent{}", uniq_id())),
-            ),
-            parser::LayoutItem::VariantPart(_) => {
-                tymems.push(Member::new_placeholder(
-                    member_off,
-                    member_size,
-                    "__variant_part",
-                ));
+// 按照 Itanium C++ ABI 中的 inheritance graph order, 即从左到右深度优先的先序遍历, 收集 ty 所有的虚基类,
+// 包括间接继承的, 比如 `struct D : L, R` 中 L, R 的虚基类. 每个虚基类只会出现一次.
+fn collect_virtual_bases(
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+    vbases: &mut Vec<VirtualBase>,
+) {
+    for inherit in ty.inherits() {
+        let base_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: inherit.type_offset(),
+        };
+        let Some(base_tyidx) = resolve_record(base_tyidx, inputs_hash, type_db) else {
+            continue;
+        };
+        if inherit.is_virtual() {
+            if vbases.iter().any(|v| v.tyidx == base_tyidx) {
                 continue;
             }
+            vbases.push(VirtualBase {
+                tyidx: base_tyidx,
+                declarer: ty.type_name().to_string(),
+                vbase_offset_offset: inherit.vbase_offset_offset(),
+            });
+        }
+        let Some(typ) = struct_type_of(base_tyidx, inputs_hash) else {
+            continue;
         };
-        let mem_tyidx = TypeIndex {
-            input_id: tyidx.input_id,
-            typoff: member_tyoff,
+        let parser::TypeKind::Struct(base) = typ.kind() else {
+            unreachable!()
         };
-        let mem_tyinfo = get_type_info(
-            processed,
-            printer,
-            mem_tyidx,
-            Some(member_size),
-            inputs_hash,
-            type_db,
-        )?;
-        let Some(mem_tyinfo) = mem_tyinfo else {
-            tymems.push(Member::new_placeholder(member_off, member_size, "__unknown_type"));
-            continue;
+        collect_virtual_bases(base_tyidx, base, inputs_hash, type_db, vbases);
+    }
+}
+
+fn has_virtual_bases(
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> bool {
+    let mut vbases = Vec::new();
+    collect_virtual_bases(tyidx, ty, inputs_hash, type_db, &mut vbases);
+    return !vbases.is_empty();
+}
+
+// 含有 vptr 的类型, 即 Itanium C++ ABI 中的 dynamic class. 其不是 POD, 所以其尾部的 padding 可以被复用.
+fn is_dynamic_class(
+    tyidx: TypeIndex,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> bool {
+    let Some(tyidx) = resolve_record(tyidx, inputs_hash, type_db) else {
+        return false;
+    };
+    let Some(typ) = struct_type_of(tyidx, inputs_hash) else {
+        return false;
+    };
+    let parser::TypeKind::Struct(ty) = typ.kind() else {
+        unreachable!()
+    };
+    if ty.members().iter().any(is_vptr) {
+        return true;
+    }
+    return ty.inherits().iter().any(|v| {
+        let base_tyidx = TypeIndex {
+            input_id: tyidx.input_id,
+            typoff: v.type_offset(),
         };
-        debug_assert!(mem_tyinfo.packed_size <= member_size);
+        return v.is_virtual() || is_dynamic_class(base_tyidx, inputs_hash, type_db);
+    });
+}
 
-        tymems.push(Member {
-            off: member_off,
-            len: mem_tyinfo.packed_size,
-            def: format!("{} {}", &mem_tyinfo.name, &member_name),
-            field_name: member_name.into_owned(),
-            is_padding: false,
-        });
-        if mem_tyinfo.packed_size < member_size {
-            tymems.push(Member::new_padding(
-                member_off + mem_tyinfo.packed_size,
-                member_size - mem_tyinfo.packed_size,
-            ));
+// 基类子对象的类型. 含有虚基类的类型作为基类时, 其虚基类并不在基类子对象中, 此时只能使用其非虚部分.
+fn base_type_info(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    if let Some(real_tyidx) = resolve_record(tyidx, inputs_hash, type_db) {
+        if let Some(typ) = struct_type_of(real_tyidx, inputs_hash) {
+            let parser::TypeKind::Struct(ty) = typ.kind() else {
+                unreachable!()
+            };
+            if has_virtual_bases(real_tyidx, ty, inputs_hash, type_db) {
+                return process_non_virtual(
+                    processed,
+                    printer,
+                    real_tyidx,
+                    ty,
+                    ty_max_size,
+                    inputs_hash,
+                    type_db,
+                );
+            }
         }
     }
-    while let Some(member) = tymems.last() {
-        if member.is_padding {
-            tymems.pop();
+    return get_type_info(processed, printer, tyidx, ty_max_size, inputs_hash, type_db);
+}
+
+// `struct L : virtual B` 作为基类时, 基类子对象中只有 L 的非虚部分, 输出为 `L_NonVirtual`. B 则位于完整对象的尾部,
+// 与其他基类共享, 详见 process_virtual_bases.
+fn process_non_virtual(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    ty_max_size: Option<u64>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    if let Some(tyinfo) = processed.non_virtual.get(&tyidx) {
+        return Ok(tyinfo.clone());
+    }
+    processed.non_virtual.insert(tyidx, None);
+
+    let vptr = process_vtable(processed, printer, tyidx, ty, inputs_hash, type_db)?;
+    let Some(tymems) = base_layout(
+        processed,
+        printer,
+        tyidx,
+        ty,
+        ty_max_size,
+        vptr.as_ref().map(|v| (tyidx, v)),
+        inputs_hash,
+        type_db,
+    )?
+    else {
+        return Ok(None);
+    };
+    let name = format!("{}_NonVirtual", ty.name().map_or("", ident_part));
+    let tyinfo = process_synthetic_record(
+        processed,
+        printer,
+        tyidx,
+        name.trim_start_matches('_'),
+        &tymems,
+        RecordKind::Struct,
+    )?;
+    processed.non_virtual.insert(tyidx, tyinfo.clone());
+    return Ok(tyinfo);
+}
+
+// 基类子对象中的成员, 即去掉结尾 padding 之后的 layout. 虚基类不在 layout 中, 其所占的空间会作为结尾的 padding 出现.
+fn base_layout(
+    processed: &mut ProcessState,
+    printer: &mut Printer,
+    tyidx: TypeIndex,
+    ty: &parser::StructType,
+    ty_max_size: Option<u64>,
+    vptr: Option<(TypeIndex, &Rc<TypeInfo>)>,
+    inputs_hash: &[parser::FileHash],
+    type_db: &HashMap<parser::TypeName, TypeIndex>,
+) -> io::Result<Option<Vec<Member>>> {
+    let Some(mut ty_bit_size) = ty.bit_size() else {
+        warn!("base_layout: unknown type size: tyidx={:?} tyname={}", tyidx, ty.type_name());
+        return Ok(None);
+    };
+    let mut tylayout = ty.layout(&inputs_hash[tyidx.input_id]);
+    while let Some(lastlayout) = tylayout.last() {
+        if let parser::LayoutItem::Padding = lastlayout.item {
+            ty_bit_size -= lastlayout.bit_size.get().unwrap();
+            tylayout.pop();
         } else {
             break;
         }
     }
-
-    let tydef = format!("struct {}", printer.alloc_ident(&tyname));
-    return process_members(
+    let tysize = bit2byte(ty_bit_size);
+    let ty_max_size = ty_max_size.map_or(tysize, |v| std::cmp::min(v, tysize));
+    let tymems = process_layout(
         processed,
         printer,
         tyidx,
-        &ty.type_name(),
-        &tymems,
-        &tydef,
-        Some(ty_dwarf_size),
-    );
+        ty,
+        &tylayout,
+        ty_max_size,
+        vptr,
+        inputs_hash,
+        type_db,
+    )?;
+    return Ok(Some(tymems));
 }
 
-fn process_enum_type(
+// 位于 offset 的基类子对象的类型. offset 为 0 的 dynamic base 即 primary base, 其与子类共用 vptr,
+// 详见 process_primary_base.
+fn parent_type_info(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
-    ty: &parser::EnumerationType,
+    offset: u64,
     ty_max_size: Option<u64>,
+    vptr: Option<(TypeIndex, &Rc<TypeInfo>)>,
     inputs_hash: &[parser::FileHash],
     type_db: &HashMap<parser::TypeName, TypeIndex>,
-) -> io::Result<()> {
-    let tyname = ty.type_name();
-    if ty.is_declaration() {
-        let Some(&real_tyidx) = type_db.get(&tyname) else {
-            warn!("process_enum_type: unknown declaration. typidx={:?} typname={}", tyidx, &tyname);
-            return Ok(());
-        };
-        return handle_sym_link(
-            processed,
-            printer,
-            tyidx,
-            real_tyidx,
-            ty_max_size,
-            inputs_hash,
-            type_db,
-        );
-    }
-    let Some(ty_size) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
-        warn!("process_enum_type: unknown byte size. typidx={:?} typname={}", tyidx, ty.type_name());
-        return Ok(());
-    };
-    if let Some(ty_max_size) = ty_max_size {
-        if ty_size > ty_max_size {
-            warn!(
-                "process_enum_type: invalid byte size. typidx={:?} typname={} expect={} actual={}",
-                tyidx,
-                ty.type_name(),
-                ty_max_size,
-                ty_size
-            );
-            return Ok(());
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    match vptr {
+        Some(vptr) if offset == 0 && is_dynamic_class(tyidx, inputs_hash, type_db) => {
+            return process_primary_base(processed, printer, tyidx, vptr, ty_max_size, inputs_hash, type_db);
         }
+        _ => return base_type_info(processed, printer, tyidx, ty_max_size, inputs_hash, type_db),
     }
-
-    // EnumerationType::ty may be none, 所以我们自己选择类型吧.
-    let ty_repr = if ty_size == 8 {
-        "__s64"
-    } else if ty_size == 4 {
-        "__s32"
-    } else if ty_size == 2 {
-        "__s16"
-    } else if ty_size == 1 {
-        "__s8"
-    } else {
-        warn!(
-            "process_enum_type: invalid byte size. typidx={:?} typname={} expect=8/4/2/1 actual={}",
-            tyidx,
-            ty.type_name(),
-            ty_size
-        );
-        return Ok(());
-    };
-
-    let mut asserts = Vec::<EqAssert>::new();
-    let mut struct_def = Vec::<String>::new();
-    asserts.push(EqAssert {
-        expr: format!("sizeof({})", ty_repr),
-        val: ty_size,
-    });
-    let tydef = printer.alloc_ident(&tyname);
-    struct_def.push(format!("// --- enum {} begin ---", &tydef));
-    for enum_item in &ty.enumerators(&inputs_hash[tyidx.input_id]) {
-        struct_def.push(format!(
-            "// {}={}",
-            enum_item.name().unwrap_or("<unknown enum item>"),
-            enum_item.value().unwrap_or(-20181218),
-        ));
-    }
-    struct_def.push(format!("// --- enum {} end ---", &tydef));
-    struct_def.push(format!("typedef {} {};", ty_repr, &tydef));
-
-    processed.insert(
-        tyidx,
-        Some(Rc::new(TypeInfo {
-            name: tydef,
-            packed_size: ty_size,
-            size: ty_size,
-        })),
-    );
-    printer.add_type(&struct_def)?;
-    printer.add_eq_asserts(&asserts)?;
-    return Ok(());
 }
 
-fn process_array_type(
+// 按照 Itanium C++ ABI, `struct C : B` 中 B 为 C 的 primary base 时二者共用同一个 vptr, 其指向 C 的 vtable.
+// 所以 C 中的 B 子对象输出为 `C_B`, 其 layout 与 B 一致, 只是 vptr 为 `C_VTable*`, 这样不需要转换就可以访问 C
+// 新增的虚函数. B 的 primary base A 也是如此, 输出为 `C_A`. B 含有虚基类时与 process_non_virtual 一样只包含其非虚部分.
+fn process_primary_base(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
-    ty: &parser::ArrayType,
+    vptr: (TypeIndex, &Rc<TypeInfo>),
     ty_max_size: Option<u64>,
     inputs_hash: &[parser::FileHash],
     type_db: &HashMap<parser::TypeName, TypeIndex>,
-) -> io::Result<()> {
-    let mem_tyidx = TypeIndex {
-        input_id: tyidx.input_id,
-        typoff: ty.ty,
+) -> io::Result<Option<Rc<TypeInfo>>> {
+    let Some(real_tyidx) = resolve_record(tyidx, inputs_hash, type_db) else {
+        return base_type_info(processed, printer, tyidx, ty_max_size, inputs_hash, type_db);
     };
-    let mem_tyinfo = get_type_info(processed, printer, mem_tyidx, None, inputs_hash, type_db)?;
-    let Some(mem_tyinfo) = mem_tyinfo else {
-        warn!("process_array_type: unknown element type: tyidx={:?} ty={:?}", tyidx, ty);
-        return Ok(());
+    let Some(typ) = struct_type_of(real_tyidx, inputs_hash) else {
+        return base_type_info(processed, printer, tyidx, ty_max_size, inputs_hash, type_db);
     };
-
-    let mem_tyident = mem_tyinfo.ident();
-    let mut mem_tyname = mem_tyinfo.name.clone();
-    if mem_tyinfo.size > mem_tyinfo.packed_size {
-        // 以 S1218 为例, 我们在生成 S1218 是 packed 的, 但如果 S1218 作为 array element, 则
-        // 需要保持其原有 padding.
-        mem_tyname = {
-            let name = format!("{}_Padded", mem_tyident);
-            let n = printer.alloc_ident(&parser::TypeName {
-                namespace: None,
-                name: Some(&name),
-            });
-            format!("struct {}", n)
-        };
-        let mut members = Vec::<Member>::new();
-        let data_name = "data";
-        members.push(Member {
-            off: 0,
-            len: mem_tyinfo.packed_size,
-            field_name: data_name.to_string(),
-            def: format!("{} {}", mem_tyinfo.name, data_name),
-            is_padding: false,
-        });
-        members.push(Member::new_padding(
-            mem_tyinfo.packed_size,
-            mem_tyinfo.size - mem_tyinfo.packed_size,
-        ));
-        process_members(
-            processed,
-            printer,
-            tyidx,
-            &parser::TypeName {
-                namespace: None,
-                name: Some("padding struct"),
-            },
-            &members,
-            &mem_tyname,
-            None,
-        )?;
+    let tyidx = real_tyidx;
+    let parser::TypeKind::Struct(ty) = typ.kind() else {
+        unreachable!()
+    };
+    let key = (tyidx, vptr.0);
+    if let Some(tyinfo) = processed.primary_bases.get(&key) {
+        return Ok(tyinfo.clone());
     }
+    processed.primary_bases.insert(key, None);
 
-    let ele_cnt = if ty_max_size == Some(0) {
-        0
-    } else {
-        let Some(array_byte_size) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
-            warn!("process_array_type: unknown array size: tyidx={:?} ty={:?}", tyidx, ty);
-            return Ok(());
-        };
-        if let Some(max_size) = ty_max_size {
-            if max_size < array_byte_size {
-                return Ok(());
-            }
-        }
-        let Some(ele_count) = ty.count(&inputs_hash[tyidx.input_id]) else {
-            warn!("process_array_type: unknown element count: tyidx={:?} ty={:?}", tyidx, ty);
-            return Ok(());
-        };
-        if array_byte_size % ele_count != 0 || mem_tyinfo.size != array_byte_size / ele_count {
-            warn!(
-                "process_array_type: invalid array def: tyidx={:?} ty={:?}",
-                tyidx, ty
-            );
-            return Ok(());
-        }
-        ele_count
+    let Some(tymems) = base_layout(
+        processed,
+        printer,
+        tyidx,
+        ty,
+        ty_max_size,
+        Some(vptr),
+        inputs_hash,
+        type_db,
+    )?
+    else {
+        return Ok(None);
     };
-    let array_name = {
-        let name = format!("{}_Array{}", mem_tyident, ele_cnt);
-        let tyname = parser::TypeName {
-            namespace: None,
-            name: Some(&name),
-        };
-        printer.alloc_ident(&tyname)
+    let owner = struct_type_of(vptr.0, inputs_hash);
+    let owner_name = match owner.as_ref().map(|v| v.kind()) {
+        Some(parser::TypeKind::Struct(v)) => v.name(),
+        _ => None,
     };
-    let array_size = mem_tyinfo.size * ele_cnt;
-
-    printer.add_type(&[format!(
-        "typedef {} {}[{}];",
-        mem_tyname, array_name, ele_cnt
-    )])?;
-    printer.add_eq_assert(&format!("sizeof({})", array_name), array_size)?;
-    processed.insert(
-        tyidx,
-        Some(Rc::new(TypeInfo {
-            name: array_name.clone(),
-            packed_size: array_size,
-            size: array_size,
-        })),
+    let name = format!(
+        "{}_{}",
+        owner_name.map_or("", ident_part),
+        ty.name().map_or("", ident_part)
     );
-    return Ok(());
+    let tyinfo = process_synthetic_record(
+        processed,
+        printer,
+        tyidx,
+        name.trim_start_matches('_'),
+        &tymems,
+        RecordKind::Struct,
+    )?;
+    processed.primary_bases.insert(key, tyinfo.clone());
+    return Ok(tyinfo);
 }
 
-fn process_modifier_type(
+// 按照 Itanium C++ ABI, 完整对象中虚基类依次放置在非虚部分之后, 每个虚基类都按照其对齐放置在当前 dsize 之后.
+// dsize 是不含尾部 padding 的大小, 只有 dynamic class 这种 non-POD 的基类的尾部 padding 可以被复用,
+// 成员与 POD 基类则按照其 sizeof 计算.
+fn process_virtual_bases(
     processed: &mut ProcessState,
     printer: &mut Printer,
     tyidx: TypeIndex,
-    ty: &parser::TypeModifier,
-    ty_max_size: Option<u64>,
+    tylayout: &[parser::Layout],
+    tymems: &mut Vec<Member>,
+    vbases: Vec<VirtualBase>,
+    ty_max_size: u64,
+    vptr: Option<(TypeIndex, &Rc<TypeInfo>)>,
     inputs_hash: &[parser::FileHash],
     type_db: &HashMap<parser::TypeName, TypeIndex>,
 ) -> io::Result<()> {
-    let real_tyidx = TypeIndex {
-        input_id: tyidx.input_id,
-        typoff: ty.ty,
-    };
-    match ty.kind() {
-        parser::TypeModifierKind::Const
-        | parser::TypeModifierKind::Packed
-        | parser::TypeModifierKind::Volatile
-        | parser::TypeModifierKind::Restrict
-        | parser::TypeModifierKind::Shared
-        | parser::TypeModifierKind::Atomic
-        | parser::TypeModifierKind::Other => {
-            return handle_sym_link(
-                processed,
-                printer,
-                tyidx,
-                real_tyidx,
-                ty_max_size,
-                inputs_hash,
-                type_db,
-            );
-        }
-        parser::TypeModifierKind::Pointer
-        | parser::TypeModifierKind::Reference
-        | parser::TypeModifierKind::RvalueReference => {
-            let Some(tysize) = ty.byte_size(&inputs_hash[tyidx.input_id]) else {
-                warn!("process_modifier_type: unknown byte size: tyidx={:?}", tyidx);
-                return Ok(());
-            };
-            if let Some(maxsize) = ty_max_size {
-                if maxsize < tysize {
-                    warn!(
-                        "process_modifier_type: invalid byte size: tyidx={:?} maxsize={} size={}",
-                        tyidx, maxsize, tysize
-                    );
-                    return Ok(());
+    let mut dsize = 0;
+    for item in tylayout {
+        let end = match item.item {
+            parser::LayoutItem::Padding => continue,
+            parser::LayoutItem::Inherit(inherit) => {
+                let base_tyidx = TypeIndex {
+                    input_id: tyidx.input_id,
+                    typoff: inherit.type_offset(),
+                };
+                let tyinfo = parent_type_info(
+                    processed,
+                    printer,
+                    base_tyidx,
+                    item.bit_offset / BITS_PER_BYTE,
+                    None,
+                    vptr,
+                    inputs_hash,
+                    type_db,
+                )?;
+                match tyinfo {
+                    Some(v) if is_dynamic_class(base_tyidx, inputs_hash, type_db) => {
+                        item.bit_offset / BITS_PER_BYTE + v.packed_size
+                    }
+                    _ => bit2byte(item.bit_offset + item.bit_size.get().unwrap_or(0)),
                 }
             }
+            _ => bit2byte(item.bit_offset + item.bit_size.get().unwrap_or(0)),
+        };
+        dsize = std::cmp::max(dsize, end);
+    }
 
-            let real_tyinfo =
-                get_type_info(processed, printer, real_tyidx, None, inputs_hash, type_db)?;
-            let real_tyname = if let Some(tyinfo) = &real_tyinfo {
-                &tyinfo.name
-            } else {
-                "void"
-            };
-            let tyname = format!("{}*", real_tyname);
-
-            printer.add_eq_assert(&format!("sizeof({})", tyname), tysize)?;
-            processed.insert(
-                tyidx,
-                Some(Rc::new(TypeInfo {
-                    name: tyname,
-                    packed_size: tysize,
-                    size: tysize,
-                })),
+    let mut next_off = tymems.last().map_or(0, |v| v.off + v.len);
+    for vbase in vbases {
+        let tyinfo = base_type_info(processed, printer, vbase.tyidx, None, inputs_hash, type_db)?;
+        let Some(tyinfo) = tyinfo else {
+            warn!("process_virtual_bases: unknown virtual base: tyidx={:?} vbase={:?}", tyidx, vbase.tyidx);
+            continue;
+        };
+        let off = std::cmp::max(dsize, next_off).next_multiple_of(tyinfo.align);
+        if off + tyinfo.packed_size > ty_max_size {
+            warn!(
+                "process_virtual_bases: virtual base out of range: tyidx={:?} vbase={:?} off={} maxsize={}",
+                tyidx, vbase.tyidx, off, ty_max_size
             );
+            break;
+        }
+        if off > next_off {
+            tymems.push(Member::new_padding(next_off, off - next_off));
         }
+        dsize = off + if is_dynamic_class(vbase.tyidx, inputs_hash, type_db) {
+            tyinfo.packed_size
+        } else {
+            tyinfo.size
+        };
+        let mut member = Member::new_parent(off, format!("__vbase{}", uniq_id()), tyinfo);
+        next_off = off + member.len;
+        member.vbase = Some(vbase);
+        tymems.push(member);
     }
     return Ok(());
 }
 
+// 与 x86_64/aarch64 一致, 基础类型按照其大小对齐, 最多 16 字节. complex 类型按照其实部对齐.
+fn base_type_align(name: &str, size: u64) -> u64 {
+    let size = if name.contains("complex") { size / 2 } else { size };
+    if size == 0 {
+        return 1;
+    }
+    // 大小不是 2 的幂时, 比如 `_BitInt(24)`, 取能整除 size 的最大 2 的幂.
+    return 1 << size.trailing_zeros().min(4);
+}
+
 // process_type 之后, ty 一定存在于 processed 之中,
 // processed[ty] 为 None, 意味着没有有效信息.
 // 由于 typedef 的存在, 可能会出现多个 tyidx 对应着相同的 TypeInfo, 所以使用 Rc.
@@ -1050,8 +3542,8 @@ fn process_type(
     inputs_hash: &[parser::FileHash],
     type_db: &HashMap<parser::TypeName, TypeIndex>,
 ) -> io::Result<()> {
-    debug_assert!(!processed.contains_key(&tyidx));
-    processed.insert(tyidx, None); // 先占个坑,
+    debug_assert!(!processed.types.contains_key(&tyidx));
+    processed.types.insert(tyidx, None); // 先占个坑,
 
     let typ = parser::Type::from_offset(&inputs_hash[tyidx.input_id], tyidx.typoff);
     let Some(typ) = typ else {
@@ -1061,9 +3553,12 @@ fn process_type(
     let typ = typ.as_ref();
 
     match typ.kind() {
+        parser::TypeKind::PointerToMember(ty) => {
+            return process_ptr_to_member(processed, printer, tyidx, ty, ty_max_size, inputs_hash);
+        }
+        // 函数类型本身没有大小, 函数指针详见 process_func_pointer.
         parser::TypeKind::Void
         | parser::TypeKind::Function(_)
-        | parser::TypeKind::PointerToMember(_)
         | parser::TypeKind::Subrange(_)
         | parser::TypeKind::Unspecified(_) => {}
         parser::TypeKind::Base(ty) => {
@@ -1071,15 +3566,22 @@ fn process_type(
                 warn!("process_type: base type has no name. tyidx={:?}", tyidx);
                 return Ok(());
             };
-            processed.insert(
+            let align = ty.alignment().unwrap_or_else(|| base_type_align(tyname, tysize));
+            let ty = TypeRef::Base {
+                name: tyname.to_string(),
+                size: tysize,
+                encoding: ty.encoding(),
+            };
+            printer.add_size_check(&ty, tysize)?;
+            processed.types.insert(
                 tyidx,
                 Some(Rc::new(TypeInfo {
-                    name: tyname.to_string(),
+                    ty,
                     packed_size: tysize,
                     size: tysize,
+                    align,
                 })),
             );
-            printer.add_eq_assert(&format!("sizeof({})", tyname), tysize)?;
         }
         parser::TypeKind::Def(ty) => {
             let real_typidx = TypeIndex {
@@ -1095,6 +3597,9 @@ fn process_type(
                 inputs_hash,
                 type_db,
             )?;
+            if processed.typedefs {
+                process_typedef(processed, printer, tyidx, typ)?;
+            }
         }
         parser::TypeKind::Struct(ty) => {
             return process_struct_type(
@@ -1155,27 +3660,82 @@ fn process_type(
     return Ok(());
 }
 
+fn type_kind_name(ty: &parser::Type) -> &'static str {
+    match ty.kind() {
+        parser::TypeKind::Base(_) => "base",
+        parser::TypeKind::Def(_) => "typedef",
+        parser::TypeKind::Struct(_) => "struct",
+        parser::TypeKind::Union(_) => "union",
+        parser::TypeKind::Enumeration(_) => "enum",
+        _ => "other",
+    }
+}
+
+fn print_dest(dest: &[TypeIndex], inputs_hash: &[parser::FileHash], input_paths: &[String]) {
+    let mut last_input = None;
+    for tyidx in dest {
+        if last_input != Some(tyidx.input_id) {
+            println!("input {}: {}", tyidx.input_id, input_paths[tyidx.input_id]);
+            last_input = Some(tyidx.input_id);
+        }
+        let hash = &inputs_hash[tyidx.input_id];
+        let Some(typ) = parser::Type::from_offset(hash, tyidx.typoff) else {
+            continue;
+        };
+        let Some(typname) = parser::TypeName::try_from(typ.as_ref()) else {
+            continue;
+        };
+        println!(
+            "  typoff={} {} {}",
+            tyidx.typoff.get().unwrap_or(usize::MAX),
+            type_kind_name(&typ),
+            typname
+        );
+    }
+    println!("{} types matched", dest.len());
+}
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
     let args = Args::parse();
-
-    let mut inputs = Vec::new();
-    let mut inputs_hash = Vec::new();
-    for input_path in &args.so_path {
-        info!("load so. path={}", input_path);
-        inputs.push(parser::File::parse(input_path.clone())?);
+    if args.lazy && args.command.is_some() {
+        anyhow::bail!("--lazy is not supported by subcommands");
     }
+
+    let mut started = Instant::now();
+    let mut so_paths = args.so_path.clone();
     for input_path in &args.so_file_path {
         for line in read_lines(input_path)? {
-            let line = line?;
-            info!("load so. path={}", &line);
-            inputs.push(parser::File::parse(line)?);
+            so_paths.push(line?);
         }
     }
-    info!("build input file hash");
+    // 各个 input 相互独立, 并行加载, input_id 仍然是其在 so_paths 中的位置.
+    let mut inputs = Vec::with_capacity(so_paths.len());
+    for input in parallel_map(&so_paths, |v| parse_input(&args, v.clone())) {
+        inputs.push(input?);
+    }
+    if args.lazy {
+        lazy::resolve_declarations(&mut inputs)?;
+    }
+    // 默认的日志级别下 warn! 是不可见的, 但缺少 split unit 时其中的类型都会丢失, 所以总是提示.
     for input in &inputs {
-        inputs_hash.push(parser::FileHash::new(input.file()));
+        let file = input.file();
+        let missing = file.missing_split_units();
+        if !missing.is_empty() {
+            eprintln!(
+                "warning: {}: {} split units are missing, neither {}.dwp nor their .dwo files are found, types defined in them are not available",
+                file.path(),
+                missing.len(),
+                file.path()
+            );
+        }
     }
+    let units: usize = inputs.iter().map(|v| v.file().units().len()).sum();
+    report_phase(&args, &format!("parse {} units", units), &mut started);
+    info!("build input file hash");
+    let inputs_hash = parallel_map(&inputs, |v| parser::FileHash::new(v.file()));
+    let types: usize = inputs_hash.iter().map(|v| v.types.len()).sum();
+    report_phase(&args, &format!("hash {} types", types), &mut started);
 
     info!("build type db");
     let mut dest = Vec::new();
@@ -1187,7 +3747,7 @@ fn main() -> anyhow::Result<()> {
             }
             if let Some(typname) = parser::TypeName::try_from(typ) {
                 let typidx = TypeIndex { input_id, typoff };
-                if args.is_dest(&typname) {
+                if args.is_dest(&typname, typ, hash) {
                     dest.push(typidx);
                 }
                 // type_db 存放着哪些可能会被跨 so file 引用的符号, 很显然 anon ty
@@ -1201,9 +3761,39 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    let mut printer = Printer::try_open(&args.out_path)?;
-    let mut processed = ProcessState::new();
+    let input_paths: Vec<String> = inputs.iter().map(|v| v.file().path().to_string()).collect();
+    match &args.command {
+        Some(Command::Offsetof { path }) => {
+            return offsetof::offsetof(&inputs_hash, &type_db, path);
+        }
+        Some(Command::Bpftrace { function }) => {
+            let Some(out_path) = &args.out_path else {
+                anyhow::bail!("bpftrace requires an output file path, use '-o'");
+            };
+            return bpftrace::bpftrace(&inputs_hash, &type_db, &input_paths, function, out_path);
+        }
+        None => {}
+    }
+
+    // hash.types 是 HashMap, 排个序使得输出稳定.
+    dest.sort_by_key(|v| (v.input_id, v.typoff));
+
+    report_phase(&args, &format!("select {} types", dest.len()), &mut started);
+
+    if args.dry_run {
+        print_dest(&dest, &inputs_hash, &input_paths);
+        return Ok(());
+    }
+    let Some(out_path) = &args.out_path else {
+        unreachable!("out_path is required without --dry-run");
+    };
+    let mut printer = Printer::try_open(out_path, &args.format, &input_paths, args.natural)?;
+    let mut processed = ProcessState::new(args.typedefs);
     for dest_ty in &dest {
+        // 可能已经作为其他 dest 的依赖被处理过了.
+        if processed.types.contains_key(dest_ty) {
+            continue;
+        }
         process_type(
             &mut processed,
             &mut printer,
@@ -1212,7 +3802,9 @@ fn main() -> anyhow::Result<()> {
             &inputs_hash,
             &type_db,
         )?;
+        process_deferred(&mut processed, &mut printer, &inputs_hash, &type_db)?;
     }
     printer.finish()?;
+    report_phase(&args, "output", &mut started);
     return Ok(());
 }
@@ -0,0 +1,447 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    c_array_def, c_enum_def, c_forward_decl, c_func_pointer_def, c_typedef_def, uniq_id, ArrayDef,
+    EnumDef, EqAssert, FuncPtrDef, MemberKind, Record, RecordKind, TypeIndex, TypeRef, TypedefDef,
+};
+
+// 与 c_record_def 不同, 这里尽量不使用 packed, 而是使用 natural layout 并显式插入 padding 保证 offset 不变,
+// 同时通过 `_Alignas`/`aligned(N)` 保证对齐与 dwarf 中一致, 这样 sizeof/_Alignof 也与原类型一致.
+// 仅当 natural layout 无法表达时才回退到 packed, 比如 S1218/A1218 这种复用了 tail padding 的情况.
+// 是否需要回退依赖于其他类型是否回退, 所以所有定义都在 finish 时才生成. vmlinux.h 与 `--natural` 均使用这里的结果.
+struct Field {
+    off: u64,
+    // clayout 计算出来的长度, 对于 struct/union 类型的成员是其 packed_size.
+    len: u64,
+    field_name: String,
+    // 仅 Field/Parent/Variant 有.
+    ty: Option<TypeRef>,
+    // 成员类型原本的对齐, 即 TypeInfo::align.
+    ty_align: u64,
+    // Member::alignas.
+    alignas: Option<u64>,
+    bitfield: bool,
+    // Member::print 的输出.
+    lines: Vec<String>,
+    // 仅 bitfield 有, 即 Member::print 输出的 bit 位置校验. 其他成员的校验在生成时重新计算.
+    bit_asserts: Vec<EqAssert>,
+}
+
+struct RecordDef {
+    kind: RecordKind,
+    ident: String,
+    tyname: String,
+    tyidx: TypeIndex,
+    size: u64,
+    packed_size: u64,
+    align: u64,
+    // 不包含 clayout 插入的 padding, 这些 padding 会在生成时重新计算.
+    fields: Vec<Field>,
+}
+
+enum Def {
+    Record(RecordDef),
+    // array, enum 这些定义与 CPrinter 一致.
+    Other {
+        ident: String,
+        lines: Vec<String>,
+    },
+    // `struct Node;` 这种前向声明.
+    Forward(String),
+    // typedef 的 sizeof 取决于 real 是否回退到 packed, 所以其校验也在 finish 时生成.
+    Typedef {
+        ident: String,
+        line: String,
+        real: TypeRef,
+        size: u64,
+        packed_size: u64,
+    },
+}
+
+// finish 生成的一个类型定义, lines 同 c_record_def 的返回值.
+// 前向声明没有 ident, 可以重复出现.
+pub(crate) struct NaturalDef {
+    pub(crate) ident: Option<String>,
+    pub(crate) lines: Vec<String>,
+    pub(crate) asserts: Vec<EqAssert>,
+}
+
+pub(crate) struct NaturalLayout {
+    defs: Vec<Def>,
+    // record ident --> defs 中的下标.
+    records: HashMap<String, usize>,
+    // array typedef 的 ident --> 数组元素类型, 数组的对齐与元素一致.
+    aliases: HashMap<String, TypeRef>,
+}
+
+impl NaturalLayout {
+    pub(crate) fn new() -> NaturalLayout {
+        NaturalLayout {
+            defs: Vec::new(),
+            records: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn add_record(&mut self, record: &Record) {
+        let tyname = match record.kind {
+            RecordKind::Struct => format!("struct {}", record.ident),
+            RecordKind::Union => format!("union {}", record.ident),
+        };
+        let mut fields = Vec::new();
+        for member in record.members.iter().filter(|m| !m.is_padding()) {
+            let mut lines = Vec::new();
+            let mut asserts = Vec::new();
+            member.print(&tyname, &mut lines, &mut asserts);
+            let bitfield = matches!(member.kind, MemberKind::Bitfield(_));
+            fields.push(Field {
+                off: member.off,
+                len: member.len,
+                field_name: member.field_name.clone(),
+                ty: member.tyinfo().map(|tyinfo| tyinfo.ty.clone()),
+                ty_align: member.tyinfo().map_or(1, |tyinfo| tyinfo.align),
+                alignas: member.alignas,
+                bitfield,
+                lines,
+                bit_asserts: if bitfield { asserts } else { Vec::new() },
+            });
+        }
+        self.records
+            .insert(record.ident.to_string(), self.defs.len());
+        self.defs.push(Def::Record(RecordDef {
+            kind: record.kind,
+            ident: record.ident.to_string(),
+            tyname: record.tyname.clone(),
+            tyidx: record.tyidx,
+            size: record.size,
+            packed_size: record.packed_size,
+            align: record.align,
+            fields,
+        }));
+    }
+
+    pub(crate) fn add_array(&mut self, array: &ArrayDef) {
+        self.aliases
+            .insert(array.ident.to_string(), array.elem.clone());
+        self.defs.push(Def::Other {
+            ident: array.ident.to_string(),
+            lines: vec![c_array_def(array)],
+        });
+    }
+
+    pub(crate) fn add_enum(&mut self, enum_def: &EnumDef) {
+        self.defs.push(Def::Other {
+            ident: enum_def.ident.to_string(),
+            lines: c_enum_def(enum_def),
+        });
+    }
+
+    pub(crate) fn add_typedef(&mut self, typedef: &TypedefDef) {
+        self.defs.push(Def::Typedef {
+            ident: typedef.ident.to_string(),
+            line: c_typedef_def(typedef),
+            real: typedef.real.clone(),
+            size: typedef.size,
+            packed_size: typedef.packed_size,
+        });
+    }
+
+    pub(crate) fn add_func_pointer(&mut self, func: &FuncPtrDef) {
+        self.defs.push(Def::Other {
+            ident: func.ident.to_string(),
+            lines: vec![c_func_pointer_def(func)],
+        });
+    }
+
+    pub(crate) fn add_forward_decl(&mut self, ty: &TypeRef) {
+        self.defs.push(Def::Forward(c_forward_decl(ty)));
+    }
+
+    fn record(&self, ident: &str) -> Option<&RecordDef> {
+        let idx = self.records.get(ident)?;
+        let Def::Record(record) = &self.defs[*idx] else {
+            unreachable!()
+        };
+        return Some(record);
+    }
+
+    fn is_packed(&self, packed: &HashSet<String>, ident: &str) -> bool {
+        // 找不到定义的 record 不会出现在成员中, 这里保守地认为其是 packed.
+        return self.record(ident).is_none() || packed.contains(ident);
+    }
+
+    // 类型在输出中的对齐, align 为其原本的对齐. 非 packed 的 record 总会通过 aligned(N) 保持原本的对齐,
+    // 所以只有 packed record 以及以其为元素的数组会小于原本的对齐.
+    fn type_align(&self, packed: &HashSet<String>, ty: &TypeRef, align: u64) -> u64 {
+        match ty {
+            TypeRef::Struct(ident) | TypeRef::Union(ident) => {
+                if self.is_packed(packed, ident) {
+                    return 1;
+                }
+                return align;
+            }
+            TypeRef::Alias(ident) => match self.aliases.get(ident) {
+                Some(elem) => self.type_align(packed, elem, align),
+                None => align,
+            },
+            TypeRef::Typedef { real, .. } | TypeRef::Qualified { real, .. } => {
+                self.type_align(packed, &real.ty, align)
+            }
+            TypeRef::Base { .. } | TypeRef::Pointer(_) => align,
+        }
+    }
+
+    // 不考虑 alignas 时 field 在输出中的对齐.
+    fn field_ty_align(&self, packed: &HashSet<String>, field: &Field) -> u64 {
+        // bitfield 会被包在一个 packed 的匿名 struct 中, placeholder 则是 `__u8` 数组.
+        match &field.ty {
+            Some(ty) if !field.bitfield => self.type_align(packed, ty, field.ty_align),
+            _ => 1,
+        }
+    }
+
+    fn field_align(&self, packed: &HashSet<String>, field: &Field) -> u64 {
+        let align = self.field_ty_align(packed, field);
+        return std::cmp::max(align, field.alignas.unwrap_or(1));
+    }
+
+    // field 在输出中实际占用的长度, 即 sizeof.
+    fn field_len(&self, packed: &HashSet<String>, field: &Field) -> u64 {
+        match field.ty.as_ref().map(TypeRef::strip) {
+            Some(TypeRef::Struct(ident) | TypeRef::Union(ident))
+                if !self.is_packed(packed, ident) =>
+            {
+                self.record(ident).unwrap().size
+            }
+            _ => field.len,
+        }
+    }
+
+    // record 输出之后的 sizeof.
+    fn record_len(&self, packed: &HashSet<String>, record: &RecordDef) -> u64 {
+        if packed.contains(&record.ident) {
+            return record.packed_size;
+        }
+        return record.size;
+    }
+
+    fn natural_fits(&self, packed: &HashSet<String>, record: &RecordDef) -> bool {
+        if !record.size.is_multiple_of(record.align) {
+            return false;
+        }
+        let mut next_off = 0;
+        for field in &record.fields {
+            let field_align = self.field_align(packed, field);
+            // aligned(N) 只能增大对齐, 无法减小.
+            if field_align > record.align || !field.off.is_multiple_of(field_align) {
+                return false;
+            }
+            let field_off = match record.kind {
+                RecordKind::Struct => field.off,
+                RecordKind::Union => 0,
+            };
+            if field.off < next_off || field_off != field.off {
+                return false;
+            }
+            let end = field.off + self.field_len(packed, field);
+            if end > record.size {
+                return false;
+            }
+            if record.kind == RecordKind::Struct {
+                next_off = end;
+            }
+        }
+        return true;
+    }
+
+    // 计算出需要回退到 packed 的 record. 回退只会使得其他 record 更容易放下, 所以迭代到不再变化即可.
+    fn packed_records(&self) -> HashSet<String> {
+        let mut packed = HashSet::new();
+        loop {
+            let mut changed = false;
+            // 成员所在的位置放不下其 natural size 时, 成员类型也需要 packed, 即 tail padding 被复用的情况.
+            for def in &self.defs {
+                let Def::Record(record) = def else {
+                    continue;
+                };
+                let record_len = self.record_len(&packed, record);
+                for (idx, field) in record.fields.iter().enumerate() {
+                    let Some(TypeRef::Struct(ident) | TypeRef::Union(ident)) =
+                        field.ty.as_ref().map(TypeRef::strip)
+                    else {
+                        continue;
+                    };
+                    if self.is_packed(&packed, ident) {
+                        continue;
+                    }
+                    let slot_end = match record.kind {
+                        RecordKind::Struct => {
+                            record.fields.get(idx + 1).map_or(record_len, |f| f.off)
+                        }
+                        RecordKind::Union => record_len,
+                    };
+                    if field.off + self.record(ident).unwrap().size > slot_end {
+                        packed.insert(ident.clone());
+                        changed = true;
+                    }
+                }
+            }
+            for def in &self.defs {
+                let Def::Record(record) = def else {
+                    continue;
+                };
+                if packed.contains(&record.ident) {
+                    continue;
+                }
+                if !self.natural_fits(&packed, record) {
+                    packed.insert(record.ident.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                return packed;
+            }
+        }
+    }
+
+    fn record_def(&self, packed: &HashSet<String>, record: &RecordDef) -> NaturalDef {
+        let is_packed = packed.contains(&record.ident);
+        let tydef = match record.kind {
+            RecordKind::Struct => format!("struct {}", record.ident),
+            RecordKind::Union => format!("union {}", record.ident),
+        };
+        let mut lines = Vec::new();
+        let mut asserts = Vec::new();
+        lines.push(format!(
+            "// tyname={} tyidx={:?}",
+            record.tyname, record.tyidx
+        ));
+        lines.push(format!("{} {{", tydef));
+        let mut next_off = 0;
+        // 不使用 aligned(N) 时 record 的对齐.
+        let mut natural_align = 1;
+        for field in &record.fields {
+            if record.kind == RecordKind::Struct && field.off > next_off {
+                lines.push(format!(
+                    "  __u8 __padding{}[{}];",
+                    uniq_id(),
+                    field.off - next_off
+                ));
+            }
+            let field_len = self.field_len(packed, field);
+            if field.bitfield {
+                if is_packed {
+                    lines.extend(field.lines.iter().cloned());
+                } else {
+                    // natural layout 下 bitfield 会按照声明类型对齐, 所以放在一个 packed 的匿名 struct 中.
+                    lines.push("  struct {".to_string());
+                    lines.extend(field.lines.iter().map(|line| format!("  {}", line)));
+                    lines.push("  } __attribute__((__packed__));".to_string());
+                }
+                asserts.extend(field.bit_asserts.iter().cloned());
+            } else {
+                let ty_align = self.field_ty_align(packed, field);
+                match field.alignas {
+                    Some(alignas) if !is_packed && alignas > ty_align => {
+                        // Member::print 最后一行总是 `  {c_def};`.
+                        let (last, init) = field.lines.split_last().unwrap();
+                        lines.extend(init.iter().cloned());
+                        lines.push(format!("  _Alignas({}) {}", alignas, last.trim_start()));
+                    }
+                    _ => lines.extend(field.lines.iter().cloned()),
+                }
+                asserts.push(EqAssert {
+                    expr: format!("(long int)(&((({}*)0)->{}))", tydef, field.field_name),
+                    val: field.off,
+                });
+                asserts.push(EqAssert {
+                    expr: format!("sizeof((({}*)0)->{})", tydef, field.field_name),
+                    val: field_len,
+                });
+            }
+            natural_align = std::cmp::max(natural_align, self.field_align(packed, field));
+            next_off = std::cmp::max(next_off, field.off + field_len);
+        }
+        let record_len = self.record_len(packed, record);
+        if record_len > next_off {
+            match record.kind {
+                RecordKind::Struct => {
+                    lines.push(format!(
+                        "  __u8 __padding{}[{}];",
+                        uniq_id(),
+                        record_len - next_off
+                    ));
+                }
+                RecordKind::Union => {
+                    lines.push(format!("  __u8 __padding{}[{}];", uniq_id(), record_len));
+                }
+            }
+        }
+        if is_packed {
+            lines.push("} __attribute__((__packed__));".to_string());
+        } else if record.align > natural_align {
+            lines.push(format!("}} __attribute__((aligned({})));", record.align));
+        } else {
+            lines.push("};".to_string());
+        }
+        asserts.push(EqAssert {
+            expr: format!("sizeof({})", tydef),
+            val: record_len,
+        });
+        if !is_packed {
+            asserts.push(EqAssert {
+                expr: format!("_Alignof({})", tydef),
+                val: record.align,
+            });
+        }
+        return NaturalDef {
+            ident: Some(record.ident.clone()),
+            lines,
+            asserts,
+        };
+    }
+
+    // 按照加入的顺序返回所有定义, array/enum 的 asserts 总是为空.
+    pub(crate) fn finish(&self) -> Vec<NaturalDef> {
+        let packed = self.packed_records();
+        let mut outputs = Vec::new();
+        for def in &self.defs {
+            match def {
+                Def::Record(record) => outputs.push(self.record_def(&packed, record)),
+                Def::Other { ident, lines } => outputs.push(NaturalDef {
+                    ident: Some(ident.clone()),
+                    lines: lines.clone(),
+                    asserts: Vec::new(),
+                }),
+                Def::Typedef {
+                    ident,
+                    line,
+                    real,
+                    size,
+                    packed_size,
+                } => {
+                    let len = match real.strip() {
+                        TypeRef::Struct(real) | TypeRef::Union(real) if packed.contains(real) => {
+                            *packed_size
+                        }
+                        _ => *size,
+                    };
+                    outputs.push(NaturalDef {
+                        ident: Some(ident.clone()),
+                        lines: vec![line.clone()],
+                        asserts: vec![EqAssert {
+                            expr: format!("sizeof({})", ident),
+                            val: len,
+                        }],
+                    });
+                }
+                Def::Forward(line) => outputs.push(NaturalDef {
+                    ident: None,
+                    lines: vec![line.clone()],
+                    asserts: Vec::new(),
+                }),
+            }
+        }
+        return outputs;
+    }
+}
@@ -0,0 +1,418 @@
+use anyhow::{bail, ensure};
+use log::warn;
+use parser::{FileHash, LayoutItem, Member, Type, TypeKind, TypeModifierKind, TypeName};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::select::{parse_int, parse_type_selector, resolve_definition, split_top_level};
+use crate::{is_declaration, is_ident_char, TypeIndex, BITS_PER_BYTE};
+
+#[derive(Debug)]
+enum Step {
+    // `.name`
+    Member(String),
+    // `->name`
+    Arrow(String),
+    // `[idx]`
+    Index(u64),
+}
+
+// 'ns::S::a.b[3]->c' -> ('ns::S', [Member(a), Member(b), Index(3), Arrow(c)]).
+fn parse_path(input: &str) -> anyhow::Result<(String, Vec<Step>)> {
+    let input = input.trim();
+    let mut depth = 0i32;
+    let mut path_idx = input.len();
+    for (idx, ch) in input.char_indices() {
+        match ch {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            '.' | '[' if depth == 0 => {
+                path_idx = idx;
+                break;
+            }
+            '-' if depth == 0 && input[idx..].starts_with("->") => {
+                path_idx = idx;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let mut parts = split_top_level(&input[..path_idx], "::");
+    ensure!(parts.len() >= 2, "expect 'Type::member'. input={}", input);
+    let first = parts.pop().unwrap_or_default().trim();
+    ensure!(
+        !first.is_empty() && first.chars().all(is_ident_char),
+        "invalid member name. input={}",
+        input
+    );
+    let tyname = parts.join("::");
+
+    let mut steps = vec![Step::Member(first.to_string())];
+    let mut rest = &input[path_idx..];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok((tyname, steps));
+        }
+        let is_arrow = rest.starts_with("->");
+        if is_arrow || rest.starts_with('.') {
+            rest = rest[if is_arrow { 2 } else { 1 }..].trim_start();
+            let end = rest.find(|v| !is_ident_char(v)).unwrap_or(rest.len());
+            ensure!(end > 0, "invalid member name. input={}", input);
+            let name = rest[..end].to_string();
+            steps.push(if is_arrow {
+                Step::Arrow(name)
+            } else {
+                Step::Member(name)
+            });
+            rest = &rest[end..];
+        } else if let Some(v) = rest.strip_prefix('[') {
+            let Some(end) = v.find(']') else {
+                bail!("missing ']'. input={}", input);
+            };
+            let Some(idx) = parse_int(v[..end].trim()).and_then(|v| u64::try_from(v).ok()) else {
+                bail!("invalid array index. input={}", input);
+            };
+            steps.push(Step::Index(idx));
+            rest = &v[end + 1..];
+        } else {
+            bail!("unexpected '{}'. input={}", rest, input);
+        }
+    }
+}
+
+// 一种 C 风格的类型名, 仅用于展示.
+pub(crate) fn type_display(hash: &FileHash, ty: &Type) -> String {
+    match ty.kind() {
+        TypeKind::Void => "void".to_string(),
+        TypeKind::Base(v) => v.name().unwrap_or("<anon>").to_string(),
+        TypeKind::Def(_) | TypeKind::Enumeration(_) | TypeKind::Struct(_) | TypeKind::Union(_) => {
+            let keyword = match ty.kind() {
+                TypeKind::Enumeration(_) => "enum ",
+                TypeKind::Struct(_) => "struct ",
+                TypeKind::Union(_) => "union ",
+                _ => "",
+            };
+            let tyname = TypeName::try_from(ty)
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            format!("{}{}", keyword, tyname)
+        }
+        TypeKind::Array(v) => {
+            let elem = v
+                .element_type(hash)
+                .map(|v| type_display(hash, &v))
+                .unwrap_or_default();
+            match v.count(hash) {
+                Some(count) => format!("{}[{}]", elem, count),
+                None => format!("{}[]", elem),
+            }
+        }
+        TypeKind::Modifier(v) => {
+            let inner = v
+                .ty(hash)
+                .map(|v| type_display(hash, &v))
+                .unwrap_or_default();
+            match v.kind() {
+                TypeModifierKind::Pointer => format!("{}*", inner),
+                TypeModifierKind::Reference => format!("{}&", inner),
+                TypeModifierKind::RvalueReference => format!("{}&&", inner),
+                TypeModifierKind::Const => format!("const {}", inner),
+                TypeModifierKind::Volatile => format!("volatile {}", inner),
+                _ => inner,
+            }
+        }
+        TypeKind::Function(_) => "<function>".to_string(),
+        _ => "<unknown>".to_string(),
+    }
+}
+
+struct Found<'input> {
+    input_id: usize,
+    // 相对于查找起点的偏移.
+    bit_off: u64,
+    member: Member<'input>,
+}
+
+struct Walker<'a, 'input> {
+    inputs_hash: &'a [FileHash<'input>],
+    type_db: &'a HashMap<TypeName<'a, 'input>, TypeIndex>,
+}
+
+impl<'a, 'input> Walker<'a, 'input> {
+    fn resolve(
+        &self,
+        input_id: usize,
+        ty: Cow<'a, Type<'input>>,
+    ) -> (usize, Cow<'a, Type<'input>>) {
+        return resolve_definition(self.inputs_hash, self.type_db, input_id, ty);
+    }
+
+    // 与 C++ 的名字查找一致: 先查找自身的成员, 包括匿名 struct/union 中的成员, 找不到时再查找各个基类.
+    fn lookup(&self, input_id: usize, ty: &Type<'input>, name: &str) -> Vec<Found<'input>> {
+        let hash = &self.inputs_hash[input_id];
+        let mut members = Vec::new();
+        let mut inherits = Vec::new();
+        match ty.kind() {
+            TypeKind::Struct(v) => {
+                for layout in v.layout(hash) {
+                    match layout.item {
+                        LayoutItem::Member(m) => members.push((layout.bit_offset, m.clone())),
+                        LayoutItem::Inherit(i) => inherits.push((layout.bit_offset, i.clone())),
+                        LayoutItem::Padding | LayoutItem::VariantPart(_) => {}
+                    }
+                }
+            }
+            TypeKind::Union(v) => {
+                members.extend(v.members().iter().map(|m| (m.bit_offset(), m.clone())));
+            }
+            _ => {}
+        }
+
+        let mut found = Vec::new();
+        for (bit_off, member) in members {
+            match member.name() {
+                Some(v) if v == name => found.push(Found {
+                    input_id,
+                    bit_off,
+                    member,
+                }),
+                Some(_) => {}
+                None => {
+                    let Some(mty) = member.ty(hash) else {
+                        continue;
+                    };
+                    let (mty_input_id, mty) = self.resolve(input_id, mty);
+                    for mut v in self.lookup(mty_input_id, &mty, name) {
+                        v.bit_off += bit_off;
+                        found.push(v);
+                    }
+                }
+            }
+        }
+        if !found.is_empty() {
+            return found;
+        }
+        for (bit_off, inherit) in inherits {
+            let Some(base) = inherit.ty(hash) else {
+                continue;
+            };
+            let (base_input_id, base) = self.resolve(input_id, base);
+            for mut v in self.lookup(base_input_id, &base, name) {
+                v.bit_off += bit_off;
+                found.push(v);
+            }
+        }
+        return found;
+    }
+}
+
+// 当前所在的位置, bit_off 是相对于 base 的偏移, base 为 None 时意味着相对于根类型.
+struct Cursor<'a, 'input> {
+    input_id: usize,
+    ty: Cow<'a, Type<'input>>,
+    bit_off: u64,
+    bit_size: Option<u64>,
+    base: Option<String>,
+}
+
+fn print_hop(walker: &Walker, path: &str, cursor: &Cursor) {
+    let hash = &walker.inputs_hash[cursor.input_id];
+    let mut line = format!("{}: offset={}", path, cursor.bit_off / BITS_PER_BYTE);
+    if let Some(base) = &cursor.base {
+        line.push_str(&format!(" (from {})", base));
+    }
+    match cursor.bit_size {
+        Some(bit_size) => line.push_str(&format!(
+            " bit_offset={} bit_size={}",
+            cursor.bit_off % BITS_PER_BYTE,
+            bit_size
+        )),
+        None => match cursor.ty.byte_size(hash) {
+            Some(size) => line.push_str(&format!(" size={}", size)),
+            None => line.push_str(" size=?"),
+        },
+    }
+    line.push_str(&format!(" type={}", type_display(hash, &cursor.ty)));
+    println!("{}", line);
+}
+
+// 如果 ty 是指针或引用, 返回其指向的类型.
+fn pointee<'a, 'input>(
+    hash: &'a FileHash<'input>,
+    ty: &Type<'input>,
+) -> Option<Cow<'a, Type<'input>>> {
+    let TypeKind::Modifier(v) = ty.kind() else {
+        return None;
+    };
+    match v.kind() {
+        TypeModifierKind::Pointer
+        | TypeModifierKind::Reference
+        | TypeModifierKind::RvalueReference => v.ty(hash),
+        _ => None,
+    }
+}
+
+// 输出路径中每一步的偏移, 大小与类型, 跨越指针之后的偏移是相对于该指针指向的地址.
+pub(crate) fn offsetof(
+    inputs_hash: &[FileHash],
+    type_db: &HashMap<TypeName, TypeIndex>,
+    input: &str,
+) -> anyhow::Result<()> {
+    let (tyname, steps) = parse_path(input)?;
+    let selector = parse_type_selector(&tyname)?;
+    let mut roots = Vec::new();
+    for (input_id, hash) in inputs_hash.iter().enumerate() {
+        for (&typoff, &typ) in hash.types.iter() {
+            if is_declaration(typ) {
+                continue;
+            }
+            let Some(typname) = TypeName::try_from(typ) else {
+                continue;
+            };
+            if selector.matches(&typname, typ, hash) {
+                roots.push(TypeIndex { input_id, typoff });
+            }
+        }
+    }
+    roots.sort_by_key(|v| (v.input_id, v.typoff));
+    let Some(root) = roots.first() else {
+        bail!("unknown type. type={}", tyname);
+    };
+    if roots.len() > 1 {
+        warn!(
+            "offsetof: {} types matched, use the first one. type={} tyidx={:?}",
+            roots.len(),
+            tyname,
+            root
+        );
+    }
+
+    let walker = Walker {
+        inputs_hash,
+        type_db,
+    };
+    let Some(root_ty) = Type::from_offset(&inputs_hash[root.input_id], root.typoff) else {
+        bail!("unknown type. tyidx={:?}", root);
+    };
+    let mut cursor = Cursor {
+        input_id: root.input_id,
+        ty: root_ty,
+        bit_off: 0,
+        bit_size: None,
+        base: None,
+    };
+    let mut path = TypeName::try_from(&cursor.ty)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| tyname.clone());
+    print_hop(&walker, &path, &cursor);
+
+    for (idx, step) in steps.iter().enumerate() {
+        let hash = &inputs_hash[cursor.input_id];
+        ensure!(
+            cursor.bit_size.is_none(),
+            "{} is a bitfield, can not step into it",
+            path
+        );
+        let (input_id, ty) = walker.resolve(cursor.input_id, cursor.ty.clone());
+        let hop_path;
+        let next = match step {
+            Step::Member(name) | Step::Arrow(name) => {
+                hop_path = if idx == 0 {
+                    format!("{}::{}", path, name)
+                } else if matches!(step, Step::Arrow(_)) {
+                    format!("{}->{}", path, name)
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                // `.` 作用于引用时, 与 `->` 一样需要跨越指针.
+                let deref = match (step, pointee(hash, &ty)) {
+                    (Step::Arrow(_), None) => bail!("{} is not a pointer", path),
+                    (Step::Member(_), Some(_)) if !is_reference(&ty) => {
+                        bail!("{} is a pointer, use '->'", path)
+                    }
+                    (_, v) => v,
+                };
+                let (base, input_id, ty, base_off) = match deref {
+                    Some(pointee_ty) => {
+                        let (input_id, ty) = walker.resolve(input_id, pointee_ty);
+                        (Some(path.clone()), input_id, ty, 0)
+                    }
+                    None => (cursor.base.clone(), input_id, ty, cursor.bit_off),
+                };
+                let mut found = walker.lookup(input_id, &ty, name);
+                ensure!(!found.is_empty(), "{} has no member named {}", path, name);
+                ensure!(found.len() == 1, "member {} is ambiguous in {}", name, path);
+                let found = found.remove(0);
+                let found_hash = &inputs_hash[found.input_id];
+                let Some(mty) = found.member.ty(found_hash) else {
+                    bail!("unknown type of {}", hop_path);
+                };
+                let bit_size = if found.member.is_bitfield() {
+                    found.member.bit_size(found_hash)
+                } else {
+                    None
+                };
+                Cursor {
+                    input_id: found.input_id,
+                    ty: mty,
+                    bit_off: base_off + found.bit_off,
+                    bit_size,
+                    base,
+                }
+            }
+            Step::Index(elem_idx) => {
+                hop_path = format!("{}[{}]", path, elem_idx);
+                let (base, elem_ty, base_off) = match (ty.kind(), pointee(hash, &ty)) {
+                    (TypeKind::Array(array), _) => {
+                        if let Some(count) = array.count(hash) {
+                            if *elem_idx >= count {
+                                warn!(
+                                    "offsetof: index out of bounds. path={} count={}",
+                                    hop_path, count
+                                );
+                            }
+                        }
+                        (
+                            cursor.base.clone(),
+                            array.element_type(hash),
+                            cursor.bit_off,
+                        )
+                    }
+                    (_, Some(pointee_ty)) if !is_reference(&ty) => {
+                        (Some(path.clone()), Some(pointee_ty), 0)
+                    }
+                    _ => bail!("{} is neither an array nor a pointer", path),
+                };
+                let Some(elem_ty) = elem_ty else {
+                    bail!("unknown element type of {}", path);
+                };
+                let (elem_input_id, elem_ty) = walker.resolve(input_id, elem_ty);
+                let Some(elem_size) = elem_ty.byte_size(&inputs_hash[elem_input_id]) else {
+                    bail!("unknown element size of {}", path);
+                };
+                Cursor {
+                    input_id: elem_input_id,
+                    ty: elem_ty,
+                    bit_off: base_off + elem_idx * elem_size * BITS_PER_BYTE,
+                    bit_size: None,
+                    base,
+                }
+            }
+        };
+        cursor = next;
+        path = hop_path;
+        print_hop(&walker, &path, &cursor);
+    }
+    return Ok(());
+}
+
+fn is_reference(ty: &Type) -> bool {
+    let TypeKind::Modifier(v) = ty.kind() else {
+        return false;
+    };
+    return matches!(
+        v.kind(),
+        TypeModifierKind::Reference | TypeModifierKind::RvalueReference
+    );
+}
@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::{
+    ArrayDef, Backend, EnumDef, FuncPtrDef, Member, MemberKind, Record, RecordKind, TypeRef,
+    TypedefDef, DISCRIMINANT_NAME,
+};
+
+fn is_keyword(ident: &str) -> bool {
+    matches!(
+        ident,
+        "False"
+            | "None"
+            | "True"
+            | "and"
+            | "as"
+            | "assert"
+            | "async"
+            | "await"
+            | "break"
+            | "class"
+            | "continue"
+            | "def"
+            | "del"
+            | "elif"
+            | "else"
+            | "except"
+            | "finally"
+            | "for"
+            | "from"
+            | "global"
+            | "if"
+            | "import"
+            | "in"
+            | "is"
+            | "lambda"
+            | "nonlocal"
+            | "not"
+            | "or"
+            | "pass"
+            | "raise"
+            | "return"
+            | "try"
+            | "while"
+            | "with"
+            | "yield"
+    )
+}
+
+// C/C++ 中合法的标识符在 Python 中可能是关键字, 此时加上 `_` 后缀.
+fn py_ident(ident: &str) -> Cow<'_, str> {
+    if is_keyword(ident) {
+        return Cow::Owned(format!("{}_", ident));
+    }
+    return Cow::Borrowed(ident);
+}
+
+// `_fields_` 中的名字是字符串, 可以是关键字, 但此时只能通过 getattr 访问.
+fn py_attr(obj: &str, name: &str) -> String {
+    if is_keyword(name) {
+        return format!("getattr({}, {:?})", obj, name);
+    }
+    return format!("{}.{}", obj, name);
+}
+
+// 与 rust.rs 一致, bool 也使用整数表示.
+fn base_type_name(size: u64, encoding: parser::BaseTypeEncoding) -> String {
+    use parser::BaseTypeEncoding::*;
+    let name = match (encoding, size) {
+        (Signed | SignedChar, 1) => "ctypes.c_int8",
+        (Signed | SignedChar, 2) => "ctypes.c_int16",
+        (Signed | SignedChar, 4) => "ctypes.c_int32",
+        (Signed | SignedChar, 8) => "ctypes.c_int64",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 1) => "ctypes.c_uint8",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 2) => "ctypes.c_uint16",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 4) => "ctypes.c_uint32",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 8) => "ctypes.c_uint64",
+        (Float, 4) => "ctypes.c_float",
+        (Float, 8) => "ctypes.c_double",
+        // 比如 long double, __int128.
+        _ => return format!("(ctypes.c_uint8 * {})", size),
+    };
+    return name.to_string();
+}
+
+fn type_name(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Base { size, encoding, .. } => base_type_name(*size, *encoding),
+        TypeRef::Struct(ident)
+        | TypeRef::Union(ident)
+        | TypeRef::Alias(ident)
+        | TypeRef::Typedef { ident, .. } => py_ident(ident).into_owned(),
+        // 离线解析时指针的值只是一个地址, 而且被指向的类型此时可能还没有定义.
+        TypeRef::Pointer(_) => "ctypes.c_void_p".to_string(),
+        TypeRef::Qualified { real, .. } => type_name(&real.ty),
+    }
+}
+
+fn member_type_name(member: &Member) -> String {
+    match member.tyinfo() {
+        Some(tyinfo) => type_name(&tyinfo.ty),
+        None => format!("(ctypes.c_uint8 * {})", member.len),
+    }
+}
+
+const BITS_DEF: &str = r###"
+# 从 obj.field 这个字节数组中取出 [bit_off, bit_off + bit_size) 这些 bit, 与 x86_64/aarch64 一致使用 little endian.
+def _clayout_bits(obj, field, bit_off, bit_size, signed):
+    val = int.from_bytes(bytes(getattr(obj, field)), "little") >> bit_off
+    val &= (1 << bit_size) - 1
+    if signed and val >> (bit_size - 1):
+        val -= 1 << bit_size
+    return val
+"###;
+
+// 所有的类型都是 `_pack_ = 1` 的, 与 C 头文件中的 packed 一致, 并通过 assert 校验其 layout, 就像 .c 中做的那样.
+// ctypes 在 `_pack_` 下的 bitfield layout 与 gcc 并不一致, 所以 bitfield 所在的位置仍然使用 `c_uint8` 数组占位,
+// 之后为每个 bitfield 生成一个同名的 property.
+pub(crate) struct PythonPrinter {
+    file: std::fs::File,
+    // enum ident --> 其底层类型, 用于判断 bitfield 是否有符号.
+    enums: HashMap<String, TypeRef>,
+}
+
+impl PythonPrinter {
+    pub(crate) fn try_open(path: &str) -> io::Result<PythonPrinter> {
+        let mut file_name = path.to_string();
+        file_name.push_str(".py");
+        let mut file = std::fs::File::create(file_name)?;
+        writeln!(file, "# Generated by hidva/clayout! 大吉大利!")?;
+        writeln!(file, "import ctypes")?;
+        write!(file, "{}", BITS_DEF)?;
+        writeln!(file)?;
+        Ok(PythonPrinter {
+            file,
+            enums: HashMap::new(),
+        })
+    }
+
+    fn add_eq_assert(&mut self, expr: &str, val: u64) -> io::Result<()> {
+        writeln!(self.file, "assert {} == {}", expr, val)
+    }
+
+    fn add_size_assert(&mut self, tyname: &str, size: u64) -> io::Result<()> {
+        self.add_eq_assert(&format!("ctypes.sizeof({})", tyname), size)
+    }
+
+    fn is_signed(&self, ty: &TypeRef) -> bool {
+        use parser::BaseTypeEncoding::*;
+        match ty {
+            TypeRef::Base { encoding, .. } => matches!(encoding, Signed | SignedChar),
+            TypeRef::Alias(ident) => self.enums.get(ident).is_some_and(|v| self.is_signed(v)),
+            TypeRef::Typedef { real, .. } | TypeRef::Qualified { real, .. } => {
+                self.is_signed(&real.ty)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Backend for PythonPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        let ident = py_ident(record.ident);
+        let base = match record.kind {
+            RecordKind::Struct => "ctypes.Structure",
+            RecordKind::Union => "ctypes.Union",
+        };
+        writeln!(
+            self.file,
+            "# tyname={} tyidx={:?}",
+            record.tyname, record.tyidx
+        )?;
+        writeln!(self.file, "class {}({}):", ident, base)?;
+        // 没有 bitfield 时 "ms" layout 与 "gcc-sysv" 一致, Python 3.14 开始 `_pack_` 要求显式指定 `_layout_`.
+        writeln!(self.file, "    _layout_ = \"ms\"")?;
+        writeln!(self.file, "    _pack_ = 1")?;
+        writeln!(self.file, "    _fields_ = [")?;
+        for member in record.members {
+            if let MemberKind::Variant { discr_value, .. } = &member.kind {
+                match discr_value {
+                    Some(v) => writeln!(self.file, "        # {} == {}", DISCRIMINANT_NAME, v)?,
+                    None => writeln!(self.file, "        # otherwise")?,
+                }
+            }
+            writeln!(
+                self.file,
+                "        ({:?}, {}),",
+                member.field_name,
+                member_type_name(member)
+            )?;
+        }
+        writeln!(self.file, "    ]")?;
+
+        for member in record.members {
+            let MemberKind::Bitfield(bitfields) = &member.kind else {
+                continue;
+            };
+            for bitfield in bitfields {
+                writeln!(
+                    self.file,
+                    "{}.{} = property(lambda self: _clayout_bits(self, {:?}, {}, {}, {}))",
+                    ident,
+                    py_ident(&bitfield.name),
+                    member.field_name,
+                    bitfield.bit_off,
+                    bitfield.bit_size,
+                    if self.is_signed(&bitfield.tyinfo.ty) {
+                        "True"
+                    } else {
+                        "False"
+                    }
+                )?;
+            }
+        }
+
+        for member in record.members {
+            let field = py_attr(&ident, &member.field_name);
+            self.add_eq_assert(&format!("{}.offset", field), member.off)?;
+            self.add_eq_assert(&format!("{}.size", field), member.len)?;
+        }
+        self.add_size_assert(&ident, record.packed_size)?;
+        writeln!(self.file)
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        let ident = py_ident(array.ident);
+        writeln!(
+            self.file,
+            "{} = {} * {}",
+            ident,
+            type_name(array.elem),
+            array.count
+        )?;
+        self.add_size_assert(&ident, array.size)?;
+        writeln!(self.file)
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        let ident = py_ident(enum_def.ident);
+        self.enums
+            .insert(enum_def.ident.to_string(), enum_def.repr.clone());
+        writeln!(self.file, "{} = {}", ident, type_name(enum_def.repr))?;
+        self.add_size_assert(&ident, enum_def.size)?;
+        // 字段的类型仍是整数, 这里仅提供各个枚举值, 比如 `s.e == E_Values.A`.
+        writeln!(self.file, "class {}_Values:", ident)?;
+        let mut empty = true;
+        for enum_item in enum_def.enumerators {
+            let Some(value) = enum_item.value else {
+                continue;
+            };
+            writeln!(self.file, "    {} = {}", py_ident(&enum_item.name), value)?;
+            empty = false;
+        }
+        if empty {
+            writeln!(self.file, "    pass")?;
+        }
+        writeln!(self.file)
+    }
+
+    // 指针总是 c_void_p, 不需要前向声明.
+    fn add_forward_decl(&mut self, _ty: &TypeRef) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        let ident = py_ident(typedef.ident);
+        writeln!(self.file, "{} = {}", ident, type_name(typedef.real))?;
+        self.add_size_assert(&ident, typedef.packed_size)?;
+        writeln!(self.file)
+    }
+
+    // 与指针一样只保存地址.
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        let ident = py_ident(func.ident);
+        writeln!(self.file, "{} = ctypes.c_void_p", ident)?;
+        self.add_size_assert(&ident, func.size)?;
+        writeln!(self.file)
+    }
+
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()> {
+        self.add_size_assert(&type_name(ty), size)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use crate::{
+    ArrayDef, Backend, EnumDef, FuncPtrDef, Member, MemberKind, Qualifier, Record, RecordKind,
+    TypeRef, TypedefDef, DISCRIMINANT_NAME,
+};
+
+// C/C++ 中合法的标识符在 Rust 中可能是关键字, 此时使用 raw identifier.
+fn rust_ident(ident: &str) -> Cow<'_, str> {
+    match ident {
+        // 这几个不能作为 raw identifier.
+        "self" | "Self" | "super" | "crate" | "_" => Cow::Owned(format!("{}_", ident)),
+        "abstract" | "as" | "async" | "await" | "become" | "box" | "break" | "const"
+        | "continue" | "do" | "dyn" | "else" | "enum" | "extern" | "false" | "final" | "fn"
+        | "for" | "gen" | "if" | "impl" | "in" | "let" | "loop" | "macro" | "match" | "mod"
+        | "move" | "mut" | "override" | "priv" | "pub" | "ref" | "return" | "static" | "struct"
+        | "trait" | "true" | "try" | "type" | "typeof" | "unsafe" | "unsized" | "use"
+        | "virtual" | "where" | "while" | "yield" => Cow::Owned(format!("r#{}", ident)),
+        _ => Cow::Borrowed(ident),
+    }
+}
+
+// 这里只关心 size 与 encoding, 不关心 C 中的类型名.
+// bool 也使用整数表示, 毕竟从 bpftrace 中拿到的字节不一定是合法的 bool.
+fn base_type_name(size: u64, encoding: parser::BaseTypeEncoding) -> Cow<'static, str> {
+    use parser::BaseTypeEncoding::*;
+    let name = match (encoding, size) {
+        (Signed | SignedChar, 1) => "i8",
+        (Signed | SignedChar, 2) => "i16",
+        (Signed | SignedChar, 4) => "i32",
+        (Signed | SignedChar, 8) => "i64",
+        (Signed | SignedChar, 16) => "i128",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 1) => "u8",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 2) => "u16",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 4) => "u32",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 8) => "u64",
+        (Unsigned | UnsignedChar | Boolean | Address | Other, 16) => "u128",
+        (Float, 4) => "f32",
+        (Float, 8) => "f64",
+        // 比如 long double.
+        _ => return Cow::Owned(format!("[u8; {}]", size)),
+    };
+    Cow::Borrowed(name)
+}
+
+fn type_name(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Base { size, encoding, .. } => base_type_name(*size, *encoding).into_owned(),
+        TypeRef::Struct(ident)
+        | TypeRef::Union(ident)
+        | TypeRef::Alias(ident)
+        | TypeRef::Typedef { ident, .. } => rust_ident(ident).into_owned(),
+        TypeRef::Pointer(None) => "*mut core::ffi::c_void".to_string(),
+        TypeRef::Pointer(Some(tyinfo)) => match &tyinfo.ty {
+            // `const Foo*`, 只有指向的类型本身是 const 时才能用 `*const`.
+            TypeRef::Qualified {
+                qualifier: Qualifier::Const,
+                real,
+            } => format!("*const {}", type_name(&real.ty)),
+            ty => format!("*mut {}", type_name(ty)),
+        },
+        // Rust 中没有 volatile 类型, const 只能体现在指针上.
+        TypeRef::Qualified { real, .. } => type_name(&real.ty),
+    }
+}
+
+fn member_type_name(member: &Member) -> String {
+    match member.tyinfo() {
+        Some(tyinfo) => type_name(&tyinfo.ty),
+        None => format!("[u8; {}]", member.len),
+    }
+}
+
+// 与 CPrinter 不同, 这里所有的校验都是编译期完成的, 即 `const _: () = assert!(...)`.
+// 生成的代码仅依赖 core, 可以直接通过 `mod` 引入到 no_std crate 中.
+pub(crate) struct RustPrinter {
+    file: std::fs::File,
+}
+
+impl RustPrinter {
+    pub(crate) fn try_open(path: &str) -> io::Result<RustPrinter> {
+        let mut file_name = path.to_string();
+        file_name.push_str(".rs");
+        let mut file = std::fs::File::create(file_name)?;
+        writeln!(file, "// Generated by hidva/clayout! 大吉大利!")?;
+        writeln!(
+            file,
+            "#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals, dead_code)]"
+        )?;
+        writeln!(file, "")?;
+        Ok(RustPrinter { file })
+    }
+
+    fn add_eq_assert(&mut self, expr: &str, val: u64) -> io::Result<()> {
+        writeln!(self.file, "const _: () = assert!({} == {});", expr, val)
+    }
+
+    fn add_size_assert(&mut self, tyname: &str, size: u64) -> io::Result<()> {
+        self.add_eq_assert(&format!("core::mem::size_of::<{}>()", tyname), size)
+    }
+}
+
+impl Backend for RustPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        let ident = rust_ident(record.ident);
+        writeln!(
+            self.file,
+            "// tyname={} tyidx={:?}",
+            record.tyname, record.tyidx
+        )?;
+        // union 中有 __HIDVA_dont_use 成员, 其大小与 dwarf 中记录的 size 一致, 所以这里不需要 packed.
+        let (repr, keyword) = match record.kind {
+            RecordKind::Struct => ("C, packed", "struct"),
+            RecordKind::Union => ("C", "union"),
+        };
+        writeln!(self.file, "#[repr({})]", repr)?;
+        writeln!(self.file, "#[derive(Clone, Copy)]")?;
+        writeln!(self.file, "pub {} {} {{", keyword, ident)?;
+        for member in record.members {
+            // Rust 没有 bitfield, 这里仍然使用 `[u8; len]` 占位, 并在注释中给出各个 bitfield 的位置.
+            if let MemberKind::Bitfield(bitfields) = &member.kind {
+                for bitfield in bitfields {
+                    writeln!(
+                        self.file,
+                        "    // {}: {} bit_off={} bit_size={}",
+                        bitfield.name,
+                        type_name(&bitfield.tyinfo.ty),
+                        bitfield.bit_off,
+                        bitfield.bit_size
+                    )?;
+                }
+            }
+            if let MemberKind::Variant { discr_value, .. } = &member.kind {
+                match discr_value {
+                    Some(v) => writeln!(self.file, "    // {} == {}", DISCRIMINANT_NAME, v)?,
+                    None => writeln!(self.file, "    // otherwise")?,
+                }
+            }
+            writeln!(
+                self.file,
+                "    pub {}: {},",
+                rust_ident(&member.field_name),
+                member_type_name(member)
+            )?;
+        }
+        writeln!(self.file, "}}")?;
+
+        for member in record.members {
+            self.add_eq_assert(
+                &format!(
+                    "core::mem::offset_of!({}, {})",
+                    ident,
+                    rust_ident(&member.field_name)
+                ),
+                member.off,
+            )?;
+            self.add_size_assert(&member_type_name(member), member.len)?;
+        }
+        self.add_size_assert(&ident, record.packed_size)?;
+        writeln!(self.file, "")
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        let ident = rust_ident(array.ident);
+        writeln!(
+            self.file,
+            "pub type {} = [{}; {}];",
+            ident,
+            type_name(array.elem),
+            array.count
+        )?;
+        self.add_size_assert(&ident, array.size)?;
+        writeln!(self.file, "")
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        let ident = rust_ident(enum_def.ident);
+        writeln!(self.file, "// --- enum {} begin ---", ident)?;
+        for enum_item in enum_def.enumerators {
+            writeln!(
+                self.file,
+                "// {}={}",
+                enum_item.name,
+                enum_item.value.unwrap_or(-20181218),
+            )?;
+        }
+        writeln!(self.file, "// --- enum {} end ---", ident)?;
+        writeln!(
+            self.file,
+            "pub type {} = {};",
+            ident,
+            type_name(enum_def.repr)
+        )?;
+        self.add_size_assert(&ident, enum_def.size)?;
+        writeln!(self.file, "")
+    }
+
+    // Rust 中类型定义的顺序无关紧要.
+    fn add_forward_decl(&mut self, _ty: &TypeRef) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        let ident = rust_ident(typedef.ident);
+        writeln!(
+            self.file,
+            "pub type {} = {};",
+            ident,
+            type_name(typedef.real)
+        )?;
+        self.add_size_assert(&ident, typedef.packed_size)?;
+        writeln!(self.file, "")
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        let ident = rust_ident(func.ident);
+        let mut params: Vec<String> = func.params.iter().map(type_name).collect();
+        if func.variadic && !params.is_empty() {
+            params.push("...".to_string());
+        }
+        let ret = match func.ret {
+            Some(ret) => format!(" -> {}", type_name(ret)),
+            None => String::new(),
+        };
+        // 与 C 中的函数指针一样可以为 NULL.
+        writeln!(
+            self.file,
+            "pub type {} = Option<unsafe extern \"C\" fn({}){}>;",
+            ident,
+            params.join(", "),
+            ret
+        )?;
+        self.add_size_assert(&ident, func.size)?;
+        writeln!(self.file, "")
+    }
+
+    fn add_size_check(&mut self, ty: &TypeRef, size: u64) -> io::Result<()> {
+        self.add_size_assert(&type_name(ty), size)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,771 @@
+use anyhow::{bail, ensure};
+use parser::{FileHash, TemplateParameter, Type, TypeKind, TypeModifierKind, TypeName};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::{is_declaration, is_ident_char, TypeIndex};
+
+fn strip_ws(input: &str) -> String {
+    input.chars().filter(|v| !v.is_whitespace()).collect()
+}
+
+// 按照不在 `<>`, `()`, `[]` 中的 sep 切分.
+pub(crate) fn split_top_level<'a>(input: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut ret = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut idx = 0;
+    let bytes = input.as_bytes();
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'<' | b'(' | b'[' => depth += 1,
+            b'>' | b')' | b']' => depth -= 1,
+            _ => {
+                if depth == 0 && input[idx..].starts_with(sep) {
+                    ret.push(&input[start..idx]);
+                    idx += sep.len();
+                    start = idx;
+                    continue;
+                }
+            }
+        }
+        idx += 1;
+    }
+    ret.push(&input[start..]);
+    return ret;
+}
+
+// 'Foo<int, Bar<3> >' -> ('Foo', Some('int, Bar<3> ')), 'Foo' -> ('Foo', None).
+fn split_template_args(input: &str) -> Option<(&str, Option<&str>)> {
+    let input = input.trim();
+    let Some(lt) = input.find('<') else {
+        return Some((input, None));
+    };
+    let args = input[lt + 1..].strip_suffix('>')?;
+    return Some((input[..lt].trim(), Some(args)));
+}
+
+fn split_args(input: &str) -> Vec<&str> {
+    if input.trim().is_empty() {
+        return Vec::new();
+    }
+    return split_top_level(input, ",");
+}
+
+fn strip_keyword_prefix<'a>(input: &'a str, kw: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(kw)?;
+    if rest.starts_with(is_ident_char) {
+        return None;
+    }
+    return Some(rest.trim_start());
+}
+
+fn strip_keyword_suffix<'a>(input: &'a str, kw: &str) -> Option<&'a str> {
+    let rest = input.strip_suffix(kw)?;
+    if rest.ends_with(is_ident_char) {
+        return None;
+    }
+    return Some(rest.trim_end());
+}
+
+// 将 `long unsigned int`, `unsigned long` 等不同写法统一为同一个名字, 非内置类型返回 None.
+fn canonical_builtin(name: &str) -> Option<String> {
+    let mut unsigned = false;
+    let mut signed = false;
+    let mut longs = 0;
+    let mut rest = Vec::new();
+    let mut empty = true;
+    for word in name.split_whitespace() {
+        empty = false;
+        match word {
+            "unsigned" => unsigned = true,
+            "signed" => signed = true,
+            "long" => longs += 1,
+            "int" => {}
+            "short" | "char" | "bool" | "float" | "double" | "void" | "wchar_t" | "char8_t"
+            | "char16_t" | "char32_t" | "__int128" => rest.push(word),
+            _ => return None,
+        }
+    }
+    if empty {
+        return None;
+    }
+    let sign = if unsigned { "unsigned " } else { "" };
+    let ret = match (rest.as_slice(), longs) {
+        ([], 0) => format!("{}int", sign),
+        ([], 1) => format!("{}long", sign),
+        ([], 2) => format!("{}long long", sign),
+        (["short"], 0) => format!("{}short", sign),
+        (["__int128"], 0) => format!("{}__int128", sign),
+        // char, signed char, unsigned char 是三个不同的类型.
+        (["char"], 0) if signed => "signed char".to_string(),
+        (["char"], 0) => format!("{}char", sign),
+        (["double"], 1) if !unsigned && !signed => "long double".to_string(),
+        ([v], 0) if !unsigned && !signed => v.to_string(),
+        _ => return None,
+    };
+    return Some(ret);
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+struct Cv {
+    is_const: bool,
+    is_volatile: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TypeArgOp {
+    Pointer,
+    Reference,
+    RvalueReference,
+}
+
+#[derive(Clone, Debug)]
+enum TypeArgBase {
+    Builtin(String),
+    Named(Box<TypeSelector>),
+}
+
+// 形如 `const ns::Foo<int>* const&` 的类型实参. ops 中越靠后的越靠外层, 每一层都有自己的 cv.
+// 不支持数组, 函数等类型.
+#[derive(Clone, Debug)]
+struct TypeArg {
+    base: TypeArgBase,
+    cv: Cv,
+    ops: Vec<(TypeArgOp, Cv)>,
+}
+
+#[derive(Clone, Debug)]
+enum ValueArg {
+    Int(i128),
+    // enum 类型的 value parameter 可以使用 enumerator 名字.
+    Enumerator(String),
+}
+
+#[derive(Clone, Debug)]
+struct TemplateArg {
+    // 去掉了空白.
+    text: String,
+    // 在匹配时才知道实参是类型还是值, 所以这里两种都尝试解析一下.
+    ty: Option<TypeArg>,
+    value: Option<ValueArg>,
+}
+
+pub(crate) fn parse_int(input: &str) -> Option<i128> {
+    let (neg, input) = match input.strip_prefix('-') {
+        Some(v) => (true, v.trim_start()),
+        None => (false, input),
+    };
+    if !input.starts_with(|v: char| v.is_ascii_digit()) {
+        return None;
+    }
+    let input = input.trim_end_matches(['u', 'U', 'l', 'L', 'z', 'Z']);
+    let input = input.replace('\'', "");
+    let lower = input.to_ascii_lowercase();
+    let (radix, digits) = if let Some(v) = lower.strip_prefix("0x") {
+        (16, v)
+    } else if let Some(v) = lower.strip_prefix("0b") {
+        (2, v)
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        (8, &lower[1..])
+    } else {
+        (10, lower.as_str())
+    };
+    let val = i128::from_str_radix(digits, radix).ok()?;
+    return Some(if neg { -val } else { val });
+}
+
+fn parse_value_arg(input: &str) -> Option<ValueArg> {
+    match input {
+        "true" => return Some(ValueArg::Int(1)),
+        "false" => return Some(ValueArg::Int(0)),
+        _ => {}
+    }
+    if let Some(ch) = input.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        let mut chars = ch.chars();
+        let (Some(ch), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        return Some(ValueArg::Int(ch as i128));
+    }
+    if let Some(val) = parse_int(input) {
+        return Some(ValueArg::Int(val));
+    }
+    let parts = split_top_level(input, "::");
+    let last = parts.last()?.trim();
+    if !last.is_empty() && last.chars().all(is_ident_char) {
+        return Some(ValueArg::Enumerator(last.to_string()));
+    }
+    return None;
+}
+
+fn parse_type_arg(input: &str) -> Option<TypeArg> {
+    let mut depth = 0i32;
+    let mut ops_idx = input.len();
+    for (idx, ch) in input.char_indices() {
+        match ch {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            '(' | '[' if depth == 0 => return None,
+            '*' | '&' if depth == 0 => {
+                ops_idx = idx;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let mut cv = Cv::default();
+    let mut base = input[..ops_idx].trim();
+    loop {
+        if let Some(v) = strip_keyword_prefix(base, "const") {
+            cv.is_const = true;
+            base = v;
+        } else if let Some(v) = strip_keyword_prefix(base, "volatile") {
+            cv.is_volatile = true;
+            base = v;
+        } else if let Some(v) = strip_keyword_suffix(base, "const") {
+            cv.is_const = true;
+            base = v;
+        } else if let Some(v) = strip_keyword_suffix(base, "volatile") {
+            cv.is_volatile = true;
+            base = v;
+        } else if let Some(v) = ["struct", "class", "union", "enum", "typename"]
+            .iter()
+            .find_map(|kw| strip_keyword_prefix(base, kw))
+        {
+            base = v;
+        } else {
+            break;
+        }
+    }
+    let base = match canonical_builtin(base) {
+        Some(v) => TypeArgBase::Builtin(v),
+        None => TypeArgBase::Named(Box::new(parse_type_selector(base).ok()?)),
+    };
+
+    let mut ops: Vec<(TypeArgOp, Cv)> = Vec::new();
+    let mut rest = input[ops_idx..].trim_start();
+    while !rest.is_empty() {
+        if let Some(v) = rest.strip_prefix("&&") {
+            ops.push((TypeArgOp::RvalueReference, Cv::default()));
+            rest = v;
+        } else if let Some(v) = rest.strip_prefix('&') {
+            ops.push((TypeArgOp::Reference, Cv::default()));
+            rest = v;
+        } else if let Some(v) = rest.strip_prefix('*') {
+            ops.push((TypeArgOp::Pointer, Cv::default()));
+            rest = v;
+        } else if let Some(v) = strip_keyword_prefix(rest, "const") {
+            ops.last_mut()?.1.is_const = true;
+            rest = v;
+        } else if let Some(v) = strip_keyword_prefix(rest, "volatile") {
+            ops.last_mut()?.1.is_volatile = true;
+            rest = v;
+        } else {
+            return None;
+        }
+        rest = rest.trim_start();
+    }
+    return Some(TypeArg { base, cv, ops });
+}
+
+// 展开 parameter pack, bool 表示形参是否来自 pack.
+fn flatten_template_parameters<'a, 'input>(
+    params: &'a [TemplateParameter<'input>],
+    in_pack: bool,
+    out: &mut Vec<(&'a TemplateParameter<'input>, bool)>,
+) {
+    for param in params {
+        match param {
+            TemplateParameter::Pack { params, .. } => {
+                flatten_template_parameters(params, true, out)
+            }
+            _ => out.push((param, in_pack)),
+        }
+    }
+}
+
+// 去掉 typedef 与 cv.
+pub(crate) fn resolve_alias<'a, 'input>(
+    hash: &'a FileHash<'input>,
+    mut ty: Cow<'a, Type<'input>>,
+) -> Option<Cow<'a, Type<'input>>> {
+    loop {
+        let next = match ty.kind() {
+            TypeKind::Def(v) => v.ty(hash),
+            TypeKind::Modifier(v)
+                if matches!(
+                    v.kind(),
+                    TypeModifierKind::Const | TypeModifierKind::Volatile
+                ) =>
+            {
+                v.ty(hash)
+            }
+            _ => return Some(ty),
+        };
+        ty = next?;
+    }
+}
+
+// 去掉 typedef 与 cv, 并将 declaration 替换为其定义, 定义可能在其他 input 中.
+pub(crate) fn resolve_definition<'a, 'input>(
+    inputs_hash: &'a [FileHash<'input>],
+    type_db: &HashMap<TypeName, TypeIndex>,
+    input_id: usize,
+    ty: Cow<'a, Type<'input>>,
+) -> (usize, Cow<'a, Type<'input>>) {
+    let hash = &inputs_hash[input_id];
+    let ty = resolve_alias(hash, ty.clone()).unwrap_or(ty);
+    if !is_declaration(&ty) {
+        return (input_id, ty);
+    }
+    let Some(tyname) = TypeName::try_from(&ty) else {
+        return (input_id, ty);
+    };
+    let Some(real_tyidx) = type_db.get(&tyname) else {
+        return (input_id, ty);
+    };
+    let real_hash = &inputs_hash[real_tyidx.input_id];
+    let Some(real_ty) = Type::from_offset(real_hash, real_tyidx.typoff) else {
+        return (input_id, ty);
+    };
+    return (real_tyidx.input_id, real_ty);
+}
+
+impl TypeArg {
+    fn matches<'a, 'input>(&self, hash: &'a FileHash<'input>, ty: Cow<'a, Type<'input>>) -> bool {
+        return self.matches_level(hash, ty, self.ops.len());
+    }
+
+    // 使用 ops[..depth] 匹配 ty.
+    fn matches_level<'a, 'input>(
+        &self,
+        hash: &'a FileHash<'input>,
+        mut ty: Cow<'a, Type<'input>>,
+        depth: usize,
+    ) -> bool {
+        let mut cv = Cv::default();
+        while let TypeKind::Modifier(v) = ty.kind() {
+            let (kind, next) = (v.kind(), v.ty(hash));
+            match kind {
+                TypeModifierKind::Const => cv.is_const = true,
+                TypeModifierKind::Volatile => cv.is_volatile = true,
+                _ => break,
+            }
+            let Some(next) = next else {
+                return false;
+            };
+            ty = next;
+        }
+
+        if depth == 0 {
+            return cv == self.cv && self.base.matches(hash, &ty);
+        }
+        let (op, op_cv) = self.ops[depth - 1];
+        if cv != op_cv {
+            return false;
+        }
+        let TypeKind::Modifier(modifier) = ty.kind() else {
+            return false;
+        };
+        let expected = match op {
+            TypeArgOp::Pointer => TypeModifierKind::Pointer,
+            TypeArgOp::Reference => TypeModifierKind::Reference,
+            TypeArgOp::RvalueReference => TypeModifierKind::RvalueReference,
+        };
+        if modifier.kind() != expected {
+            return false;
+        }
+        let Some(next) = modifier.ty(hash) else {
+            return false;
+        };
+        return self.matches_level(hash, next, depth - 1);
+    }
+}
+
+impl TypeArgBase {
+    fn matches(&self, hash: &FileHash, ty: &Type) -> bool {
+        match self {
+            TypeArgBase::Builtin(name) => match ty.kind() {
+                TypeKind::Void => name == "void",
+                TypeKind::Base(v) => v.name().and_then(canonical_builtin).as_ref() == Some(name),
+                _ => false,
+            },
+            // 比如 Rust 中的 u32, 也是 base type.
+            TypeArgBase::Named(selector) => match (TypeName::try_from(ty), ty.kind()) {
+                (Some(tyname), _) => selector.matches(&tyname, ty, hash),
+                (None, TypeKind::Modifier(v)) => match v.name() {
+                    Some(name) => selector.matches_qualified_name(name),
+                    None => false,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+impl TemplateArg {
+    fn parse(input: &str) -> TemplateArg {
+        let input = input.trim();
+        return TemplateArg {
+            text: strip_ws(input),
+            ty: parse_type_arg(input),
+            value: parse_value_arg(input),
+        };
+    }
+
+    fn matches(&self, hash: &FileHash, param: &TemplateParameter) -> bool {
+        match param {
+            TemplateParameter::Type { ty, .. } => {
+                let Some(arg) = &self.ty else {
+                    return false;
+                };
+                let Some(ty) = Type::from_offset(hash, *ty) else {
+                    return false;
+                };
+                return arg.matches(hash, ty);
+            }
+            TemplateParameter::Value { ty, value, .. } => {
+                let (Some(arg), Some(value)) = (&self.value, value) else {
+                    return false;
+                };
+                let Some(ty) = Type::from_offset(hash, *ty).and_then(|v| resolve_alias(hash, v))
+                else {
+                    return false;
+                };
+                let arg = match arg {
+                    ValueArg::Int(v) => *v as u64,
+                    ValueArg::Enumerator(name) => {
+                        let TypeKind::Enumeration(enum_ty) = ty.kind() else {
+                            return false;
+                        };
+                        let enumerators = enum_ty.enumerators(hash);
+                        let Some(v) = enumerators
+                            .iter()
+                            .find(|v| v.name() == Some(name.as_str()))
+                            .and_then(|v| v.value())
+                        else {
+                            return false;
+                        };
+                        v as u64
+                    }
+                };
+                // 实参会被转换为形参类型, 所以 `-1` 与 `0xffffffff` 对于 unsigned int 是同一个值.
+                let size = ty.byte_size(hash).unwrap_or(8);
+                let mask = if size >= 8 {
+                    u64::MAX
+                } else {
+                    (1u64 << (size * 8)) - 1
+                };
+                return arg & mask == value & mask;
+            }
+            TemplateParameter::Pack { .. } => false,
+        }
+    }
+}
+
+// 用于选择要输出的类型, 形如 `ns1::ns2::Foo<int, 3>`.
+#[derive(Clone, Debug)]
+pub(crate) struct TypeSelector {
+    // 以 `::` 开头时要求从 root namespace 开始匹配, 否则 `ns2::Foo` 也可以匹配 `::ns1::ns2::Foo`.
+    rooted: bool,
+    namespace: Vec<String>,
+    name: String,
+    // None 意味着匹配所有实例化. 实参可以少于形参, 但多出来的形参必须使用了默认值, 即 DW_AT_default_value,
+    // 且 parameter pack 中的形参个数必须与实参一致.
+    args: Option<Vec<TemplateArg>>,
+}
+
+// 'ns::Foo<int, Bar<3> >' -> TypeSelector { namespace: ['ns'], name: 'Foo', args: ['int', 'Bar<3>'] }.
+pub(crate) fn parse_type_selector(input: &str) -> anyhow::Result<TypeSelector> {
+    let input = input.trim();
+    let (rooted, input) = match input.strip_prefix("::") {
+        Some(v) => (true, v),
+        None => (false, input),
+    };
+    let mut parts = split_top_level(input, "::");
+    let Some(last) = parts.pop() else {
+        bail!("empty type name");
+    };
+    let Some((name, args)) = split_template_args(last) else {
+        bail!("invalid template argument list. input={}", input);
+    };
+    ensure!(!name.is_empty(), "empty type name. input={}", input);
+    let mut namespace = Vec::new();
+    for part in parts {
+        let part = strip_ws(part);
+        ensure!(!part.is_empty(), "empty namespace. input={}", input);
+        namespace.push(part);
+    }
+    return Ok(TypeSelector {
+        rooted,
+        namespace,
+        name: strip_ws(name),
+        args: args.map(|v| split_args(v).into_iter().map(TemplateArg::parse).collect()),
+    });
+}
+
+// 只有名字时无法知道形参是否有默认值, 只能允许省略末尾的实参.
+fn args_match_text(args: &[TemplateArg], die_args: Option<&str>) -> bool {
+    let die_args = die_args.map(split_args).unwrap_or_default();
+    return args.len() <= die_args.len()
+        && args
+            .iter()
+            .zip(die_args)
+            .all(|(l, r)| l.text == strip_ws(r));
+}
+
+impl TypeSelector {
+    // Rust 中的 `Box<T>` 等是带名字的指针, 只有 'alloc::boxed::Box<u32, alloc::alloc::Global>' 这样的名字可用.
+    fn matches_qualified_name(&self, name: &str) -> bool {
+        let name = name.trim();
+        let name = name.strip_prefix("::").unwrap_or(name);
+        let mut parts = split_top_level(name, "::");
+        let Some(last) = parts.pop() else {
+            return false;
+        };
+        let (base, die_args) = split_template_args(last).unwrap_or((last, None));
+        if strip_ws(base) != self.name
+            || parts.len() < self.namespace.len()
+            || (self.rooted && parts.len() != self.namespace.len())
+        {
+            return false;
+        }
+        let ns_matched = parts
+            .iter()
+            .rev()
+            .zip(self.namespace.iter().rev())
+            .all(|(l, r)| strip_ws(l) == *r);
+        if !ns_matched {
+            return false;
+        }
+        return match &self.args {
+            Some(args) => args_match_text(args, die_args),
+            None => true,
+        };
+    }
+
+    pub(crate) fn matches(&self, tyname: &TypeName, ty: &Type, hash: &FileHash) -> bool {
+        let Some(name) = tyname.name else {
+            return false;
+        };
+        let (base, die_args) = split_template_args(name).unwrap_or((name, None));
+        if strip_ws(base) != self.name {
+            return false;
+        }
+
+        let mut ns_opt = tyname.namespace;
+        for part in self.namespace.iter().rev() {
+            let Some(ns) = ns_opt else {
+                return false;
+            };
+            if ns.name().map(strip_ws).as_ref() != Some(part) {
+                return false;
+            }
+            ns_opt = ns.parent();
+        }
+        if self.rooted && ns_opt.is_some() {
+            return false;
+        }
+
+        let Some(args) = &self.args else {
+            return true;
+        };
+        let params = match ty.kind() {
+            TypeKind::Struct(v) => v.template_parameters(),
+            TypeKind::Union(v) => v.template_parameters(),
+            _ => &[],
+        };
+        let mut flat_params = Vec::new();
+        flatten_template_parameters(params, false, &mut flat_params);
+        if flat_params.is_empty() {
+            // 拿不到 template parameter 时, 只能比较 DIE name 中的实参了.
+            return args_match_text(args, die_args);
+        }
+        if args.len() > flat_params.len()
+            || flat_params[args.len()..]
+                .iter()
+                .any(|(param, in_pack)| *in_pack || !param.is_default())
+        {
+            return false;
+        }
+        return args
+            .iter()
+            .zip(flat_params)
+            .all(|(arg, (param, _))| arg.matches(hash, param));
+    }
+}
+
+// 通配符匹配, `*` 匹配任意字符串, `?` 匹配任意一个字符.
+fn wildcard_match(pattern: &[char], input: &[char]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // 上一个 `*` 在 pattern 中的位置, 以及其当前匹配到了 input 哪里.
+    let mut star: Option<(usize, usize)> = None;
+    while i < input.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == input[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((star_p, star_i)) = star {
+            p = star_p + 1;
+            i = star_i + 1;
+            star = Some((star_p, star_i + 1));
+        } else {
+            return false;
+        }
+    }
+    return pattern[p..].iter().all(|v| *v == '*');
+}
+
+fn glob_segments_match(pattern: &[Vec<char>], segments: &[Vec<char>]) -> bool {
+    let Some((first, pattern_rest)) = pattern.split_first() else {
+        return segments.is_empty();
+    };
+    if first.as_slice() == ['*', '*'] {
+        return (0..=segments.len()).any(|v| glob_segments_match(pattern_rest, &segments[v..]));
+    }
+    let Some((segment, segments_rest)) = segments.split_first() else {
+        return false;
+    };
+    // pattern 中没有给出 template 实参时, 匹配所有实例化, 即 `*Request` 也可以匹配 `Request<T>`.
+    let segment = match segment.iter().position(|v| *v == '<') {
+        Some(lt) if !first.contains(&'<') => &segment[..lt],
+        _ => segment.as_slice(),
+    };
+    return wildcard_match(first, segment) && glob_segments_match(pattern_rest, segments_rest);
+}
+
+// 形如 `myapp::net::*`, 按照 `::` 切分之后逐段匹配, `*` 不会跨越 `::`, 单独的 `**` 可以匹配任意多段.
+// 与 TypeSelector 一样, 仅当以 `::` 开头时才要求从 root namespace 开始匹配.
+#[derive(Clone, Debug)]
+struct GlobPattern {
+    rooted: bool,
+    segments: Vec<Vec<char>>,
+}
+
+impl GlobPattern {
+    fn matches(&self, tyname: &TypeName) -> bool {
+        let Some(name) = tyname.name else {
+            return false;
+        };
+        let mut segments = vec![strip_ws(name).chars().collect::<Vec<_>>()];
+        let mut ns_opt = tyname.namespace;
+        while let Some(ns) = ns_opt {
+            let Some(ns_name) = ns.name() else {
+                return false;
+            };
+            segments.push(strip_ws(ns_name).chars().collect());
+            ns_opt = ns.parent();
+        }
+        segments.reverse();
+        if self.rooted {
+            return glob_segments_match(&self.segments, &segments);
+        }
+        return (0..=segments.len()).any(|v| glob_segments_match(&self.segments, &segments[v..]));
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Name(TypeSelector),
+    Glob(GlobPattern),
+    // 匹配不带前导 `::` 的全名, 如 'myapp::net::Request'.
+    Regex(regex::Regex),
+}
+
+// 命令行中指定的类型选择器, `!` 开头表示排除.
+#[derive(Clone, Debug)]
+pub(crate) struct Selector {
+    pub(crate) exclude: bool,
+    pattern: Pattern,
+}
+
+// 顶层的 `*`, `?` 总是通配符. 在 template 实参中 `*` 也可能是指针, 比如 `Foo<int*>`,
+// 所以仅当其前面不是类型时才认为是通配符, 比如 `Foo<*>`, `Foo<int, *>`, `Foo<std::*>`.
+fn is_glob(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut prev = None;
+    for ch in input.chars() {
+        match ch {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            '?' => return true,
+            '*' if depth == 0 || matches!(prev, Some('<' | ',' | ':')) => return true,
+            _ => {}
+        }
+        if !ch.is_whitespace() {
+            prev = Some(ch);
+        }
+    }
+    return false;
+}
+
+// 'myapp::net::*', 're:^myapp::.*Request$', '!myapp::detail::*', 'ns::Foo<int, 3>'.
+pub(crate) fn parse_selector(input: &str) -> anyhow::Result<Selector> {
+    let input = input.trim();
+    let (exclude, input) = match input.strip_prefix('!') {
+        Some(v) => (true, v.trim_start()),
+        None => (false, input),
+    };
+    let pattern = if let Some(re) = input.strip_prefix("re:") {
+        Pattern::Regex(regex::Regex::new(re)?)
+    } else if is_glob(input) {
+        let (rooted, glob) = match input.strip_prefix("::") {
+            Some(v) => (true, v),
+            None => (false, input),
+        };
+        let segments: Vec<Vec<char>> = split_top_level(glob, "::")
+            .into_iter()
+            .map(|v| strip_ws(v).chars().collect())
+            .collect();
+        ensure!(
+            segments.iter().all(|v| !v.is_empty()),
+            "empty namespace. input={}",
+            input
+        );
+        Pattern::Glob(GlobPattern { rooted, segments })
+    } else {
+        Pattern::Name(parse_type_selector(input)?)
+    };
+    return Ok(Selector { exclude, pattern });
+}
+
+impl Selector {
+    // --lazy 时只能拿到不带 namespace 的类型名, 这里仅当一定不会匹配时才返回 false.
+    pub(crate) fn may_match_name(&self, name: &str) -> bool {
+        match &self.pattern {
+            Pattern::Name(v) => {
+                let (base, _) = split_template_args(name).unwrap_or((name, None));
+                return strip_ws(base) == v.name;
+            }
+            Pattern::Glob(v) => {
+                let Some(last) = v.segments.last() else {
+                    return true;
+                };
+                if last.as_slice() == ['*', '*'] {
+                    return true;
+                }
+                let segment: Vec<char> = strip_ws(name).chars().collect();
+                return glob_segments_match(&v.segments[v.segments.len() - 1..], &[segment]);
+            }
+            Pattern::Regex(_) => return true,
+        }
+    }
+
+    pub(crate) fn matches(&self, tyname: &TypeName, ty: &Type, hash: &FileHash) -> bool {
+        match &self.pattern {
+            Pattern::Name(v) => v.matches(tyname, ty, hash),
+            Pattern::Glob(v) => v.matches(tyname),
+            Pattern::Regex(v) => {
+                if tyname.is_anon() {
+                    return false;
+                }
+                let name = tyname.to_string();
+                return v.is_match(name.strip_prefix("::").unwrap_or(&name));
+            }
+        }
+    }
+}
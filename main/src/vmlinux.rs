@@ -0,0 +1,106 @@
+use std::io::{self, Write};
+
+use crate::natural::NaturalLayout;
+use crate::{ArrayDef, Backend, EnumDef, FuncPtrDef, Record, TypeRef, TypedefDef};
+
+// 与 `bpftool btf dump format c` 生成的 vmlinux.h 类似, 供 BPF CO-RE 程序使用.
+// 与 CPrinter 不同, 这里使用 natural layout, 详见 NaturalLayout. layout 校验由 CPrinter 负责, 这里不需要.
+pub(crate) struct VmlinuxPrinter {
+    file: std::fs::File,
+    layout: NaturalLayout,
+}
+
+impl VmlinuxPrinter {
+    pub(crate) fn try_open(path: &str) -> io::Result<VmlinuxPrinter> {
+        let file = std::fs::File::create(format!("{}.vmlinux.h", path))?;
+        Ok(VmlinuxPrinter {
+            file,
+            layout: NaturalLayout::new(),
+        })
+    }
+
+    fn write_def(&mut self, ident: Option<&str>, lines: &[String]) -> io::Result<()> {
+        if let Some(ident) = ident {
+            writeln!(self.file, "#ifndef __CLAYOUT_DEF_{}", ident)?;
+            writeln!(self.file, "#define __CLAYOUT_DEF_{}", ident)?;
+        }
+        for line in lines {
+            writeln!(self.file, "{}", line)?;
+        }
+        if ident.is_some() {
+            writeln!(self.file, "#endif")?;
+        }
+        writeln!(self.file)
+    }
+}
+
+impl Backend for VmlinuxPrinter {
+    fn add_record(&mut self, record: &Record) -> io::Result<()> {
+        self.layout.add_record(record);
+        Ok(())
+    }
+
+    fn add_array(&mut self, array: &ArrayDef) -> io::Result<()> {
+        self.layout.add_array(array);
+        Ok(())
+    }
+
+    fn add_enum(&mut self, enum_def: &EnumDef) -> io::Result<()> {
+        self.layout.add_enum(enum_def);
+        Ok(())
+    }
+
+    fn add_typedef(&mut self, typedef: &TypedefDef) -> io::Result<()> {
+        self.layout.add_typedef(typedef);
+        Ok(())
+    }
+
+    fn add_func_pointer(&mut self, func: &FuncPtrDef) -> io::Result<()> {
+        self.layout.add_func_pointer(func);
+        Ok(())
+    }
+
+    fn add_forward_decl(&mut self, ty: &TypeRef) -> io::Result<()> {
+        self.layout.add_forward_decl(ty);
+        Ok(())
+    }
+
+    fn add_size_check(&mut self, _ty: &TypeRef, _size: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let outputs = self.layout.finish();
+
+        writeln!(self.file, "// Generated by hidva/clayout! 大吉大利!")?;
+        writeln!(self.file, "#ifndef __CLAYOUT_VMLINUX_H__")?;
+        writeln!(self.file, "#define __CLAYOUT_VMLINUX_H__")?;
+        writeln!(self.file)?;
+        // 与 linux/types.h 中的定义一致, 重复定义是允许的.
+        writeln!(self.file, "typedef unsigned char __u8;")?;
+        writeln!(self.file, "typedef unsigned short __u16;")?;
+        writeln!(self.file, "typedef unsigned int __u32;")?;
+        writeln!(self.file, "typedef unsigned long long __u64;")?;
+        writeln!(self.file, "typedef signed char __s8;")?;
+        writeln!(self.file, "typedef short __s16;")?;
+        writeln!(self.file, "typedef int __s32;")?;
+        writeln!(self.file, "typedef long long __s64;")?;
+        writeln!(self.file)?;
+        writeln!(self.file, "#ifndef BPF_NO_PRESERVE_ACCESS_INDEX")?;
+        writeln!(
+            self.file,
+            "#pragma clang attribute push (__attribute__((preserve_access_index)), apply_to = record)"
+        )?;
+        writeln!(self.file, "#endif")?;
+        writeln!(self.file)?;
+        for def in &outputs {
+            self.write_def(def.ident.as_deref(), &def.lines)?;
+        }
+        writeln!(self.file, "#ifndef BPF_NO_PRESERVE_ACCESS_INDEX")?;
+        writeln!(self.file, "#pragma clang attribute pop")?;
+        writeln!(self.file, "#endif")?;
+        writeln!(self.file)?;
+        writeln!(self.file, "#endif /* __CLAYOUT_VMLINUX_H__ */")?;
+        self.file.flush()
+    }
+}
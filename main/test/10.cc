@@ -0,0 +1,67 @@
+#include <stddef.h>
+#include <stdio.h>
+
+namespace app {
+
+struct C {
+  short s;
+  int c;
+};
+
+struct B {
+  char tag;
+  C *next;
+  C inl[2];
+};
+
+struct Base1 {
+  virtual ~Base1() {}
+  long b1;
+};
+
+struct Base2 {
+  int b2;
+  unsigned flag : 3;
+  unsigned mode : 5;
+};
+
+typedef B BArr[4];
+
+struct A {
+  int x;
+  union {
+    struct {
+      char lo;
+      char hi;
+    };
+    short both;
+  };
+  BArr b;
+  const B *pb;
+};
+
+struct S : Base1, Base2 {
+  char pad;
+  A a;
+  A &ref;
+  S(A &r) : ref(r) {}
+};
+
+}  // namespace app
+
+int main() {
+  app::A a;
+  app::S s(a);
+  printf("S::a=%zu\n", offsetof(app::S, a));
+  printf("S::a.b[3]=%zu\n", offsetof(app::S, a.b[3]));
+  printf("S::a.b[3].next=%zu\n", offsetof(app::S, a.b[3].next));
+  printf("C::c=%zu\n", offsetof(app::C, c));
+  printf("S::a.hi=%zu\n", offsetof(app::S, a.hi));
+  printf("S::a.both=%zu\n", offsetof(app::S, a.both));
+  printf("S::b1=%zu\n", (size_t)((char *)&s.b1 - (char *)&s));
+  printf("S::b2=%zu\n", (size_t)((char *)&s.b2 - (char *)&s));
+  printf("S::pad=%zu\n", (size_t)((char *)&s.pad - (char *)&s));
+  printf("A::pb=%zu\n", offsetof(app::A, pb));
+  printf("B::inl[1].c=%zu\n", offsetof(app::B, inl[1].c));
+  return 0;
+}
@@ -0,0 +1,54 @@
+#include <stdio.h>
+
+namespace app {
+
+// 两个 eightbyte: INTEGER, SSE.
+struct Small {
+  int id;
+  double ratio;
+};
+
+// 超过 16 字节, 复制到栈上.
+struct Big {
+  long a[3];
+};
+
+// 用户定义了拷贝构造函数, 通过指针传递.
+struct Handle {
+  Handle(int f) : fd(f) {}
+  Handle(const Handle &other) : fd(other.fd) {}
+  int fd;
+};
+
+struct Poly {
+  virtual ~Poly() {}
+  int v;
+};
+
+struct Service {
+  long counter;
+
+  __attribute__((noinline)) Small stats(Small s, double scale, Big big) {
+    counter += big.a[0];
+    return Small{s.id, s.ratio * scale};
+  }
+  __attribute__((noinline)) Big snapshot(Handle h, Poly p) {
+    return Big{{counter, h.fd, p.v}};
+  }
+  __attribute__((noinline)) static long sum(long a, long b, long c, long d, long e, long f, long g,
+                                            Small s) {
+    return a + b + c + d + e + f + g + s.id;
+  }
+};
+
+}  // namespace app
+
+int main() {
+  app::Service svc{0};
+  app::Big big{{1, 2, 3}};
+  app::Small s = svc.stats(app::Small{1, 0.5}, 2.0, big);
+  app::Big b = svc.snapshot(app::Handle(3), app::Poly());
+  long total = app::Service::sum(1, 2, 3, 4, 5, 6, 7, s);
+  printf("%d %ld %ld\n", s.id, b.a[1], total);
+  return 0;
+}
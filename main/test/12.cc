@@ -0,0 +1,46 @@
+// vmlinux.h: A1218 的 tail padding 被 S1218::d 复用, 只有 A1218 需要 packed.
+struct A1218 {
+  A1218() {}
+  long l;
+  char c;
+};
+
+struct S1218 : A1218 {
+  char d;
+};
+
+struct Outer {
+  int x;
+  S1218 s;
+  short y;
+};
+
+struct Bits {
+  unsigned a : 3;
+  unsigned b : 7;
+  char c;
+  long tail;
+};
+
+union U {
+  Bits b;
+  S1218 s;
+  double d;
+};
+
+struct W {
+  char c;
+  U u;
+  A1218 arr[2];
+};
+
+W *gw;
+Outer go;
+
+int main() {
+  W &w = *gw;
+  Outer &o = go;
+  (void)w;
+  (void)o;
+  return 0;
+}
@@ -0,0 +1,26 @@
+#include <stdint.h>
+
+// enum: 底层类型决定符号, 超出 int 范围的值使用 #define.
+namespace net {
+enum class State : uint8_t { IDLE = 0, BUSY = 200 };
+}
+
+namespace disk {
+enum class State : int16_t { IDLE = -1, BUSY = 1 };
+}
+
+enum Big : uint64_t { SMALL = 1, HUGE = 0xfffffffffffffff0ULL };
+enum Neg : int64_t { MIN = INT64_MIN, MINUS = -5000000000LL, ZERO = 0 };
+enum Plain { A, B = 3 };
+
+struct Device {
+  net::State net_state;
+  disk::State disk_state;
+  Big big;
+  Neg neg;
+  Plain plain;
+};
+
+Device g_dev{net::State::BUSY, disk::State::IDLE, HUGE, MIN, B};
+
+int main() { return (int)g_dev.plain; }
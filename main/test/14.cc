@@ -0,0 +1,55 @@
+// --natural: 各个类型的 sizeof/_Alignof 与原类型一致, 只有 A1418 与 Packed 需要 packed.
+#include <stdint.h>
+
+struct alignas(16) Vec4 {
+  float v[4];
+};
+
+struct Aligned {
+  char c;
+  alignas(64) int counter;
+  Vec4 vec;
+};
+
+struct Bits {
+  uint8_t flag;
+  uint32_t a : 3;
+  uint64_t b : 40;
+};
+
+struct __attribute__((packed)) Packed {
+  char c;
+  int i;
+};
+
+struct A1418 {
+  A1418() {}
+  long l;
+  char c;
+};
+
+struct S1418 : A1418 {
+  char d;
+};
+
+union __attribute__((aligned(32))) U {
+  Bits b;
+  double d;
+};
+
+struct All {
+  Aligned a;
+  Bits bits[2];
+  Packed p;
+  S1418 s;
+  U u;
+  long double ld;
+};
+
+All *gall;
+
+int main() {
+  All &a = *gall;
+  (void)a;
+  return 0;
+}
@@ -0,0 +1,42 @@
+// 递归引用自身的类型: 指针成员应指向前向声明的类型, 而不是 void*.
+#include <stdint.h>
+
+struct Node {
+  int val;
+  Node *next;
+  const Node *prev;
+  Node *children[2];
+};
+
+struct B;
+struct A {
+  B *b;
+  int x;
+};
+// 从 A 开始处理时, B 只有前向声明, 在 A 之后定义, 否则 A 还未完成, inner 的类型未知.
+// CHECK:   struct A inner;
+struct B {
+  A *a;
+  A inner;
+};
+
+typedef struct List List_t;
+struct List {
+  List_t *next;
+  int len;
+};
+
+struct Holder {
+  List_t list;
+  union U {
+    U *self;
+    long l;
+  } u;
+};
+
+int main() {
+  Node n{};
+  B b{};
+  Holder h{};
+  return n.val + b.inner.x + h.list.len;
+}
@@ -0,0 +1,40 @@
+// --typedefs: 保留 typedef 与 const/volatile, layout 与不加 --typedefs 时一致.
+#include <stdint.h>
+
+typedef uint64_t LSN;
+typedef LSN LSNArray[3];
+
+enum Color { RED, GREEN };
+typedef enum Color Color_t;
+
+struct Inner {
+  int x;
+};
+typedef struct Inner Inner_t;
+
+typedef struct Chain Chain_t;
+struct Chain {
+  Chain_t *next;
+  const Chain_t *prev;
+};
+
+struct Page {
+  LSN lsn;
+  const LSN start;
+  volatile uint32_t flags;
+  const volatile int16_t cv;
+  uint32_t a : 3;
+  const uint32_t b : 5;
+  const char *name;
+  char *const fixed;
+  const Inner_t *const inner_ptr;
+  Inner_t inner;
+  LSNArray history;
+  const Color_t color;
+  Chain_t chain;
+};
+
+int main() {
+  Page p{1, 2, 3, 4, 1, 2, "n", nullptr, nullptr, {}, {}, RED, {}};
+  return (int)p.lsn;
+}
@@ -0,0 +1,39 @@
+// 函数指针输出为带有真实签名的 typedef, 成员指针按照 Itanium C++ ABI 输出.
+#include <stdint.h>
+
+struct Node;
+typedef int (*compare_t)(const void *, const void *);
+typedef void handler_t(int);
+
+struct Ops {
+  void (*init)(void);
+  int (*log)(const char *fmt, ...);
+  compare_t cmp;
+  handler_t *on_signal;
+  Node *(*next)(Node *, int64_t);
+  void (*(*factory)(int))(double);
+};
+
+struct Node {
+  int val;
+  Node *(*visit)(Node *self);
+};
+
+struct Base {
+  int a;
+  virtual void f() {}
+  void g() {}
+};
+
+struct Holder {
+  int Base::*data;
+  void (Base::*method)();
+  char c;
+  Ops ops;
+  Node node;
+};
+
+int main() {
+  Holder h{&Base::a, &Base::g, 'c', {}, {}};
+  return h.c;
+}
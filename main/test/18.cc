@@ -0,0 +1,29 @@
+// 多态类的 vptr 输出为指向 `{Class}_VTable` 的指针, 每个虚函数对应一个函数指针成员.
+// 子类与其 primary base 共用 vptr, 子类中的 primary base 输出为 `{Class}_{Base}`, 其 vptr 指向子类的 `{Class}_VTable`.
+// gcc 不会记录虚析构函数的 slot, 其位于 area 之前的两个 slot.
+// CHECK:   Multi_dtor_fn __dtor_complete;
+// CHECK:   Multi_dtor_fn __dtor_deleting;
+// CHECK:   Derived_extra_fn extra;
+struct Base {
+  virtual ~Base() {}
+  virtual int area(int scale) const { return scale; }
+  virtual void draw() = 0;
+  int id;
+};
+struct Derived : Base {
+  int area(int scale) const override { return scale * 2; }
+  void draw() override {}
+  virtual long extra(long a, ...) { return a; }
+  long payload;
+};
+struct Other {
+  virtual void ping() {}
+  char c;
+};
+struct Multi : Derived, Other {
+  void ping() override {}
+  virtual void own() {}
+  short s;
+};
+Multi m;
+int main() { return 0; }
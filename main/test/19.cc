@@ -0,0 +1,29 @@
+// 虚基类按 complete object 的 layout 输出为 `__vbase{N}`, 基类中用 `{Class}_NonVirtual` 表示不含虚基类的部分.
+// 只因为虚基类而含有 vptr 的类型, vptr 仍然指向 `{Class}_VTable`, vbase offset 位于 `{Class}_VTablePrefix`.
+// CHECK:   struct X_VTable* __vptr;
+// CHECK:   long int V1_offset;
+// CHECK:   struct V1_VTable* __vptr;
+// CHECK:   long int V0_offset;
+struct V0 {
+  long a;
+};
+struct V1 : virtual V0 {
+  int b;
+};
+struct V2 {
+  short c;
+};
+struct X : virtual V1, virtual V2 {
+  char x;
+};
+struct Y : V1, virtual V2 {
+  char y;
+};
+struct P : virtual V0 {
+  virtual void f() {}
+  int p;
+};
+X x;
+Y y;
+P p;
+int main() { return 0; }
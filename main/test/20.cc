@@ -0,0 +1,35 @@
+// split DWARF: 类型定义在 .dwo/.dwp 中, 以下方式编译时输出的 layout 均与 `g++ -g 20.cc -o t20` 一致.
+//   g++ -g -gsplit-dwarf 20.cc -o t20
+//   g++ -g -gdwarf-4 -gsplit-dwarf 20.cc -o t20
+//   g++ -g -gsplit-dwarf 20.cc -o t20 && llvm-dwp -e t20 -o t20.dwp && rm t20-20.dwo
+//   g++ -g -gdwarf-4 -gsplit-dwarf 20.cc -o t20 && dwp -e t20 -o t20.dwp && rm t20-20.dwo
+// 删除 .dwo 与 .dwp 之后, clayout 会在 stderr 中提示缺少 split unit.
+#include <stdint.h>
+
+namespace split {
+
+enum class Kind : uint16_t { NONE = 0, LEAF = 1, INNER = 0xffff };
+
+struct Header {
+  Kind kind;
+  uint8_t level : 3;
+  uint8_t dirty : 1;
+  uint32_t count;
+};
+
+union Slot {
+  uint64_t raw;
+  struct Header *child;
+};
+
+template <typename T, int N>
+struct Page {
+  struct Header header;
+  T slots[N];
+};
+
+}  // namespace split
+
+split::Page<split::Slot, 4> page;
+
+int main() { return page.header.count; }
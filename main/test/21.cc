@@ -0,0 +1,46 @@
+// type units: 两个 CU 共用 .debug_types 中的同一个类型, 通过 DW_FORM_ref_sig8 引用.
+//   g++ -g -gdwarf-4 -fdebug-types-section -c 21.cc -o t21-1.o
+//   g++ -g -gdwarf-4 -fdebug-types-section -DSECOND_UNIT -c 21.cc -o t21-2.o
+//   g++ t21-1.o t21-2.o -o t21
+// 所有类型都位于 .debug_types 中, 所以 `--dry-run 'tu::*' Top Bottom` 按照 type unit 的顺序列出 Top, tu::Shape,
+// tu::Point, Bottom, 头文件中的顺序为 Point, Shape, Top, Bottom. 不加 -fdebug-types-section 时每个 CU 各有一份
+// tu::Point 与 tu::Shape, 按照 CU 中的定义顺序列出, 头文件中为 Point, Shape, Top, tu_Point, tu_Shape, Bottom.
+#include <stdint.h>
+
+namespace tu {
+
+struct Point {
+  int32_t x;
+  int32_t y;
+};
+
+struct Shape {
+  struct Point origin;
+  uint16_t kind;
+  struct Shape *next;
+};
+
+}  // namespace tu
+
+#ifndef SECOND_UNIT
+
+struct Top {
+  tu::Shape shape;
+  char tag;
+};
+
+Top top;
+int second();
+int main() { return top.tag + second(); }
+
+#else
+
+struct Bottom {
+  tu::Point corner;
+  tu::Shape *shape;
+};
+
+Bottom bottom;
+int second() { return bottom.corner.x; }
+
+#endif
@@ -0,0 +1,31 @@
+#include <stdint.h>
+
+enum Color { RED, GREEN, BLUE };
+
+struct BF {
+    unsigned a : 3;
+    int b : 5;        // signed, 跨越了 byte 边界.
+    char ch;
+    uint32_t c : 12;  // typedef
+    Color color : 2;  // enum
+    unsigned char flag : 1;
+    long l : 32;      // bit_size 是 BYTE 的整数倍.
+    unsigned long long big : 40;
+    short s;
+};
+
+union UBF {
+    unsigned x : 3;
+    int i;
+};
+
+struct BF2 : public BF {
+    unsigned d : 1;
+};
+
+int main() {
+    BF bf;
+    UBF u;
+    BF2 bf2;
+    return 0;
+}
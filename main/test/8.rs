@@ -0,0 +1,40 @@
+// rustc -g 8.rs && clayout -i 8 -o output Holder
+#![allow(dead_code)]
+pub enum Shape {
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+    Empty,
+}
+// niche 优化, discriminant 与 A.0 重叠.
+pub enum Niche {
+    A(bool),
+    B,
+    C,
+}
+pub enum Big {
+    X(u8, u64),
+    Y(u16),
+}
+// 没有 discriminant.
+pub enum One {
+    A(u32, u8),
+}
+pub struct Holder {
+    pub s: Shape,
+    pub n: Niche,
+    pub o: Option<Box<u32>>,
+    pub b: Big,
+    pub one: One,
+    pub nested: Option<Niche>,
+}
+fn main() {
+    let h = Holder {
+        s: Shape::Empty,
+        n: Niche::B,
+        o: None,
+        b: Big::Y(3),
+        one: One::A(1, 2),
+        nested: None,
+    };
+    std::hint::black_box(&h);
+}
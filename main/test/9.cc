@@ -0,0 +1,50 @@
+// 省略的末尾实参只匹配使用了默认值的形参, 所以 `Foo<int>` 不会选中 `ns::Foo<int, 3>`, `ns::U<ns::Green, true>` 选中 u1.
+#include <stdio.h>
+
+namespace ns {
+
+enum Color { Red, Green = 7 };
+
+template <typename T, int N>
+struct Foo {
+  T vals[N > 0 ? N : 1];
+  int n;
+};
+
+template <typename T, typename... Ts>
+struct Tuple {
+  T first;
+  char rest[sizeof...(Ts)];
+};
+
+template <Color C, bool B, typename T = unsigned long>
+union U {
+  T t;
+  char c[C + B + 1];
+};
+
+struct Bar {
+  Foo<const char *, 2> a;
+};
+
+}  // namespace ns
+
+template <typename T>
+struct Foo {
+  T x;
+};
+
+ns::Foo<int, 3> f1;
+ns::Foo<unsigned long, -1> f2;
+ns::Foo<ns::Bar, 1> f3;
+ns::Tuple<int, long, short> t1;
+ns::Tuple<int> t2;
+ns::U<ns::Green, true> u1;
+ns::U<ns::Red, false, int *> u2;
+Foo<int> g1;
+
+int main() {
+  printf("%zu %zu %zu %zu %zu %zu %zu %zu\n", sizeof(f1), sizeof(f2), sizeof(f3), sizeof(t1), sizeof(t2),
+         sizeof(u1), sizeof(u2), sizeof(g1));
+  return 0;
+}
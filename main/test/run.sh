@@ -0,0 +1,151 @@
+#!/bin/bash
+# 编译 main/test 下的所有 fixture, 对其执行 clayout 并检查各个输出格式:
+#   c: 编译并运行 .c, 其中会检查各个类型的 size 与 offset. 同时检查 --typedefs 与 --natural.
+#   rust: 编译 .rs, layout 在编译时检查.
+#   python: 导入 .py, layout 在导入时检查.
+#   vmlinux: 编译 .vmlinux.h, 有 clang 时以 bpf 为目标, 否则使用 gcc.
+#   go: 有 go 时执行 go test.
+# fixture 中 `// CHECK: xxx` 意味着 .h 中必须包含 xxx 这一行.
+# 8.rs 中的基础类型为 u8/u64 等 Rust 类型名, 需要使用者自行提供 typedef, 所以不检查其 c 与 vmlinux 输出.
+# usage: main/test/run.sh [clayout]
+set -u
+cd "$(dirname "$0")"
+TEST_DIR=$PWD
+CLAYOUT=$(realpath "${1:-../../target/debug/clayout}")
+OUT=$(mktemp -d)
+trap 'rm -rf "$OUT"' EXIT
+FAILED=0
+
+fail() {
+  echo "FAIL $*"
+  FAILED=1
+}
+
+# fixture 以及要选择的类型.
+declare -A TYPES=(
+  [1]="zhanyi_struct"
+  [2]="S"
+  [3]="ZhanyiStruct2 ZhanyiUnion1 ZhanyiClass1"
+  [4]="S"
+  [5]="S2"
+  [6]="u"
+  [7]="BF UBF BF2"
+  [8]="Holder"
+  [9]="ns::Foo ns::Tuple ns::U ns::Bar ::Foo"
+  [10]="app::**"
+  [11]="app::**"
+  [12]="A1218 S1218 Outer Bits U W"
+  [13]="Device"
+  [14]="All"
+  [15]="A Node B Holder"
+  [16]="Page"
+  [17]="Holder Ops Node"
+  [18]="Multi"
+  [19]="X Y P"
+  [20]="split::Page<split::Slot, 4>"
+  [21]="Top Bottom"
+)
+
+build() {
+  local id=$1 bin=$OUT/$1/t$1
+  mkdir -p "$OUT/$id"
+  case $id in
+  8) rustc -g -o "$bin" "$TEST_DIR/8.rs" 2>/dev/null ;;
+  20) (cd "$OUT/$id" && g++ -g -gsplit-dwarf "$TEST_DIR/20.cc" -o t20) ;;
+  21)
+    g++ -g -gdwarf-4 -fdebug-types-section -c 21.cc -o "$bin-1.o" &&
+      g++ -g -gdwarf-4 -fdebug-types-section -DSECOND_UNIT -c 21.cc -o "$bin-2.o" &&
+      g++ "$bin-1.o" "$bin-2.o" -o "$bin"
+    ;;
+  *) g++ -g -w -o "$bin" "$TEST_DIR/$id.cc" ;;
+  esac
+}
+
+check_c() {
+  local out=$1
+  gcc -o "$out.chk" "$out.c" && "$out.chk" >/dev/null
+}
+
+check_rust() {
+  local out=$1
+  printf '#![no_std]\n#[path = "%s.rs"]\nmod m;\n' "$out" >"${out}_lib.rs"
+  rustc --edition 2021 --crate-type lib --crate-name layout -o "$out.rlib" "${out}_lib.rs" 2>&1 | head -20
+  return "${PIPESTATUS[0]}"
+}
+
+check_python() {
+  python3 "$1.py"
+}
+
+check_vmlinux() {
+  local out=$1
+  printf '#include "%s.vmlinux.h"\n' "$out" >"$out.bpf.c"
+  if command -v clang >/dev/null; then
+    clang -target bpf -O2 -c -o "$out.bpf.o" "$out.bpf.c"
+  else
+    gcc -fsyntax-only -Wno-attributes "$out.bpf.c"
+  fi
+}
+
+check_go() {
+  local out=$1
+  command -v go >/dev/null || return 0
+  (cd "$(dirname "$out")" && go test ./... >/dev/null)
+}
+
+for id in $(printf '%s\n' "${!TYPES[@]}" | sort -n); do
+  src=$TEST_DIR/$id.cc
+  [ "$id" = 8 ] && src=$TEST_DIR/8.rs
+  if ! build "$id"; then
+    fail "$id: build"
+    continue
+  fi
+  read -ra types <<<"${TYPES[$id]}"
+  [ "$id" = 20 ] && types=("${TYPES[$id]}")
+  bin=$OUT/$id/t$id
+
+  out=$OUT/$id/packed
+  if "$CLAYOUT" -i "$bin" -o "$out" -f c -f rust -f python -f json "${types[@]}"; then
+    [ "$id" = 8 ] || check_c "$out" || fail "$id: c"
+    check_rust "$out" || fail "$id: rust"
+    check_python "$out" || fail "$id: python"
+    python3 -m json.tool "$out.json" >/dev/null || fail "$id: json"
+    grep '// CHECK: ' "$src" | sed 's|.*// CHECK: ||' | while IFS= read -r line; do
+      grep -qxF "$line" "$out.h" || echo "$line"
+    done | grep -q . && fail "$id: CHECK"
+  else
+    fail "$id: clayout"
+  fi
+
+  out=$OUT/$id/typedefs
+  if "$CLAYOUT" -i "$bin" -o "$out" -f c -f rust -f python --typedefs "${types[@]}"; then
+    [ "$id" = 8 ] || check_c "$out" || fail "$id: c --typedefs"
+    check_rust "$out" || fail "$id: rust --typedefs"
+    check_python "$out" || fail "$id: python --typedefs"
+  else
+    fail "$id: clayout --typedefs"
+  fi
+
+  out=$OUT/$id/natural
+  if [ "$id" != 8 ]; then
+    if "$CLAYOUT" -i "$bin" -o "$out" -f c -f vmlinux --natural "${types[@]}"; then
+      check_c "$out" || fail "$id: c --natural"
+      check_vmlinux "$out" || fail "$id: vmlinux"
+    else
+      fail "$id: clayout --natural"
+    fi
+  fi
+
+  mkdir -p "$OUT/$id/go"
+  out=$OUT/$id/go/layout
+  if "$CLAYOUT" -i "$bin" -o "$out" -f go "${types[@]}"; then
+    check_go "$out" || fail "$id: go"
+  else
+    fail "$id: clayout -f go"
+  fi
+done
+
+if [ $FAILED = 0 ]; then
+  echo "all fixtures passed"
+fi
+exit $FAILED
@@ -1,17 +1,27 @@
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
 use std::mem;
+use std::ops::Deref;
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread;
 use std::u32;
 
 use gimli;
 use gimli::Reader as GimliReader;
+use gimli::Section as GimliSection;
 use object::{self, ObjectSection, ObjectSymbol};
 
+mod lazy;
+
 use crate::cfi::{Cfi, CfiDirective};
 use crate::file::{Architecture, Arena, DebugInfo, FileHash};
 use crate::function::{
     Function, FunctionDetails, FunctionOffset, InlinedFunction, Parameter, ParameterOffset,
+    Virtuality,
 };
 use crate::location::{Location, Piece, Register};
 use crate::namespace::{Namespace, NamespaceKind};
@@ -20,8 +30,8 @@ use crate::source::Source;
 use crate::types::{
     ArrayType, BaseType, BaseTypeEncoding, Endianity, EnumerationType, Enumerator, FunctionType,
     Inherit, Member, MemberOffset, ParameterType, PointerToMemberType, StructType, SubrangeType,
-    Type, TypeDef, TypeKind, TypeModifier, TypeModifierKind, TypeOffset, UnionType,
-    UnspecifiedType, Variant, VariantPart,
+    TemplateParameter, Type, TypeDef, TypeKind, TypeModifier, TypeModifierKind, TypeOffset,
+    UnionType, UnspecifiedType, Variant, VariantPart,
 };
 use crate::unit::Unit;
 use crate::variable::{LocalVariable, Variable, VariableOffset};
@@ -256,10 +266,24 @@ where
     Endian: gimli::Endianity,
 {
     endian: Endian,
-    read: gimli::Dwarf<Reader<'input, Endian>>,
+    read: Arc<gimli::Dwarf<Reader<'input, Endian>>>,
     frame: DwarfFrame<Reader<'input, Endian>>,
     arena: &'input Arena,
-    units: Vec<gimli::Unit<Reader<'input, Endian>, usize>>,
+    units: Vec<DwarfUnit<'input, Endian>>,
+    // The start offset of each unit in `units`, and its index.
+    unit_ranges: BTreeMap<usize, usize>,
+    // The sorted offsets of the units in the main and supplementary files,
+    // and in `.debug_types`.
+    unit_offsets: Vec<usize>,
+    // The headers of the units in `.debug_types`, whose offsets start at `types_base`.
+    type_units: Vec<gimli::UnitHeader<Reader<'input, Endian>, usize>>,
+    types_base: usize,
+    // The offsets of the partial units which have been imported.
+    imported: HashSet<usize>,
+    signatures: Arc<HashMap<u64, usize>>,
+    split: SplitDwarf<'input, Endian>,
+    // Only used if the units are parsed lazily.
+    lazy: Option<lazy::LazyUnits<'input, Endian>>,
 }
 
 impl<'input, Endian> DwarfDebugInfo<'input, Endian>
@@ -271,7 +295,8 @@ where
         dwarf_unit: &DwarfUnit<'input, Endian>,
         value: gimli::AttributeValue<Reader<'input, Endian>>,
     ) -> Option<&'input str> {
-        self.read
+        dwarf_unit
+            .read
             .attr_string(dwarf_unit, value)
             .map(|r| self.arena.add_string(r.slice()))
             .ok()
@@ -284,16 +309,81 @@ where
         &DwarfUnit<'input, Endian>,
         gimli::EntriesTree<Reader<'input, Endian>>,
     )> {
-        // FIXME: make this more efficient for large numbers of units
         // FIXME: cache lookups
-        let offset = gimli::UnitSectionOffset::DebugInfoOffset(offset);
-        for unit in &self.units {
-            if let Some(offset) = offset.to_unit_offset(unit) {
-                let tree = unit.entries_tree(Some(offset)).ok()?;
-                return Some((unit, tree));
+        let unit = self.parsed_unit(offset.0)?;
+        let offset = unit.unit_offset(gimli::UnitSectionOffset::DebugInfoOffset(offset))?;
+        let tree = unit.entries_tree(Some(offset)).ok()?;
+        Some((unit, tree))
+    }
+
+    /// Return the parsed unit which contains `offset`.
+    fn parsed_unit(&self, offset: usize) -> Option<&DwarfUnit<'input, Endian>> {
+        let (_, &index) = self.unit_ranges.range(..=offset).next_back()?;
+        let unit = &self.units[index];
+        unit.unit_offset(gimli::UnitSectionOffset::DebugInfoOffset(
+            gimli::DebugInfoOffset(offset),
+        ))?;
+        Some(unit)
+    }
+
+    fn push_unit(&mut self, dwarf_unit: DwarfUnit<'input, Endian>) {
+        let offset = match dwarf_unit.header.offset() {
+            gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+            gimli::UnitSectionOffset::DebugTypesOffset(offset) => offset.0,
+        };
+        self.unit_ranges
+            .insert(offset + dwarf_unit.base, self.units.len());
+        self.units.push(dwarf_unit);
+    }
+
+    /// Return the unit of the main or supplementary file, or of `.debug_types`,
+    /// which contains `offset`.
+    fn unit_at(
+        &self,
+        offset: gimli::UnitSectionOffset,
+    ) -> Result<Option<DwarfUnit<'input, Endian>>> {
+        let offset = match offset {
+            gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+            _ => return Ok(None),
+        };
+        let unit_offset = match self.unit_offsets.binary_search(&offset) {
+            Ok(i) => self.unit_offsets[i],
+            Err(0) => return Ok(None),
+            Err(i) => self.unit_offsets[i - 1],
+        };
+        if unit_offset >= self.types_base {
+            let offset = unit_offset - self.types_base;
+            let header = match self.type_units.binary_search_by_key(&offset, |header| {
+                header.offset().as_debug_types_offset().map_or(0, |o| o.0)
+            }) {
+                Ok(i) => self.type_units[i],
+                Err(_) => return Ok(None),
+            };
+            return Ok(Some(DwarfUnit {
+                unit: self.read.unit(header)?,
+                read: self.read.clone(),
+                base: self.types_base,
+                signatures: self.signatures.clone(),
+            }));
+        }
+        let sup_base = self.read.debug_info.reader().len();
+        let (read, base) = if unit_offset < sup_base {
+            (self.read.clone(), 0)
+        } else {
+            match &self.read.sup {
+                Some(sup) => (sup.clone(), sup_base),
+                None => return Ok(None),
             }
-        }
-        None
+        };
+        let header = read
+            .debug_info
+            .header_from_offset(gimli::DebugInfoOffset(unit_offset - base))?;
+        Ok(Some(DwarfUnit {
+            unit: read.unit(header)?,
+            read,
+            base,
+            signatures: self.signatures.clone(),
+        }))
     }
 
     fn type_tree(
@@ -347,6 +437,10 @@ where
         })
     }
 
+    pub(crate) fn missing_split_units(&self) -> &[u64] {
+        &self.split.missing
+    }
+
     pub(crate) fn get_cfi(&self, range: Range) -> Vec<Cfi> {
         self.frame.get_cfi(range).unwrap_or_default()
     }
@@ -366,7 +460,96 @@ where
     }
 }
 
-type DwarfUnit<'input, Endian> = gimli::Unit<Reader<'input, Endian>>;
+/// A unit together with the sections it is read from.
+///
+/// The sections of a split unit are in a `.dwo` or `.dwp` file, and its
+/// `.debug_info` offsets overlap with those of the other files. `base` is
+/// added to these offsets so that they are unique within the `File`.
+/// Similarly for the `.debug_types` offsets of a type unit.
+struct DwarfUnit<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    unit: gimli::Unit<Reader<'input, Endian>, usize>,
+    read: Arc<gimli::Dwarf<Reader<'input, Endian>>>,
+    base: usize,
+    // The offsets of the types defined by type units, indexed by signature.
+    signatures: Arc<HashMap<u64, usize>>,
+}
+
+impl<'input, Endian> Deref for DwarfUnit<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    type Target = gimli::Unit<Reader<'input, Endian>, usize>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.unit
+    }
+}
+
+impl<'input, Endian> DwarfUnit<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    fn section_offset(&self, offset: gimli::UnitOffset) -> gimli::UnitSectionOffset {
+        let offset = match offset.to_unit_section_offset(&self.unit) {
+            gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+            gimli::UnitSectionOffset::DebugTypesOffset(offset) => offset.0,
+        };
+        gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(offset + self.base))
+    }
+
+    /// The base of the `.debug_info` offsets referred to by this unit.
+    ///
+    /// The units in the `.debug_types` of the main file refer to its `.debug_info`.
+    fn info_base(&self) -> usize {
+        match self.header.offset() {
+            gimli::UnitSectionOffset::DebugInfoOffset(_) => self.base,
+            gimli::UnitSectionOffset::DebugTypesOffset(_) => 0,
+        }
+    }
+
+    fn info_offset(&self, offset: gimli::DebugInfoOffset) -> gimli::UnitSectionOffset {
+        gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(
+            offset.0 + self.info_base(),
+        ))
+    }
+
+    /// Convert an offset in the supplementary file, whose units follow those of
+    /// the main file.
+    fn sup_offset(&self, offset: gimli::DebugInfoOffset) -> gimli::UnitSectionOffset {
+        let base = self.info_base() + self.read.debug_info.reader().len();
+        gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(offset.0 + base))
+    }
+
+    /// Return the offset of the type with the given signature.
+    fn signature_offset(
+        &self,
+        signature: gimli::DebugTypeSignature,
+    ) -> Option<gimli::UnitSectionOffset> {
+        let offset = self.signatures.get(&signature.0)?;
+        Some(gimli::UnitSectionOffset::DebugInfoOffset(
+            gimli::DebugInfoOffset(*offset),
+        ))
+    }
+
+    fn unit_offset(&self, offset: gimli::UnitSectionOffset) -> Option<gimli::UnitOffset> {
+        let offset = match offset {
+            gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0.checked_sub(self.base)?,
+            gimli::UnitSectionOffset::DebugTypesOffset(_) => return None,
+        };
+        let offset = match self.header.offset() {
+            gimli::UnitSectionOffset::DebugInfoOffset(_) => {
+                gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(offset))
+            }
+            gimli::UnitSectionOffset::DebugTypesOffset(_) => {
+                gimli::UnitSectionOffset::DebugTypesOffset(gimli::DebugTypesOffset(offset))
+            }
+        };
+        offset.to_unit_offset(&self.unit)
+    }
+}
 
 struct DwarfSubprogram<'input> {
     offset: gimli::UnitOffset,
@@ -384,33 +567,95 @@ struct DwarfVariable<'input> {
 pub(crate) fn parse<'input: 'file, 'file, Endian, Object>(
     endian: Endian,
     object: &'file Object,
+    sup_object: Option<&'file Object>,
+    path: &str,
     arena: &'input Arena,
 ) -> Result<(Vec<Unit<'input>>, DebugInfo<'input, Endian>)>
 where
-    Endian: gimli::Endianity,
+    Endian: gimli::Endianity + Send + Sync,
     Object: object::Object<'input, 'file>,
 {
-    let get_section = |id: gimli::SectionId| -> Result<_> {
-        let mut relocations = RelocationMap::default();
-        let data = match object.section_by_name(id.name()) {
-            Some(ref section) => {
-                add_relocations(&mut relocations, object, section);
-                match section.uncompressed_data()? {
-                    Cow::Borrowed(bytes) => bytes,
-                    Cow::Owned(bytes) => arena.add_buffer(bytes),
-                }
-            }
-            None => &[],
+    let mut dwarf = load(endian, object, sup_object, path, arena)?;
+    let read = dwarf.read.clone();
+    let mut dwarf_units = Vec::new();
+    let mut partial_units = Vec::new();
+    let mut unit_headers = read.units();
+    while let Some(unit_header) = unit_headers.next()? {
+        let dwarf_unit = DwarfUnit {
+            unit: read.unit(unit_header)?,
+            read: read.clone(),
+            base: 0,
+            signatures: dwarf.signatures.clone(),
         };
-        let relocations = arena.add_relocations(Box::new(relocations));
-        let reader = gimli::EndianSlice::new(data, endian);
-        Ok(Relocate {
-            relocations,
-            section: reader,
-            reader,
-        })
-    };
-    let read = gimli::Dwarf::load(get_section)?;
+        // Partial units are parsed as part of the units importing them.
+        let (tag, offset) = parse_unit_root(&dwarf_unit)?;
+        if tag == gimli::DW_TAG_partial_unit {
+            partial_units.push((offset, dwarf_unit));
+            continue;
+        }
+        let (dwarf_unit, type_units) = dwarf.split.split_unit(dwarf_unit)?;
+        dwarf_units.push(dwarf_unit);
+        dwarf_units.extend(type_units);
+    }
+    let mut units = parse_units(&mut dwarf, dwarf_units)?;
+    // These may import each other, so they are parsed one by one.
+    for (offset, dwarf_unit) in partial_units {
+        if !dwarf.imported.contains(&offset) {
+            units.push(parse_unit(&mut dwarf, dwarf_unit)?);
+        }
+    }
+    let mut dwarf_units = Vec::new();
+    for unit_header in dwarf.type_units.clone() {
+        dwarf_units.push(DwarfUnit {
+            unit: read.unit(unit_header)?,
+            read: read.clone(),
+            base: dwarf.types_base,
+            signatures: dwarf.signatures.clone(),
+        });
+    }
+    units.extend(parse_units(&mut dwarf, dwarf_units)?);
+    Ok((units, DebugInfo::Dwarf(dwarf)))
+}
+
+/// Like `parse`, but only parse the units which define the types whose names
+/// are accepted by `filter`, and the units these types depend on.
+pub(crate) fn parse_lazy<'input: 'file, 'file, Endian, Object>(
+    endian: Endian,
+    object: &'file Object,
+    sup_object: Option<&'file Object>,
+    path: &str,
+    arena: &'input Arena,
+    filter: &mut dyn FnMut(&str) -> bool,
+) -> Result<(Vec<Unit<'input>>, DebugInfo<'input, Endian>)>
+where
+    Endian: gimli::Endianity + Send + Sync,
+    Object: object::Object<'input, 'file>,
+{
+    let mut dwarf = load(endian, object, sup_object, path, arena)?;
+    let index = lazy::NameIndex::new(&mut dwarf, object)?;
+    dwarf.lazy = Some(lazy::LazyUnits::new(index));
+    let units = dwarf.load_names(filter)?;
+    Ok((units, DebugInfo::Dwarf(dwarf)))
+}
+
+/// Load the sections and the unit headers, without parsing any unit.
+fn load<'input: 'file, 'file, Endian, Object>(
+    endian: Endian,
+    object: &'file Object,
+    sup_object: Option<&'file Object>,
+    path: &str,
+    arena: &'input Arena,
+) -> Result<DwarfDebugInfo<'input, Endian>>
+where
+    Endian: gimli::Endianity,
+    Object: object::Object<'input, 'file>,
+{
+    let get_section = |id: gimli::SectionId| load_section(endian, object, Some(id.name()), arena);
+    let mut read = gimli::Dwarf::load(get_section)?;
+    if let Some(sup_object) = sup_object {
+        read.load_sup(|id| load_section(endian, sup_object, Some(id.name()), arena))?;
+    }
+    let read = Arc::new(read);
 
     let debug_frame = get_section(gimli::SectionId::DebugFrame)?;
     let eh_frame = get_section(gimli::SectionId::EhFrame)?;
@@ -426,27 +671,494 @@ where
     }
     let frame = DwarfFrame::new(debug_frame.into(), eh_frame.into(), bases);
 
-    let mut dwarf = DwarfDebugInfo {
+    // The units of the supplementary file follow those of the main file,
+    // then the units of `.debug_types`, and split units follow all of them.
+    let mut unit_offsets = Vec::new();
+    let mut signatures = HashMap::new();
+    let mut base = 0;
+    for read in Some(&*read).into_iter().chain(read.sup()) {
+        let mut unit_headers = read.units();
+        while let Some(unit_header) = unit_headers.next()? {
+            if let gimli::UnitSectionOffset::DebugInfoOffset(offset) = unit_header.offset() {
+                unit_offsets.push(offset.0 + base);
+                add_signature(&mut signatures, &unit_header, offset.0 + base);
+            }
+        }
+        base += read.debug_info.reader().len();
+    }
+    let types_base = base;
+    let mut type_units = Vec::new();
+    let mut unit_headers = read.type_units();
+    while let Some(unit_header) = unit_headers.next()? {
+        if let gimli::UnitSectionOffset::DebugTypesOffset(offset) = unit_header.offset() {
+            unit_offsets.push(offset.0 + base);
+            add_signature(&mut signatures, &unit_header, offset.0 + base);
+            type_units.push(unit_header);
+        }
+    }
+    base += read.debug_types.reader().len();
+    let signatures = Arc::new(signatures);
+
+    Ok(DwarfDebugInfo {
         endian,
         read,
         frame,
         arena,
         units: Vec::new(),
+        unit_ranges: BTreeMap::new(),
+        unit_offsets,
+        type_units,
+        types_base,
+        imported: HashSet::new(),
+        signatures,
+        split: SplitDwarf {
+            endian,
+            arena,
+            path: path.to_string(),
+            dwp: None,
+            base,
+            loaded: HashMap::new(),
+            missing: Vec::new(),
+        },
+        lazy: None,
+    })
+}
+
+/// Index the type defined by the type unit at `offset` by its signature.
+fn add_signature<'input, Endian>(
+    signatures: &mut HashMap<u64, usize>,
+    unit_header: &gimli::UnitHeader<Reader<'input, Endian>>,
+    offset: usize,
+) where
+    Endian: gimli::Endianity,
+{
+    match unit_header.type_() {
+        gimli::UnitType::Type {
+            type_signature,
+            type_offset,
+        }
+        | gimli::UnitType::SplitType {
+            type_signature,
+            type_offset,
+        } => {
+            signatures.insert(type_signature.0, offset + type_offset.0);
+        }
+        _ => {}
+    }
+}
+
+/// Return the tag and the offset of the root entry of the unit.
+fn parse_unit_root<'input, Endian>(
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+) -> Result<(gimli::DwTag, usize)>
+where
+    Endian: gimli::Endianity,
+{
+    let mut entries = dwarf_unit.entries();
+    let entry = match entries.next_dfs()? {
+        Some((_, entry)) => entry,
+        None => return Err("missing unit root".into()),
+    };
+    let offset = match dwarf_unit.section_offset(entry.offset()) {
+        gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+        _ => return Err("unexpected unit offset".into()),
     };
+    Ok((entry.tag(), offset))
+}
 
-    let mut units = Vec::new();
-    let mut unit_headers = dwarf.read.units();
-    while let Some(unit_header) = unit_headers.next()? {
-        let dwarf_unit = dwarf.read.unit(unit_header)?;
-        units.push(parse_unit(&mut dwarf, dwarf_unit)?);
+fn load_section<'input: 'file, 'file, Endian, Object>(
+    endian: Endian,
+    object: &'file Object,
+    name: Option<&str>,
+    arena: &'input Arena,
+) -> Result<Reader<'input, Endian>>
+where
+    Endian: gimli::Endianity,
+    Object: object::Object<'input, 'file>,
+{
+    let mut relocations = RelocationMap::default();
+    let data = match name.and_then(|name| object.section_by_name(name)) {
+        Some(ref section) => {
+            add_relocations(&mut relocations, object, section);
+            match section.uncompressed_data()? {
+                Cow::Borrowed(bytes) => bytes,
+                Cow::Owned(bytes) => arena.add_buffer(bytes),
+            }
+        }
+        None => &[],
+    };
+    let relocations = arena.add_relocations(Box::new(relocations));
+    let reader = gimli::EndianSlice::new(data, endian);
+    Ok(Relocate {
+        relocations,
+        section: reader,
+        reader,
+    })
+}
+
+/// Like `load_section`, but concatenates the sections with the same name.
+///
+/// GCC puts each type unit of a `.dwo` file in its own `.debug_info.dwo` section.
+fn load_dwo_section<'input: 'file, 'file, Endian, Object>(
+    endian: Endian,
+    object: &'file Object,
+    id: gimli::SectionId,
+    arena: &'input Arena,
+) -> Result<Reader<'input, Endian>>
+where
+    Endian: gimli::Endianity,
+    Object: object::Object<'input, 'file>,
+{
+    let name = id.dwo_name();
+    let sections: Vec<_> = object
+        .sections()
+        .filter(|section| name.is_some() && section.name().ok() == name)
+        .collect();
+    if sections.len() <= 1 {
+        return load_section(endian, object, name, arena);
+    }
+    let mut data = Vec::new();
+    for section in &sections {
+        data.extend_from_slice(&section.uncompressed_data()?);
+    }
+    let data = arena.add_buffer(data);
+    let relocations = arena.add_relocations(Box::new(RelocationMap::default()));
+    let reader = gimli::EndianSlice::new(data, endian);
+    Ok(Relocate {
+        relocations,
+        section: reader,
+        reader,
+    })
+}
+
+/// Finds the split units for skeleton units, either in the `.dwp` file next
+/// to the file, or in the `.dwo` file named by `DW_AT_dwo_name`.
+struct SplitDwarf<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    endian: Endian,
+    arena: &'input Arena,
+    path: String,
+    // `None` until the first skeleton unit.
+    dwp: Option<Option<gimli::DwarfPackage<Reader<'input, Endian>>>>,
+    // The offset base for the next split unit.
+    base: usize,
+    // The sections and the offset base of the split units which have been
+    // loaded, by `dwo_id`.
+    loaded: HashMap<u64, (Arc<gimli::Dwarf<Reader<'input, Endian>>>, usize)>,
+    // The `dwo_id`s of the split units which can't be found.
+    missing: Vec<u64>,
+}
+
+impl<'input, Endian> SplitDwarf<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    /// Return the split unit for `skeleton`, and the split type units in the
+    /// same `.dwo` file.
+    ///
+    /// Return `skeleton` itself if it is not a skeleton unit, or if the split
+    /// unit can't be found.
+    fn split_unit(
+        &mut self,
+        skeleton: DwarfUnit<'input, Endian>,
+    ) -> Result<(DwarfUnit<'input, Endian>, Vec<DwarfUnit<'input, Endian>>)> {
+        let dwo_id = match skeleton.dwo_id {
+            Some(dwo_id) => dwo_id,
+            None => return Ok((skeleton, Vec::new())),
+        };
+        let (read, base) = match self.loaded.get(&dwo_id.0) {
+            Some((read, base)) => (read.clone(), *base),
+            None => {
+                let read = match self.load_split(&skeleton, dwo_id)? {
+                    Some(read) => Arc::new(read),
+                    None => {
+                        warn!(
+                            "missing split unit {:#x}, neither {}.dwp nor its .dwo file is found",
+                            dwo_id.0, self.path
+                        );
+                        self.add_missing(dwo_id);
+                        return Ok((skeleton, Vec::new()));
+                    }
+                };
+                let base = self.base;
+                self.base += read.debug_info.reader().len() + read.debug_types.reader().len();
+                self.loaded.insert(dwo_id.0, (read.clone(), base));
+                (read, base)
+            }
+        };
+
+        // The split type units are only referred to by the units of the same
+        // file. Their `.debug_types` offsets follow the `.debug_info` offsets.
+        let types_base = base + read.debug_info.reader().len();
+        let mut split_unit = None;
+        let mut type_units = Vec::new();
+        let mut signatures = HashMap::new();
+        let mut unit_headers = read.units();
+        while let Some(unit_header) = unit_headers.next()? {
+            if let gimli::UnitType::SplitType { .. } = unit_header.type_() {
+                if let gimli::UnitSectionOffset::DebugInfoOffset(offset) = unit_header.offset() {
+                    add_signature(&mut signatures, &unit_header, base + offset.0);
+                }
+                type_units.push((read.unit(unit_header)?, base));
+                continue;
+            }
+            let mut unit = read.unit(unit_header)?;
+            if split_unit.is_none() && unit.dwo_id == Some(dwo_id) {
+                unit.copy_relocated_attributes(&skeleton);
+                split_unit = Some(unit);
+            }
+        }
+        let mut unit_headers = read.type_units();
+        while let Some(unit_header) = unit_headers.next()? {
+            if let gimli::UnitSectionOffset::DebugTypesOffset(offset) = unit_header.offset() {
+                add_signature(&mut signatures, &unit_header, types_base + offset.0);
+            }
+            type_units.push((read.unit(unit_header)?, types_base));
+        }
+        let split_unit = match split_unit {
+            Some(split_unit) => split_unit,
+            None => {
+                warn!("missing split unit {:#x}", dwo_id.0);
+                self.add_missing(dwo_id);
+                return Ok((skeleton, Vec::new()));
+            }
+        };
+        let signatures = if signatures.is_empty() {
+            skeleton.signatures
+        } else {
+            signatures.extend(skeleton.signatures.iter().map(|(k, v)| (*k, *v)));
+            Arc::new(signatures)
+        };
+        let type_units = type_units
+            .into_iter()
+            .map(|(unit, base)| DwarfUnit {
+                unit,
+                read: read.clone(),
+                base,
+                signatures: signatures.clone(),
+            })
+            .collect();
+        let split_unit = DwarfUnit {
+            unit: split_unit,
+            read,
+            base,
+            signatures,
+        };
+        Ok((split_unit, type_units))
+    }
+
+    fn add_missing(&mut self, dwo_id: gimli::DwoId) {
+        if !self.missing.contains(&dwo_id.0) {
+            self.missing.push(dwo_id.0);
+        }
+    }
+
+    fn load_split(
+        &mut self,
+        skeleton: &DwarfUnit<'input, Endian>,
+        dwo_id: gimli::DwoId,
+    ) -> Result<Option<gimli::Dwarf<Reader<'input, Endian>>>> {
+        if self.dwp.is_none() {
+            self.dwp = Some(self.load_dwp()?);
+        }
+        if let Some(Some(dwp)) = &self.dwp {
+            if let Some(read) = dwp.find_cu(dwo_id, &skeleton.read)? {
+                return Ok(Some(read));
+            }
+        }
+        self.load_dwo(skeleton)
+    }
+
+    fn load_dwp(&self) -> Result<Option<gimli::DwarfPackage<Reader<'input, Endian>>>> {
+        let data = match fs::read(format!("{}.dwp", self.path)) {
+            Ok(data) => self.arena.add_buffer(data),
+            Err(_) => return Ok(None),
+        };
+        let object = object::File::parse(data)?;
+        let empty = load_section(self.endian, &object, None, self.arena)?;
+        let get_section = |id: gimli::SectionId| -> Result<_> {
+            let section = load_section(self.endian, &object, id.dwo_name(), self.arena)?;
+            // GNU dwp writes an index without slots if there are no type units,
+            // which gimli rejects. Only the 16 byte header is present then.
+            if id == gimli::SectionId::DebugTuIndex && section.len() <= 16 {
+                return Ok(empty);
+            }
+            Ok(section)
+        };
+        Ok(Some(gimli::DwarfPackage::load(get_section, empty)?))
+    }
+
+    fn load_dwo(
+        &self,
+        skeleton: &DwarfUnit<'input, Endian>,
+    ) -> Result<Option<gimli::Dwarf<Reader<'input, Endian>>>> {
+        let mut tree = skeleton.entries_tree(None)?;
+        let root = tree.root()?;
+        let name = match root.entry().attr_value(gimli::DW_AT_dwo_name)? {
+            Some(name) => Some(name),
+            None => root.entry().attr_value(gimli::DW_AT_GNU_dwo_name)?,
+        };
+        let name = match name {
+            Some(name) => skeleton.read.attr_string(skeleton, name)?,
+            None => return Ok(None),
+        };
+        let name = String::from_utf8_lossy(name.slice());
+        let name = Path::new(name.as_ref());
+
+        // Try `DW_AT_comp_dir` first, then the directory of the file in case
+        // the build tree has been moved.
+        let mut paths = Vec::new();
+        if let Some(comp_dir) = &skeleton.comp_dir {
+            let comp_dir = String::from_utf8_lossy(comp_dir.slice());
+            paths.push(Path::new(comp_dir.as_ref()).join(name));
+        }
+        if let Some(dir) = Path::new(&self.path).parent() {
+            paths.push(dir.join(name));
+            if let Some(file_name) = name.file_name() {
+                paths.push(dir.join(file_name));
+            }
+        }
+        for path in paths {
+            let data = match fs::read(&path) {
+                Ok(data) => self.arena.add_buffer(data),
+                Err(_) => continue,
+            };
+            let object = object::File::parse(data)?;
+            let get_section =
+                |id: gimli::SectionId| load_dwo_section(self.endian, &object, id, self.arena);
+            let mut read = gimli::Dwarf::load(get_section)?;
+            read.file_type = gimli::DwarfFileType::Dwo;
+            // Like `gimli::DwarfPackage::sections`.
+            read.debug_addr = skeleton.read.debug_addr;
+            read.ranges = gimli::RangeLists::new(
+                *skeleton.read.ranges.debug_ranges(),
+                *read.ranges.debug_rnglists(),
+            );
+            return Ok(Some(read));
+        }
+        Ok(None)
     }
-    Ok((units, DebugInfo::Dwarf(dwarf)))
 }
 
 fn parse_unit<'input, Endian>(
     dwarf: &mut DwarfDebugInfo<'input, Endian>,
     dwarf_unit: DwarfUnit<'input, Endian>,
 ) -> Result<Unit<'input>>
+where
+    Endian: gimli::Endianity,
+{
+    let (unit, imports) = parse_unit_entries(dwarf, &dwarf_unit)?;
+    add_unit(dwarf, dwarf_unit, unit, imports)
+}
+
+// The number of threads parsing units in addition to the threads calling
+// `parse_units`.
+static RESERVED_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Threads reserved for parsing units, which are released when dropped.
+///
+/// The threads are shared by all files which are parsed at the same time, so
+/// that parsing files on several threads doesn't create more threads than
+/// there are CPUs.
+struct ParseThreads {
+    count: usize,
+}
+
+impl ParseThreads {
+    fn reserve(wanted: usize) -> Self {
+        let max = thread::available_parallelism().map_or(1, |n| n.get()) - 1;
+        let reserve = |reserved: usize| wanted.min(max.saturating_sub(reserved));
+        let reserved = RESERVED_THREADS
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                Some(reserved + reserve(reserved))
+            })
+            .unwrap();
+        ParseThreads {
+            count: reserve(reserved),
+        }
+    }
+}
+
+impl Drop for ParseThreads {
+    fn drop(&mut self) {
+        RESERVED_THREADS.fetch_sub(self.count, Ordering::Relaxed);
+    }
+}
+
+/// Parse the units on the current thread and the threads which can be
+/// reserved, then add them to `dwarf` in order.
+///
+/// The partial units imported by the units are parsed in order by `add_unit`
+/// on the current thread, so that the result is the same as `parse_unit`.
+fn parse_units<'input, Endian>(
+    dwarf: &mut DwarfDebugInfo<'input, Endian>,
+    dwarf_units: Vec<DwarfUnit<'input, Endian>>,
+) -> Result<Vec<Unit<'input>>>
+where
+    Endian: gimli::Endianity + Send + Sync,
+{
+    let threads = ParseThreads::reserve(dwarf_units.len().saturating_sub(1));
+    let mut results = {
+        let dwarf = &*dwarf;
+        let dwarf_units = &dwarf_units;
+        let next = AtomicUsize::new(0);
+        let work = || {
+            let mut results = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match dwarf_units.get(i) {
+                    Some(dwarf_unit) => results.push((i, parse_unit_entries(dwarf, dwarf_unit))),
+                    None => return results,
+                }
+            }
+        };
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.count).map(|_| scope.spawn(work)).collect();
+            let mut results = work();
+            for worker in workers {
+                match worker.join() {
+                    Ok(v) => results.extend(v),
+                    Err(e) => panic::resume_unwind(e),
+                }
+            }
+            results
+        })
+    };
+    drop(threads);
+    results.sort_unstable_by_key(|(i, _)| *i);
+
+    let mut units = Vec::with_capacity(dwarf_units.len());
+    for (dwarf_unit, (_, parsed)) in dwarf_units.into_iter().zip(results) {
+        let (unit, imports) = parsed?;
+        units.push(add_unit(dwarf, dwarf_unit, unit, imports)?);
+    }
+    Ok(units)
+}
+
+/// Add a unit parsed by `parse_unit_entries` to `dwarf`, then parse the
+/// partial units it imports as part of it.
+fn add_unit<'input, Endian>(
+    dwarf: &mut DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: DwarfUnit<'input, Endian>,
+    mut unit: Unit<'input>,
+    imports: Vec<gimli::UnitSectionOffset>,
+) -> Result<Unit<'input>>
+where
+    Endian: gimli::Endianity,
+{
+    dwarf.push_unit(dwarf_unit);
+    parse_imports(&mut unit, dwarf, imports)?;
+    Ok(unit)
+}
+
+/// Parse the entries of a unit, and return the offsets of the partial units
+/// it imports.
+fn parse_unit_entries<'input, Endian>(
+    dwarf: &DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+) -> Result<(Unit<'input>, Vec<gimli::UnitSectionOffset>)>
 where
     Endian: gimli::Endianity,
 {
@@ -455,12 +1167,19 @@ where
 
     let mut subprograms = Vec::new();
     let mut variables = Vec::new();
+    let mut imports = Vec::new();
 
     let mut tree = dwarf_unit.entries_tree(None)?;
     let root = tree.root()?;
 
     let entry = root.entry();
-    if entry.tag() != gimli::DW_TAG_compile_unit {
+    // A skeleton unit is only parsed if its split unit is missing, and a
+    // partial unit if no unit imports it.
+    if entry.tag() != gimli::DW_TAG_compile_unit
+        && entry.tag() != gimli::DW_TAG_skeleton_unit
+        && entry.tag() != gimli::DW_TAG_partial_unit
+        && entry.tag() != gimli::DW_TAG_type_unit
+    {
         return Err(format!("unknown CU tag: {}", entry.tag()).into());
     }
 
@@ -471,10 +1190,10 @@ where
     while let Some(attr) = attrs.next()? {
         match attr.name() {
             gimli::DW_AT_name => {
-                unit.name = dwarf.string(&dwarf_unit, attr.value()).map(Cow::Borrowed);
+                unit.name = dwarf.string(dwarf_unit, attr.value()).map(Cow::Borrowed);
             }
             gimli::DW_AT_comp_dir => {
-                unit.dir = dwarf.string(&dwarf_unit, attr.value()).map(Cow::Borrowed);
+                unit.dir = dwarf.string(dwarf_unit, attr.value()).map(Cow::Borrowed);
             }
             gimli::DW_AT_language => {
                 if let gimli::AttributeValue::Language(language) = attr.value() {
@@ -534,8 +1253,8 @@ where
             }
         }
     } else if let Some(offset) = ranges {
-        let offset = dwarf.read.ranges_offset_from_raw(&dwarf_unit, offset);
-        let mut ranges = dwarf.read.ranges(&dwarf_unit, offset)?;
+        let offset = dwarf_unit.read.ranges_offset_from_raw(dwarf_unit, offset);
+        let mut ranges = dwarf_unit.read.ranges(dwarf_unit, offset)?;
         while let Some(range) = ranges.next()? {
             if range.begin < range.end {
                 unit.ranges.push(Range {
@@ -565,9 +1284,10 @@ where
     parse_namespace_children(
         &mut unit,
         dwarf,
-        &dwarf_unit,
+        dwarf_unit,
         &mut subprograms,
         &mut variables,
+        &mut imports,
         &namespace,
         root.children(),
     )?;
@@ -575,14 +1295,68 @@ where
     fixup_subprogram_specifications(
         &mut unit,
         dwarf,
-        &dwarf_unit,
+        dwarf_unit,
         &mut subprograms,
         &mut variables,
     )?;
-    fixup_variable_specifications(&mut unit, dwarf, &dwarf_unit, &mut variables)?;
+    fixup_variable_specifications(&mut unit, dwarf, dwarf_unit, &mut variables)?;
+    Ok((unit, imports))
+}
 
-    dwarf.units.push(dwarf_unit);
-    Ok(unit)
+/// Parse the partial units imported by `DW_TAG_imported_unit` as part of `unit`.
+///
+/// A partial unit which is imported by several units is only parsed for the
+/// first one.
+fn parse_imports<'input, Endian>(
+    unit: &mut Unit<'input>,
+    dwarf: &mut DwarfDebugInfo<'input, Endian>,
+    imports: Vec<gimli::UnitSectionOffset>,
+) -> Result<()>
+where
+    Endian: gimli::Endianity,
+{
+    for offset in imports {
+        let dwarf_unit = match dwarf.unit_at(offset)? {
+            Some(dwarf_unit) => dwarf_unit,
+            None => {
+                debug!("missing imported unit: {:?}", offset);
+                continue;
+            }
+        };
+        let (_, offset) = parse_unit_root(&dwarf_unit)?;
+        if !dwarf.imported.insert(offset) {
+            continue;
+        }
+
+        let mut subprograms = Vec::new();
+        let mut variables = Vec::new();
+        let mut imports = Vec::new();
+        let mut tree = dwarf_unit.entries_tree(None)?;
+        let root = tree.root()?;
+        let namespace = None;
+        parse_namespace_children(
+            unit,
+            dwarf,
+            &dwarf_unit,
+            &mut subprograms,
+            &mut variables,
+            &mut imports,
+            &namespace,
+            root.children(),
+        )?;
+        fixup_subprogram_specifications(
+            unit,
+            dwarf,
+            &dwarf_unit,
+            &mut subprograms,
+            &mut variables,
+        )?;
+        fixup_variable_specifications(unit, dwarf, &dwarf_unit, &mut variables)?;
+
+        dwarf.push_unit(dwarf_unit);
+        parse_imports(unit, dwarf, imports)?;
+    }
+    Ok(())
 }
 
 #[inline(never)]
@@ -627,7 +1401,7 @@ where
                     &mut subprogram.function,
                     tree.root()?.children(),
                 )?;
-                let offset = subprogram.offset.to_unit_section_offset(dwarf_unit);
+                let offset = dwarf_unit.section_offset(subprogram.offset);
                 functions.insert(offset.into(), subprogram.function);
                 for function in unit.functions.drain(..) {
                     functions.insert(function.offset, function);
@@ -655,7 +1429,7 @@ where
                     &mut subprogram.function,
                     tree.root()?.children(),
                 )?;
-                let offset = subprogram.offset.to_unit_section_offset(dwarf_unit);
+                let offset = dwarf_unit.section_offset(subprogram.offset);
                 functions.insert(offset.into(), subprogram.function);
                 for function in unit.functions.drain(..) {
                     functions.insert(function.offset, function);
@@ -709,7 +1483,7 @@ where
                     continue;
                 }
             }
-            let offset = variable.offset.to_unit_section_offset(dwarf_unit);
+            let offset = dwarf_unit.section_offset(variable.offset);
             variable_map.insert(offset.into(), variable.variable);
             progress = true;
         }
@@ -720,7 +1494,7 @@ where
         if !progress {
             debug!("invalid specification for {} variables", defer.len());
             for variable in variables.drain(..) {
-                let offset = variable.offset.to_unit_section_offset(dwarf_unit);
+                let offset = dwarf_unit.section_offset(variable.offset);
                 variable_map.insert(offset.into(), variable.variable);
             }
             break;
@@ -738,6 +1512,7 @@ fn parse_namespace_children<'input, 'abbrev, 'unit, 'tree, Endian>(
     dwarf_unit: &DwarfUnit<'input, Endian>,
     subprograms: &mut Vec<DwarfSubprogram<'input>>,
     variables: &mut Vec<DwarfVariable<'input>>,
+    imports: &mut Vec<gimli::UnitSectionOffset>,
     namespace: &Option<Arc<Namespace<'input>>>,
     mut iter: gimli::EntriesTreeIter<'abbrev, 'unit, 'tree, Reader<'input, Endian>>,
 ) -> Result<()>
@@ -753,10 +1528,18 @@ where
                     dwarf_unit,
                     subprograms,
                     variables,
+                    imports,
                     namespace,
                     child,
                 )?;
             }
+            gimli::DW_TAG_imported_unit => {
+                if let Some(attr) = child.entry().attr(gimli::DW_AT_import)? {
+                    if let Some(offset) = parse_debug_info_offset(dwarf_unit, &attr) {
+                        imports.push(offset);
+                    }
+                }
+            }
             gimli::DW_TAG_subprogram => {
                 parse_subprogram(
                     unit,
@@ -804,6 +1587,7 @@ fn parse_namespace<'input, 'abbrev, 'unit, 'tree, Endian>(
     dwarf_unit: &DwarfUnit<'input, Endian>,
     subprograms: &mut Vec<DwarfSubprogram<'input>>,
     variables: &mut Vec<DwarfVariable<'input>>,
+    imports: &mut Vec<gimli::UnitSectionOffset>,
     namespace: &Option<Arc<Namespace<'input>>>,
     node: gimli::EntriesTreeNode<'abbrev, 'unit, 'tree, Reader<'input, Endian>>,
 ) -> Result<()>
@@ -835,6 +1619,7 @@ where
         dwarf_unit,
         subprograms,
         variables,
+        imports,
         &namespace,
         node.children(),
     )
@@ -881,7 +1666,7 @@ where
     let tag = node.entry().tag();
     let mut ty = Type::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     ty.offset = offset.into();
     ty.kind = match tag {
         gimli::DW_TAG_base_type => TypeKind::Base(parse_base_type(dwarf, dwarf_unit, node)?),
@@ -938,7 +1723,7 @@ where
     let tag = node.entry().tag();
     let mut ty = Type::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     ty.offset = offset.into();
     ty.kind = match tag {
         gimli::DW_TAG_array_type => TypeKind::Array(parse_array_type(dwarf, dwarf_unit, node)?),
@@ -1114,6 +1899,11 @@ where
                     }
                 }
             }
+            gimli::DW_AT_alignment => {
+                if let Some(alignment) = attr.udata_value() {
+                    ty.alignment = Size::new(alignment);
+                }
+            }
             gimli::DW_AT_artificial | gimli::DW_AT_decimal_scale => {}
             _ => debug!(
                 "unknown base type attribute: {} {:?}",
@@ -1182,6 +1972,55 @@ where
     Ok(typedef)
 }
 
+/// Return the namespace of the declaration referred to by `DW_AT_specification`.
+///
+/// Type units define their type at the top level of the unit, and its
+/// declaration within the namespaces.
+fn parse_specification_namespace<'input, Endian>(
+    dwarf: &DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+    attr: &gimli::Attribute<Reader<'input, Endian>>,
+) -> Result<Option<Option<Arc<Namespace<'input>>>>>
+where
+    Endian: gimli::Endianity,
+{
+    let offset = match attr.value() {
+        gimli::AttributeValue::UnitRef(offset) => offset,
+        _ => return Ok(None),
+    };
+    // The namespaces of the children of each entry on the current path.
+    let mut namespaces: Vec<Option<Arc<Namespace<'input>>>> = Vec::new();
+    let mut depth = 0;
+    let mut entries = dwarf_unit.entries();
+    while let Some((delta_depth, entry)) = entries.next_dfs()? {
+        depth += delta_depth;
+        namespaces.truncate(depth as usize);
+        let namespace = namespaces.last().cloned().flatten();
+        if entry.offset() == offset {
+            return Ok(Some(namespace));
+        }
+        let kind = match entry.tag() {
+            gimli::DW_TAG_namespace => Some(NamespaceKind::Namespace),
+            gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type => Some(NamespaceKind::Type),
+            _ => None,
+        };
+        let namespace = match kind {
+            Some(kind) => {
+                let name = entry
+                    .attr_value(gimli::DW_AT_name)?
+                    .and_then(|name| dwarf.string(dwarf_unit, name));
+                Some(Namespace::new(&namespace, name, kind))
+            }
+            None => namespace,
+        };
+        namespaces.push(namespace);
+    }
+    Ok(None)
+}
+
 fn parse_structure_type<'input, 'abbrev, 'unit, 'tree, Endian>(
     unit: &mut Unit<'input>,
     dwarf: &DwarfDebugInfo<'input, Endian>,
@@ -1213,10 +2052,23 @@ where
                     ty.declaration = flag;
                 }
             }
+            gimli::DW_AT_calling_convention => {
+                ty.pass_by_reference = parse_pass_by_reference(&attr);
+            }
             gimli::DW_AT_decl_file => parse_source_file(dwarf, dwarf_unit, &attr, &mut ty.source),
             gimli::DW_AT_decl_line => parse_source_line(&attr, &mut ty.source),
             gimli::DW_AT_decl_column => parse_source_column(&attr, &mut ty.source),
-            gimli::DW_AT_containing_type | gimli::DW_AT_alignment | gimli::DW_AT_sibling => {}
+            gimli::DW_AT_alignment => {
+                if let Some(alignment) = attr.udata_value() {
+                    ty.alignment = Size::new(alignment);
+                }
+            }
+            gimli::DW_AT_specification => {
+                if let Some(namespace) = parse_specification_namespace(dwarf, dwarf_unit, &attr)? {
+                    ty.namespace = namespace;
+                }
+            }
+            gimli::DW_AT_containing_type | gimli::DW_AT_signature | gimli::DW_AT_sibling => {}
             _ => debug!(
                 "unknown struct attribute: {} {:?}",
                 attr.name(),
@@ -1230,6 +2082,8 @@ where
     while let Some(child) = iter.next()? {
         match child.entry().tag() {
             gimli::DW_TAG_subprogram => {
+                let offset = dwarf_unit.section_offset(child.entry().offset());
+                ty.methods.push(offset.into());
                 parse_subprogram(
                     unit,
                     dwarf,
@@ -1259,7 +2113,9 @@ where
             }
             gimli::DW_TAG_template_type_parameter
             | gimli::DW_TAG_template_value_parameter
-            | gimli::DW_TAG_GNU_template_parameter_pack => {}
+            | gimli::DW_TAG_GNU_template_parameter_pack => {
+                parse_template_parameter(&mut ty.template_parameters, dwarf, dwarf_unit, child)?;
+            }
             tag => {
                 if !parse_type(
                     unit,
@@ -1275,6 +2131,12 @@ where
             }
         }
     }
+    for variant in ty.variant_parts.iter_mut().flat_map(|v| v.variants.iter_mut()) {
+        let params = std::mem::take(&mut variant.template_parameters);
+        if ty.template_parameters.is_empty() {
+            ty.template_parameters = params;
+        }
+    }
     Ok(ty)
 }
 
@@ -1309,10 +2171,23 @@ where
                     ty.declaration = flag;
                 }
             }
+            gimli::DW_AT_calling_convention => {
+                ty.pass_by_reference = parse_pass_by_reference(&attr);
+            }
             gimli::DW_AT_decl_file => parse_source_file(dwarf, dwarf_unit, &attr, &mut ty.source),
             gimli::DW_AT_decl_line => parse_source_line(&attr, &mut ty.source),
             gimli::DW_AT_decl_column => parse_source_column(&attr, &mut ty.source),
-            gimli::DW_AT_alignment | gimli::DW_AT_sibling => {}
+            gimli::DW_AT_alignment => {
+                if let Some(alignment) = attr.udata_value() {
+                    ty.alignment = Size::new(alignment);
+                }
+            }
+            gimli::DW_AT_specification => {
+                if let Some(namespace) = parse_specification_namespace(dwarf, dwarf_unit, &attr)? {
+                    ty.namespace = namespace;
+                }
+            }
+            gimli::DW_AT_signature | gimli::DW_AT_sibling => {}
             _ => debug!(
                 "unknown union attribute: {} {:?}",
                 attr.name(),
@@ -1339,7 +2214,11 @@ where
             gimli::DW_TAG_member => {
                 parse_member(&mut ty.members, unit, dwarf, dwarf_unit, &namespace, child)?;
             }
-            gimli::DW_TAG_template_type_parameter => {}
+            gimli::DW_TAG_template_type_parameter
+            | gimli::DW_TAG_template_value_parameter
+            | gimli::DW_TAG_GNU_template_parameter_pack => {
+                parse_template_parameter(&mut ty.template_parameters, dwarf, dwarf_unit, child)?;
+            }
             tag => {
                 if !parse_type(
                     unit,
@@ -1480,7 +2359,7 @@ where
     if unit.language == Some(gimli::DW_LANG_Rust) && variant.members.len() == 1 {
         if let Some(offset) = variant.members[0].ty.get() {
             let offset = gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(offset));
-            if let Some(offset) = offset.to_unit_offset(dwarf_unit) {
+            if let Some(offset) = dwarf_unit.unit_offset(offset) {
                 let mut tree = dwarf_unit.entries_tree(Some(offset))?;
                 let node = tree.root()?;
                 if node.entry().tag() == gimli::DW_TAG_structure_type {
@@ -1493,15 +2372,28 @@ where
                     variant.members.clear();
                     let mut iter = node.children();
                     while let Some(child) = iter.next()? {
-                        if child.entry().tag() == gimli::DW_TAG_member {
-                            parse_member(
-                                &mut variant.members,
-                                unit,
-                                dwarf,
-                                dwarf_unit,
-                                namespace,
-                                child,
-                            )?;
+                        match child.entry().tag() {
+                            gimli::DW_TAG_member => {
+                                parse_member(
+                                    &mut variant.members,
+                                    unit,
+                                    dwarf,
+                                    dwarf_unit,
+                                    namespace,
+                                    child,
+                                )?;
+                            }
+                            gimli::DW_TAG_template_type_parameter
+                            | gimli::DW_TAG_template_value_parameter
+                            | gimli::DW_TAG_GNU_template_parameter_pack => {
+                                parse_template_parameter(
+                                    &mut variant.template_parameters,
+                                    dwarf,
+                                    dwarf_unit,
+                                    child,
+                                )?;
+                            }
+                            _ => {}
                         }
                     }
                 }
@@ -1526,7 +2418,7 @@ where
 {
     let mut member = Member::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     member.offset = offset.into();
     let mut bit_offset = None;
     let mut byte_size = None;
@@ -1567,14 +2459,22 @@ where
             gimli::DW_AT_declaration => {
                 declaration = true;
             }
+            gimli::DW_AT_alignment => {
+                if let Some(alignment) = attr.udata_value() {
+                    member.alignment = Size::new(alignment);
+                }
+            }
+            gimli::DW_AT_artificial => {
+                if let gimli::AttributeValue::Flag(flag) = attr.value() {
+                    member.artificial = flag;
+                }
+            }
             gimli::DW_AT_decl_file
             | gimli::DW_AT_decl_line
             | gimli::DW_AT_decl_column
             | gimli::DW_AT_external
             | gimli::DW_AT_accessibility
-            | gimli::DW_AT_artificial
             | gimli::DW_AT_const_value
-            | gimli::DW_AT_alignment
             | gimli::DW_AT_sibling => {}
             _ => {
                 debug!(
@@ -1645,6 +2545,90 @@ where
     Ok(())
 }
 
+fn parse_template_parameter<'input, 'abbrev, 'unit, 'tree, Endian>(
+    params: &mut Vec<TemplateParameter<'input>>,
+    dwarf: &DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+    node: gimli::EntriesTreeNode<'abbrev, 'unit, 'tree, Reader<'input, Endian>>,
+) -> Result<()>
+where
+    Endian: gimli::Endianity,
+{
+    let tag = node.entry().tag();
+    let mut name = None;
+    let mut ty = TypeOffset::none();
+    let mut value = None;
+    let mut default = false;
+
+    let mut attrs = node.entry().attrs();
+    while let Some(attr) = attrs.next()? {
+        match attr.name() {
+            gimli::DW_AT_name => {
+                name = dwarf.string(dwarf_unit, attr.value());
+            }
+            gimli::DW_AT_type => {
+                if let Some(offset) = parse_type_offset(dwarf_unit, &attr) {
+                    ty = offset;
+                }
+            }
+            gimli::DW_AT_const_value => match attr.value() {
+                gimli::AttributeValue::Sdata(v) => value = Some(v as u64),
+                _ => {
+                    if let Some(v) = attr.udata_value() {
+                        value = Some(v);
+                    } else {
+                        debug!("unknown template parameter const_value: {:?}", attr.value());
+                    }
+                }
+            },
+            gimli::DW_AT_default_value => {
+                if let gimli::AttributeValue::Flag(flag) = attr.value() {
+                    default = flag;
+                }
+            }
+            gimli::DW_AT_location | gimli::DW_AT_sibling => {}
+            _ => {
+                debug!(
+                    "unknown template parameter attribute: {} {:?}",
+                    attr.name(),
+                    attr.value()
+                );
+            }
+        }
+    }
+
+    let mut pack_params = Vec::new();
+    let mut iter = node.children();
+    while let Some(child) = iter.next()? {
+        match child.entry().tag() {
+            gimli::DW_TAG_template_type_parameter | gimli::DW_TAG_template_value_parameter
+                if tag == gimli::DW_TAG_GNU_template_parameter_pack =>
+            {
+                parse_template_parameter(&mut pack_params, dwarf, dwarf_unit, child)?;
+            }
+            tag => {
+                debug!("unknown template parameter child tag: {}", tag);
+            }
+        }
+    }
+
+    let param = match tag {
+        gimli::DW_TAG_template_type_parameter => TemplateParameter::Type { name, ty, default },
+        gimli::DW_TAG_template_value_parameter => TemplateParameter::Value {
+            name,
+            ty,
+            value,
+            default,
+        },
+        _ => TemplateParameter::Pack {
+            name,
+            params: pack_params,
+        },
+    };
+    params.push(param);
+    Ok(())
+}
+
 fn parse_inheritance<'input, 'abbrev, 'unit, 'tree, Endian>(
     inherits: &mut Vec<Inherit>,
     dwarf_unit: &DwarfUnit<'input, Endian>,
@@ -1664,11 +2648,26 @@ where
                 }
             }
             gimli::DW_AT_data_member_location => {
-                if let Some(offset) = parse_data_member_location(dwarf_unit, &attr) {
-                    inherit.bit_offset = offset;
+                if let gimli::AttributeValue::Exprloc(expr) = attr.value() {
+                    inherit.vbase_offset_offset =
+                        evaluate_vbase_offset_offset(&dwarf_unit.header, expr);
+                }
+                if inherit.vbase_offset_offset.is_none() {
+                    if let Some(offset) = parse_data_member_location(dwarf_unit, &attr) {
+                        inherit.bit_offset = offset;
+                    }
                 }
             }
-            gimli::DW_AT_accessibility | gimli::DW_AT_virtuality | gimli::DW_AT_sibling => {}
+            gimli::DW_AT_virtuality => {
+                if let gimli::AttributeValue::Virtuality(val) = attr.value() {
+                    inherit.virtuality = match val {
+                        gimli::DW_VIRTUALITY_virtual => Virtuality::Virtual,
+                        gimli::DW_VIRTUALITY_pure_virtual => Virtuality::PureVirtual,
+                        _ => Virtuality::None,
+                    };
+                }
+            }
+            gimli::DW_AT_accessibility | gimli::DW_AT_sibling => {}
             _ => {
                 debug!(
                     "unknown inheritance attribute: {} {:?}",
@@ -1767,9 +2766,19 @@ where
             gimli::DW_AT_decl_file => parse_source_file(dwarf, dwarf_unit, &attr, &mut ty.source),
             gimli::DW_AT_decl_line => parse_source_line(&attr, &mut ty.source),
             gimli::DW_AT_decl_column => parse_source_column(&attr, &mut ty.source),
+            gimli::DW_AT_type => {
+                if let Some(offset) = parse_type_offset(dwarf_unit, &attr) {
+                    ty.ty = offset;
+                }
+            }
+            gimli::DW_AT_specification => {
+                if let Some(namespace) = parse_specification_namespace(dwarf, dwarf_unit, &attr)? {
+                    ty.namespace = namespace;
+                }
+            }
             gimli::DW_AT_sibling
+            | gimli::DW_AT_signature
             | gimli::DW_AT_encoding
-            | gimli::DW_AT_type
             | gimli::DW_AT_alignment
             | gimli::DW_AT_enum_class => {}
             _ => debug!(
@@ -1842,9 +2851,9 @@ where
                 enumerator.name = dwarf.string(dwarf_unit, attr.value());
             }
             gimli::DW_AT_const_value => {
-                if let Some(value) = attr.sdata_value() {
-                    enumerator.value = Some(value);
-                } else {
+                enumerator.value = attr.sdata_value();
+                enumerator.udata_value = attr.udata_value();
+                if enumerator.value.is_none() && enumerator.udata_value.is_none() {
                     debug!("unknown enumerator const_value: {:?}", attr.value());
                 }
             }
@@ -2047,6 +3056,9 @@ where
             gimli::DW_TAG_formal_parameter => {
                 parse_parameter_type(&mut function.parameters, dwarf, dwarf_unit, child)?;
             }
+            gimli::DW_TAG_unspecified_parameters => {
+                function.variadic = true;
+            }
             tag => {
                 debug!("unknown subroutine child tag: {}", tag);
             }
@@ -2157,7 +3169,7 @@ where
     let offset = node.entry().offset();
     let mut function = Function {
         id: Id::new(0),
-        offset: offset.to_unit_section_offset(dwarf_unit).into(),
+        offset: dwarf_unit.section_offset(offset).into(),
         namespace: namespace.clone(),
         name: None,
         symbol_name: None,
@@ -2168,7 +3180,11 @@ where
         ranges: Vec::new(),
         inline: false,
         declaration: false,
+        artificial: false,
+        virtuality: Virtuality::None,
+        vtable_elem_location: None,
         parameters: Vec::new(),
+        variadic: false,
         return_type: TypeOffset::none(),
     };
 
@@ -2240,6 +3256,29 @@ where
                     function.declaration = flag;
                 }
             }
+            gimli::DW_AT_artificial => {
+                if let gimli::AttributeValue::Flag(flag) = attr.value() {
+                    function.artificial = flag;
+                }
+            }
+            gimli::DW_AT_virtuality => {
+                if let gimli::AttributeValue::Virtuality(val) = attr.value() {
+                    function.virtuality = match val {
+                        gimli::DW_VIRTUALITY_virtual => Virtuality::Virtual,
+                        gimli::DW_VIRTUALITY_pure_virtual => Virtuality::PureVirtual,
+                        _ => Virtuality::None,
+                    };
+                }
+            }
+            gimli::DW_AT_vtable_elem_location => match attr.value() {
+                gimli::AttributeValue::Exprloc(expr) => {
+                    function.vtable_elem_location =
+                        evaluate_vtable_elem_location(&dwarf_unit.header, expr);
+                }
+                _ => {
+                    debug!("unknown DW_AT_vtable_elem_location: {:?}", attr.value());
+                }
+            },
             gimli::DW_AT_frame_base => {
                 // FIXME
             }
@@ -2251,10 +3290,7 @@ where
             | gimli::DW_AT_prototyped
             | gimli::DW_AT_accessibility
             | gimli::DW_AT_explicit
-            | gimli::DW_AT_artificial
             | gimli::DW_AT_object_pointer
-            | gimli::DW_AT_virtuality
-            | gimli::DW_AT_vtable_elem_location
             | gimli::DW_AT_containing_type
             | gimli::DW_AT_main_subprogram
             | gimli::DW_AT_noreturn
@@ -2270,8 +3306,8 @@ where
     }
 
     if let Some(offset) = ranges {
-        let offset = dwarf.read.ranges_offset_from_raw(&dwarf_unit, offset);
-        let mut ranges = dwarf.read.ranges(&dwarf_unit, offset)?;
+        let offset = dwarf_unit.read.ranges_offset_from_raw(&dwarf_unit, offset);
+        let mut ranges = dwarf_unit.read.ranges(&dwarf_unit, offset)?;
         let mut size = 0;
         while let Some(range) = ranges.next()? {
             if range.end > range.begin {
@@ -2349,6 +3385,12 @@ fn inherit_subprogram<'input>(
     if function.return_type.is_none() {
         function.return_type = specification.return_type;
     }
+    if function.virtuality == Virtuality::None {
+        function.virtuality = specification.virtuality;
+    }
+    if function.vtable_elem_location.is_none() {
+        function.vtable_elem_location = specification.vtable_elem_location;
+    }
     if abstract_origin {
         // We inherit all children, and then extend them when parsing our children.
         function.parameters = specification.parameters.clone();
@@ -2409,8 +3451,10 @@ where
                     child,
                 )?;
             }
-            gimli::DW_TAG_unspecified_parameters
-            | gimli::DW_TAG_template_type_parameter
+            gimli::DW_TAG_unspecified_parameters => {
+                function.variadic = true;
+            }
+            gimli::DW_TAG_template_type_parameter
             | gimli::DW_TAG_template_value_parameter
             | gimli::DW_TAG_GNU_template_parameter_pack
             | gimli::DW_TAG_label
@@ -2447,7 +3491,7 @@ where
 {
     let mut parameter = ParameterType::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     parameter.offset = offset.into();
     let mut abstract_origin = None;
 
@@ -2504,8 +3548,8 @@ where
             }
             return Ok(());
         } else {
-            let unit_offset = offset
-                .to_unit_offset(dwarf_unit)
+            let unit_offset = dwarf_unit
+                .unit_offset(offset)
                 .unwrap_or(gimli::UnitOffset(0));
             let offset = match offset {
                 gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
@@ -2537,7 +3581,7 @@ where
 {
     let mut parameter = Parameter::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     parameter.offset = offset.into();
     let mut abstract_origin = None;
 
@@ -2568,7 +3612,7 @@ where
                         );
                     }
                     gimli::AttributeValue::LocationListsRef(offset) => {
-                        let mut locations = dwarf.read.locations(dwarf_unit, offset)?;
+                        let mut locations = dwarf_unit.read.locations(dwarf_unit, offset)?;
                         while let Some(location) = locations.next()? {
                             // TODO: use location.range too
                             evaluate_parameter_location(
@@ -2623,8 +3667,8 @@ where
             }
             return Ok(());
         } else {
-            let unit_offset = offset
-                .to_unit_offset(dwarf_unit)
+            let unit_offset = dwarf_unit
+                .unit_offset(offset)
                 .unwrap_or(gimli::UnitOffset(0));
             let offset = match offset {
                 gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
@@ -2989,8 +4033,8 @@ where
 
     if let Some(offset) = ranges {
         let mut size = 0;
-        let offset = dwarf.read.ranges_offset_from_raw(dwarf_unit, offset);
-        let mut ranges = dwarf.read.ranges(dwarf_unit, offset)?;
+        let offset = dwarf_unit.read.ranges_offset_from_raw(dwarf_unit, offset);
+        let mut ranges = dwarf_unit.read.ranges(dwarf_unit, offset)?;
         while let Some(range) = ranges.next()? {
             size += range.end.wrapping_sub(range.begin);
         }
@@ -3051,7 +4095,7 @@ where
     let offset = node.entry().offset();
     let mut specification = None;
     let mut variable = Variable {
-        offset: offset.to_unit_section_offset(dwarf_unit).into(),
+        offset: dwarf_unit.section_offset(offset).into(),
         namespace,
         ..Default::default()
     };
@@ -3144,7 +4188,7 @@ where
 {
     let mut variable = LocalVariable::default();
     let offset = node.entry().offset();
-    let offset = offset.to_unit_section_offset(dwarf_unit);
+    let offset = dwarf_unit.section_offset(offset);
     variable.offset = offset.into();
     let mut abstract_origin = None;
 
@@ -3180,7 +4224,7 @@ where
                         );
                     }
                     gimli::AttributeValue::LocationListsRef(offset) => {
-                        let mut locations = dwarf.read.locations(dwarf_unit, offset)?;
+                        let mut locations = dwarf_unit.read.locations(dwarf_unit, offset)?;
                         while let Some(location) = locations.next()? {
                             // TODO: use location.range too
                             evaluate_local_variable_location(
@@ -3242,8 +4286,8 @@ where
             }
             return Ok(());
         } else {
-            let unit_offset = offset
-                .to_unit_offset(dwarf_unit)
+            let unit_offset = dwarf_unit
+                .unit_offset(offset)
                 .unwrap_or(gimli::UnitOffset(0));
             let offset = match offset {
                 gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
@@ -3286,6 +4330,59 @@ where
     }
 }
 
+// The location of a virtual base is read from the vtable at runtime.
+// For the Itanium C++ ABI, both GCC and clang emit:
+//   DW_OP_dup; DW_OP_deref; DW_OP_constu N; DW_OP_minus; DW_OP_deref; DW_OP_plus
+// i.e. the offset of the virtual base is stored N bytes before the vtable address point.
+fn evaluate_vbase_offset_offset<'input, Endian>(
+    unit: &gimli::UnitHeader<Reader<'input, Endian>>,
+    expression: gimli::Expression<Reader<'input, Endian>>,
+) -> Option<i64>
+where
+    Endian: gimli::Endianity,
+{
+    let mut bytes = expression.0;
+    let mut ops = Vec::new();
+    while !bytes.is_empty() {
+        ops.push(gimli::Operation::parse(&mut bytes, unit.encoding()).ok()?);
+    }
+    if ops.len() != 6 {
+        return None;
+    }
+    let is_vbase = matches!(ops[0], gimli::Operation::Pick { index: 0 })
+        && matches!(ops[1], gimli::Operation::Deref { .. })
+        && matches!(ops[3], gimli::Operation::Minus)
+        && matches!(ops[4], gimli::Operation::Deref { .. })
+        && matches!(ops[5], gimli::Operation::Plus);
+    match ops[2] {
+        gimli::Operation::UnsignedConstant { value } if is_vbase => Some(-(value as i64)),
+        _ => None,
+    }
+}
+
+// Like DW_AT_data_member_location, this expects the object address on the stack.
+// The result is an index into the vtable, not an address.
+fn evaluate_vtable_elem_location<'input, Endian>(
+    unit: &gimli::UnitHeader<Reader<'input, Endian>>,
+    expression: gimli::Expression<Reader<'input, Endian>>,
+) -> Option<u64>
+where
+    Endian: gimli::Endianity,
+{
+    let pieces = evaluate(unit, expression, true);
+    if pieces.len() != 1 {
+        debug!("unsupported number of evaluation pieces: {:?}", pieces);
+        return None;
+    }
+    match pieces[0].location {
+        gimli::Location::Address { address } => Some(address),
+        _ => {
+            debug!("unknown DW_AT_vtable_elem_location result: {:?}", pieces);
+            None
+        }
+    }
+}
+
 fn evaluate_variable_location<'input, Endian>(
     unit: &gimli::UnitHeader<Reader<'input, Endian>>,
     expression: gimli::Expression<Reader<'input, Endian>>,
@@ -3803,9 +4900,15 @@ where
     Endian: gimli::Endianity,
 {
     match attr.value() {
-        gimli::AttributeValue::UnitRef(offset) => Some(offset.to_unit_section_offset(dwarf_unit)),
-        gimli::AttributeValue::DebugInfoRef(offset) => {
-            Some(gimli::UnitSectionOffset::DebugInfoOffset(offset))
+        gimli::AttributeValue::UnitRef(offset) => Some(dwarf_unit.section_offset(offset)),
+        gimli::AttributeValue::DebugInfoRefSup(offset) => Some(dwarf_unit.sup_offset(offset)),
+        gimli::AttributeValue::DebugInfoRef(offset) => Some(dwarf_unit.info_offset(offset)),
+        gimli::AttributeValue::DebugTypesRef(signature) => {
+            let offset = dwarf_unit.signature_offset(signature);
+            if offset.is_none() {
+                debug!("unknown type signature: {:#x}", signature.0);
+            }
+            offset
         }
         other => {
             debug!("unknown offset: {:?}", other);
@@ -3814,6 +4917,19 @@ where
     }
 }
 
+fn parse_pass_by_reference<'input, Endian>(
+    attr: &gimli::Attribute<Reader<'input, Endian>>,
+) -> Option<bool>
+where
+    Endian: gimli::Endianity,
+{
+    match attr.value() {
+        gimli::AttributeValue::CallingConvention(gimli::DW_CC_pass_by_reference) => Some(true),
+        gimli::AttributeValue::CallingConvention(gimli::DW_CC_pass_by_value) => Some(false),
+        _ => None,
+    }
+}
+
 fn parse_function_offset<'input, Endian>(
     dwarf_unit: &DwarfUnit<'input, Endian>,
     attr: &gimli::Attribute<Reader<'input, Endian>>,
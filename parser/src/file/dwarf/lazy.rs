@@ -0,0 +1,700 @@
+//! Parse only the units which define the requested types.
+//!
+//! The units are found by the names of the types they define, using
+//! `.debug_names` or `.gdb_index`. If the file has neither, the names are
+//! read from the entries of all units outside of the subprograms, which is
+//! still much cheaper than parsing the units.
+
+use std::collections::{HashMap, HashSet};
+use std::str;
+
+use gimli::Reader as GimliReader;
+use gimli::Section as GimliSection;
+
+use super::{
+    load_section, parse_unit, parse_unit_root, parse_units, DwarfDebugInfo, DwarfUnit, Reader,
+};
+use crate::types::{Member, TemplateParameter, Type, TypeKind, TypeName, TypeOffset};
+use crate::unit::Unit;
+use crate::Result;
+
+// The attribute indices and the kind of `.gdb_index` symbols that we need.
+const DW_IDX_COMPILE_UNIT: u64 = 1;
+const DW_IDX_TYPE_UNIT: u64 = 2;
+const GDB_INDEX_SYMBOL_KIND_TYPE: u32 = 1;
+
+/// Maps the unqualified names of types to the units defining them.
+pub(crate) enum NameIndex<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    DebugNames(Reader<'input, Endian>),
+    GdbIndex(&'input [u8]),
+    Scan(HashMap<&'input str, Vec<usize>>),
+}
+
+impl<'input, Endian> NameIndex<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    pub(crate) fn new<'file, Object>(
+        dwarf: &mut DwarfDebugInfo<'input, Endian>,
+        object: &'file Object,
+    ) -> Result<Self>
+    where
+        'input: 'file,
+        Object: object::Object<'input, 'file>,
+    {
+        // The split units and the supplementary file aren't covered by the
+        // index of the main file.
+        let indexable = dwarf.read.sup.is_none() && !dwarf_units_are_split(dwarf)?;
+        if indexable {
+            if object.section_by_name(".debug_names").is_some() {
+                let section =
+                    load_section(dwarf.endian, object, Some(".debug_names"), dwarf.arena)?;
+                return Ok(NameIndex::DebugNames(section));
+            }
+            if object.section_by_name(".gdb_index").is_some() {
+                let section = load_section(dwarf.endian, object, Some(".gdb_index"), dwarf.arena)?;
+                let section = section.slice();
+                match section
+                    .get(..4)
+                    .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                {
+                    Some(7) | Some(8) => return Ok(NameIndex::GdbIndex(section)),
+                    version => debug!("unsupported .gdb_index version {:?}", version),
+                }
+            }
+        }
+        Ok(NameIndex::Scan(scan_names(dwarf)?))
+    }
+}
+
+/// Return true if any unit of the main file is a skeleton unit.
+fn dwarf_units_are_split<Endian>(dwarf: &DwarfDebugInfo<Endian>) -> Result<bool>
+where
+    Endian: gimli::Endianity,
+{
+    let mut unit_headers = dwarf.read.units();
+    while let Some(unit_header) = unit_headers.next()? {
+        match unit_header.type_() {
+            gimli::UnitType::Skeleton(_) => return Ok(true),
+            gimli::UnitType::Compilation => {
+                let unit = dwarf.read.unit(unit_header)?;
+                if unit.dwo_id.is_some() {
+                    return Ok(true);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Read the names of the types defined by all units, including split units.
+fn scan_names<'input, Endian>(
+    dwarf: &mut DwarfDebugInfo<'input, Endian>,
+) -> Result<HashMap<&'input str, Vec<usize>>>
+where
+    Endian: gimli::Endianity,
+{
+    let mut names = HashMap::new();
+    for i in 0..dwarf.unit_offsets.len() {
+        let offset = dwarf.unit_offsets[i];
+        let dwarf_unit = match dwarf.unit_at(unit_offset(offset))? {
+            Some(dwarf_unit) => dwarf_unit,
+            None => continue,
+        };
+        let (dwarf_unit, type_units) = dwarf.split.split_unit(dwarf_unit)?;
+        for dwarf_unit in Some(dwarf_unit).into_iter().chain(type_units) {
+            scan_unit(dwarf, &dwarf_unit, offset, &mut names)?;
+        }
+    }
+    Ok(names)
+}
+
+fn scan_unit<'input, Endian>(
+    dwarf: &DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+    offset: usize,
+    names: &mut HashMap<&'input str, Vec<usize>>,
+) -> Result<()>
+where
+    Endian: gimli::Endianity,
+{
+    let mut tree = dwarf_unit.entries_tree(None)?;
+    scan_children(dwarf, dwarf_unit, tree.root()?.children(), offset, names)
+}
+
+/// Read the names of the types defined by the entries of `iter` and their
+/// children, except the children of subprograms.
+///
+/// Like `.debug_names` and `.gdb_index`, this doesn't find the types local to
+/// a function, but it skips the subtrees of the subprograms, which hold most
+/// of the entries, using `DW_AT_sibling`.
+fn scan_children<'input, 'abbrev, 'unit, 'tree, Endian>(
+    dwarf: &DwarfDebugInfo<'input, Endian>,
+    dwarf_unit: &DwarfUnit<'input, Endian>,
+    mut iter: gimli::EntriesTreeIter<'abbrev, 'unit, 'tree, Reader<'input, Endian>>,
+    offset: usize,
+    names: &mut HashMap<&'input str, Vec<usize>>,
+) -> Result<()>
+where
+    Endian: gimli::Endianity,
+{
+    while let Some(child) = iter.next()? {
+        let entry = child.entry();
+        if entry.tag() == gimli::DW_TAG_subprogram {
+            continue;
+        }
+        let declaration = matches!(
+            entry.attr_value(gimli::DW_AT_declaration)?,
+            Some(gimli::AttributeValue::Flag(true))
+        );
+        if is_type_tag(entry.tag()) && !declaration {
+            let name = match entry.attr_value(gimli::DW_AT_name)? {
+                Some(name) => dwarf.string(dwarf_unit, name),
+                None => None,
+            };
+            if let Some(name) = name {
+                let offsets = names.entry(name).or_default();
+                if offsets.last() != Some(&offset) {
+                    offsets.push(offset);
+                }
+            }
+        }
+        scan_children(dwarf, dwarf_unit, child.children(), offset, names)?;
+    }
+    Ok(())
+}
+
+/// The tags of the entries which are indexed by their names.
+fn is_type_tag(tag: gimli::DwTag) -> bool {
+    matches!(
+        tag,
+        gimli::DW_TAG_typedef
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type
+            | gimli::DW_TAG_base_type
+            | gimli::DW_TAG_unspecified_type
+    )
+}
+
+fn unit_offset(offset: usize) -> gimli::UnitSectionOffset {
+    gimli::UnitSectionOffset::DebugInfoOffset(gimli::DebugInfoOffset(offset))
+}
+
+/// Return the last component of a qualified name, e.g. `Foo<ns::Bar>` for
+/// `::ns::Foo<ns::Bar>`.
+fn base_name(name: &str) -> &str {
+    let bytes = name.as_bytes();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        match b {
+            b'<' | b'(' | b'[' => depth += 1,
+            b'>' | b')' | b']' => depth -= 1,
+            b':' if depth == 0 && bytes.get(i + 1) == Some(&b':') => start = i + 2,
+            _ => {}
+        }
+    }
+    &name[start..]
+}
+
+fn read_form<'input, Endian>(
+    input: &mut Reader<'input, Endian>,
+    form: u64,
+    format: gimli::Format,
+) -> Result<u64>
+where
+    Endian: gimli::Endianity,
+{
+    let value = match gimli::DwForm(form as u16) {
+        gimli::DW_FORM_flag_present => 1,
+        gimli::DW_FORM_data1 | gimli::DW_FORM_ref1 | gimli::DW_FORM_flag => {
+            u64::from(input.read_u8()?)
+        }
+        gimli::DW_FORM_data2 | gimli::DW_FORM_ref2 => u64::from(input.read_u16()?),
+        gimli::DW_FORM_data4 | gimli::DW_FORM_ref4 => u64::from(input.read_u32()?),
+        gimli::DW_FORM_data8 | gimli::DW_FORM_ref8 | gimli::DW_FORM_ref_sig8 => input.read_u64()?,
+        gimli::DW_FORM_udata | gimli::DW_FORM_ref_udata => input.read_uleb128()?,
+        gimli::DW_FORM_sdata => input.read_sleb128()? as u64,
+        gimli::DW_FORM_sec_offset => input.read_offset(format)? as u64,
+        form => return Err(format!("unsupported .debug_names form {}", form).into()),
+    };
+    Ok(value)
+}
+
+/// The state of lazy parsing.
+pub(crate) struct LazyUnits<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    index: NameIndex<'input, Endian>,
+    // The offsets of the units which have been parsed.
+    loaded: HashSet<usize>,
+    // The qualified names of the structs, unions and enumerations defined by
+    // the parsed units.
+    defined: HashSet<String>,
+    // The qualified names of the declarations which no unit defines.
+    missing: HashSet<String>,
+}
+
+impl<'input, Endian> LazyUnits<'input, Endian>
+where
+    Endian: gimli::Endianity,
+{
+    pub(crate) fn new(index: NameIndex<'input, Endian>) -> Self {
+        LazyUnits {
+            index,
+            loaded: HashSet::new(),
+            defined: HashSet::new(),
+            missing: HashSet::new(),
+        }
+    }
+}
+
+impl<'input, Endian> DwarfDebugInfo<'input, Endian>
+where
+    Endian: gimli::Endianity + Send + Sync,
+{
+    /// Parse the units which define the types whose unqualified names are
+    /// accepted by `filter`, and the units these types depend on.
+    pub(crate) fn load_names(
+        &mut self,
+        filter: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<Vec<Unit<'input>>> {
+        let mut offsets = Vec::new();
+        self.find_names(filter, &mut |_, offset| offsets.push(offset))?;
+        self.load_units(offsets, HashMap::new())
+    }
+
+    /// Parse the units which define the declarations with the given qualified
+    /// names, e.g. `::ns::Foo`, and the units these types depend on.
+    ///
+    /// Does nothing if the file isn't parsed lazily.
+    pub(crate) fn load_declarations(&mut self, names: &[String]) -> Result<Vec<Unit<'input>>> {
+        let lazy = match &self.lazy {
+            Some(lazy) => lazy,
+            None => return Ok(Vec::new()),
+        };
+        let pending = names
+            .iter()
+            .filter(|name| !lazy.defined.contains(*name) && !lazy.missing.contains(*name))
+            .map(|name| (name.clone(), base_name(name).to_string()))
+            .collect();
+        self.load_units(Vec::new(), pending)
+    }
+
+    /// Parse the units at `offsets`, then repeatedly parse the units which
+    /// define the types referred to by the parsed units, either by offset or
+    /// by the name of a declaration. `pending` maps the qualified names of
+    /// the declarations to their unqualified names.
+    fn load_units(
+        &mut self,
+        mut offsets: Vec<usize>,
+        mut pending: HashMap<String, String>,
+    ) -> Result<Vec<Unit<'input>>> {
+        let mut units = Vec::new();
+        loop {
+            offsets.sort_unstable();
+            offsets.dedup();
+            let start = units.len();
+            let mut dwarf_units = Vec::new();
+            let mut partial_units = Vec::new();
+            for offset in offsets.drain(..) {
+                self.unit_with_split_units(offset, &mut dwarf_units, &mut partial_units)?;
+            }
+            units.extend(parse_units(self, dwarf_units)?);
+            // A partial unit may already be parsed as part of a unit importing
+            // it, and mustn't be parsed again by units importing it later.
+            for (root, dwarf_unit) in partial_units {
+                if self.imported.insert(root) {
+                    units.push(parse_unit(self, dwarf_unit)?);
+                }
+            }
+
+            let lazy = self.lazy.as_mut().unwrap();
+            for ty in units[start..].iter().flat_map(|unit| unit.types.iter()) {
+                if let Some(name) = definition_name(ty) {
+                    lazy.defined.insert(name);
+                }
+            }
+            let mut refs = Vec::new();
+            for ty in units[start..].iter().flat_map(|unit| unit.types.iter()) {
+                if let Some((name, base)) = declaration_name(ty) {
+                    if !lazy.defined.contains(&name) && !lazy.missing.contains(&name) {
+                        pending.insert(name, base.to_string());
+                    }
+                }
+                type_refs(ty, &mut refs);
+            }
+            pending.retain(|name, _| !lazy.defined.contains(name));
+
+            // References to types in other units.
+            for offset in refs.iter().filter_map(|offset| offset.get()) {
+                if self.parsed_unit(offset).is_some() {
+                    continue;
+                }
+                let unit_offset = match self.unit_offsets.binary_search(&offset) {
+                    Ok(i) => self.unit_offsets[i],
+                    Err(0) => continue,
+                    Err(i) => self.unit_offsets[i - 1],
+                };
+                if !self.lazy.as_ref().unwrap().loaded.contains(&unit_offset) {
+                    offsets.push(unit_offset);
+                }
+            }
+            if offsets.is_empty() && !pending.is_empty() {
+                self.find_declarations(&mut pending, &mut offsets)?;
+            }
+            if offsets.is_empty() {
+                break;
+            }
+        }
+        Ok(units)
+    }
+
+    /// Add the offset of the next unit defining each of the `pending`
+    /// declarations to `offsets`. The declarations which no remaining unit
+    /// defines are moved to `missing`.
+    fn find_declarations(
+        &mut self,
+        pending: &mut HashMap<String, String>,
+        offsets: &mut Vec<usize>,
+    ) -> Result<()> {
+        let bases: HashSet<&str> = pending.values().map(String::as_str).collect();
+        let mut candidates: HashMap<String, Vec<usize>> = HashMap::new();
+        self.find_names(&mut |name| bases.contains(name), &mut |name, offset| {
+            candidates.entry(name.to_string()).or_default().push(offset)
+        })?;
+        let lazy = self.lazy.as_mut().unwrap();
+        for (name, base) in pending.drain() {
+            let next = candidates
+                .get(&base)
+                .and_then(|v| v.iter().filter(|o| !lazy.loaded.contains(o)).min());
+            match next {
+                Some(offset) => offsets.push(*offset),
+                None => {
+                    debug!("no definition of {}", name);
+                    lazy.missing.insert(name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the unit at `offset` to `dwarf_units` if it hasn't been loaded,
+    /// together with its split unit and split type units. A partial unit is
+    /// added to `partial_units` with the offset of its root entry instead.
+    fn unit_with_split_units(
+        &mut self,
+        offset: usize,
+        dwarf_units: &mut Vec<DwarfUnit<'input, Endian>>,
+        partial_units: &mut Vec<(usize, DwarfUnit<'input, Endian>)>,
+    ) -> Result<()> {
+        if !self.lazy.as_mut().unwrap().loaded.insert(offset) {
+            return Ok(());
+        }
+        let dwarf_unit = match self.unit_at(unit_offset(offset))? {
+            Some(dwarf_unit) => dwarf_unit,
+            None => {
+                debug!("missing unit at {:#x}", offset);
+                return Ok(());
+            }
+        };
+        let (tag, root) = parse_unit_root(&dwarf_unit)?;
+        if tag == gimli::DW_TAG_partial_unit {
+            partial_units.push((root, dwarf_unit));
+            return Ok(());
+        }
+        let (dwarf_unit, type_units) = self.split.split_unit(dwarf_unit)?;
+        dwarf_units.push(dwarf_unit);
+        dwarf_units.extend(type_units);
+        Ok(())
+    }
+
+    /// Call `f` with the offset of each unit which defines a type whose
+    /// unqualified name is accepted by `filter`.
+    fn find_names(
+        &self,
+        filter: &mut dyn FnMut(&str) -> bool,
+        f: &mut dyn FnMut(&str, usize),
+    ) -> Result<()> {
+        match &self.lazy.as_ref().unwrap().index {
+            NameIndex::DebugNames(section) => self.find_debug_names(*section, filter, f),
+            NameIndex::GdbIndex(section) => self.find_gdb_index(section, filter, f),
+            NameIndex::Scan(names) => {
+                for (name, offsets) in names {
+                    if filter(name) {
+                        for offset in offsets {
+                            f(name, *offset);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn find_debug_names(
+        &self,
+        mut input: Reader<'input, Endian>,
+        filter: &mut dyn FnMut(&str) -> bool,
+        f: &mut dyn FnMut(&str, usize),
+    ) -> Result<()> {
+        let debug_str = *self.read.debug_str.reader();
+        while !input.is_empty() {
+            let (length, format) = input.read_initial_length()?;
+            let mut unit = input.split(length)?;
+            let version = unit.read_u16()?;
+            if version != 5 {
+                return Err(format!("unsupported .debug_names version {}", version).into());
+            }
+            unit.skip(2)?;
+            let cu_count = unit.read_u32()? as usize;
+            let local_tu_count = unit.read_u32()? as usize;
+            let foreign_tu_count = unit.read_u32()? as usize;
+            let bucket_count = unit.read_u32()? as usize;
+            let name_count = unit.read_u32()? as usize;
+            let abbrev_size = unit.read_u32()? as usize;
+            let augmentation_size = unit.read_u32()? as usize;
+            unit.skip(augmentation_size)?;
+            let mut cus = Vec::with_capacity(cu_count);
+            for _ in 0..cu_count {
+                cus.push(unit.read_offset(format)?);
+            }
+            let mut tus = Vec::with_capacity(local_tu_count);
+            for _ in 0..local_tu_count {
+                tus.push(unit.read_offset(format)?);
+            }
+            unit.skip(foreign_tu_count * 8)?;
+            unit.skip(bucket_count * 4)?;
+            if bucket_count != 0 {
+                unit.skip(name_count * 4)?;
+            }
+            let word_size = usize::from(format.word_size());
+            let mut string_offsets = unit.split(name_count * word_size)?;
+            let mut entry_offsets = unit.split(name_count * word_size)?;
+            let mut abbrevs_input = unit.split(abbrev_size)?;
+            let pool = unit;
+
+            let mut abbrevs = HashMap::new();
+            loop {
+                let code = abbrevs_input.read_uleb128()?;
+                if code == 0 {
+                    break;
+                }
+                let tag = abbrevs_input.read_uleb128()?;
+                let mut attrs = Vec::new();
+                loop {
+                    let index = abbrevs_input.read_uleb128()?;
+                    let form = abbrevs_input.read_uleb128()?;
+                    if index == 0 && form == 0 {
+                        break;
+                    }
+                    attrs.push((index, form));
+                }
+                abbrevs.insert(code, (gimli::DwTag(tag as u16), attrs));
+            }
+
+            for _ in 0..name_count {
+                let string_offset = string_offsets.read_offset(format)?;
+                let entry_offset = entry_offsets.read_offset(format)?;
+                let mut name = debug_str;
+                name.skip(string_offset)?;
+                let name = match str::from_utf8(name.read_null_terminated_slice()?.slice()) {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                if !filter(name) {
+                    continue;
+                }
+                let mut entries = pool;
+                entries.skip(entry_offset)?;
+                loop {
+                    let code = entries.read_uleb128()?;
+                    if code == 0 {
+                        break;
+                    }
+                    let (tag, attrs) = match abbrevs.get(&code) {
+                        Some(abbrev) => abbrev,
+                        None => return Err("invalid .debug_names abbreviation code".into()),
+                    };
+                    let mut cu = None;
+                    let mut tu = None;
+                    for (index, form) in attrs {
+                        let value = read_form(&mut entries, *form, format)? as usize;
+                        match *index {
+                            DW_IDX_COMPILE_UNIT => cu = Some(value),
+                            DW_IDX_TYPE_UNIT => tu = Some(value),
+                            _ => {}
+                        }
+                    }
+                    if !is_type_tag(*tag) {
+                        continue;
+                    }
+                    // The compile unit may be implied if there is only one.
+                    let offset = match (tu, cu) {
+                        (Some(tu), _) => tus.get(tu),
+                        (None, Some(cu)) => cus.get(cu),
+                        (None, None) if cus.len() == 1 => cus.first(),
+                        (None, None) => None,
+                    };
+                    match offset {
+                        Some(offset) => f(name, *offset),
+                        // Foreign type units are in split units.
+                        None => debug!("unknown .debug_names unit for {}", name),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn find_gdb_index(
+        &self,
+        section: &'input [u8],
+        filter: &mut dyn FnMut(&str) -> bool,
+        f: &mut dyn FnMut(&str, usize),
+    ) -> Result<()> {
+        let read_u32 = |offset: usize| -> Result<u32> {
+            match section.get(offset..offset + 4) {
+                Some(v) => Ok(u32::from_le_bytes([v[0], v[1], v[2], v[3]])),
+                None => Err("invalid .gdb_index offset".into()),
+            }
+        };
+        let read_u64 = |offset: usize| -> Result<u64> {
+            Ok(u64::from(read_u32(offset)?) | u64::from(read_u32(offset + 4)?) << 32)
+        };
+        let cu_list = read_u32(4)? as usize;
+        let tu_list = read_u32(8)? as usize;
+        let address_area = read_u32(12)? as usize;
+        let symbol_table = read_u32(16)? as usize;
+        let constant_pool = read_u32(20)? as usize;
+        let cu_count = tu_list.saturating_sub(cu_list) / 16;
+        let tu_count = address_area.saturating_sub(tu_list) / 24;
+
+        let mut slot = symbol_table;
+        while slot + 8 <= constant_pool {
+            let name_offset = read_u32(slot)? as usize;
+            let vec_offset = read_u32(slot + 4)? as usize;
+            slot += 8;
+            if name_offset == 0 && vec_offset == 0 {
+                continue;
+            }
+            let name = match section.get(constant_pool + name_offset..) {
+                Some(name) => name,
+                None => continue,
+            };
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+            // The names are qualified, unlike those of `.debug_names`.
+            let name = match str::from_utf8(name) {
+                Ok(name) => base_name(name),
+                Err(_) => continue,
+            };
+            if !filter(name) {
+                continue;
+            }
+            let vec_offset = constant_pool + vec_offset;
+            let count = read_u32(vec_offset)? as usize;
+            for i in 0..count {
+                let value = read_u32(vec_offset + 4 + i * 4)?;
+                let index = (value & 0xff_ffff) as usize;
+                let kind = (value >> 28) & 7;
+                // Kind 0 is used by version 7, which has no kinds.
+                if kind != 0 && kind != GDB_INDEX_SYMBOL_KIND_TYPE {
+                    continue;
+                }
+                let offset = if index < cu_count {
+                    read_u64(cu_list + index * 16)? as usize
+                } else if index - cu_count < tu_count {
+                    let tu = tu_list + (index - cu_count) * 24;
+                    self.types_base + read_u64(tu)? as usize
+                } else {
+                    continue;
+                };
+                f(name, offset);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The qualified name of a struct, union or enumeration definition.
+fn definition_name(ty: &Type) -> Option<String> {
+    match ty.kind() {
+        TypeKind::Struct(t) if !t.declaration => {}
+        TypeKind::Union(t) if !t.declaration => {}
+        TypeKind::Enumeration(t) if !t.declaration => {}
+        _ => return None,
+    }
+    let name = TypeName::try_from(ty)?;
+    if name.is_anon() {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// The qualified and unqualified names of a struct, union or enumeration
+/// declaration.
+fn declaration_name<'input>(ty: &Type<'input>) -> Option<(String, &'input str)> {
+    let base = match ty.kind() {
+        TypeKind::Struct(t) if t.declaration => t.name?,
+        TypeKind::Union(t) if t.declaration => t.name?,
+        TypeKind::Enumeration(t) if t.declaration => t.name?,
+        _ => return None,
+    };
+    let name = TypeName::try_from(ty)?;
+    if name.is_anon() {
+        return None;
+    }
+    Some((name.to_string(), base))
+}
+
+/// Add the offsets of the types that `ty` refers to.
+fn type_refs(ty: &Type, refs: &mut Vec<TypeOffset>) {
+    fn members(members: &[Member], refs: &mut Vec<TypeOffset>) {
+        refs.extend(members.iter().map(|member| member.ty));
+    }
+    fn template_parameters(params: &[TemplateParameter], refs: &mut Vec<TypeOffset>) {
+        for param in params {
+            match param {
+                TemplateParameter::Type { ty, .. } | TemplateParameter::Value { ty, .. } => {
+                    refs.push(*ty)
+                }
+                TemplateParameter::Pack { params, .. } => template_parameters(params, refs),
+            }
+        }
+    }
+    match ty.kind() {
+        TypeKind::Void | TypeKind::Base(_) | TypeKind::Unspecified(_) => {}
+        TypeKind::Def(t) => refs.push(t.ty),
+        TypeKind::Struct(t) => {
+            members(&t.members, refs);
+            for variant in t.variant_parts.iter().flat_map(|v| v.variants.iter()) {
+                members(&variant.members, refs);
+            }
+            refs.extend(t.inherits.iter().map(|inherit| inherit.ty));
+            template_parameters(&t.template_parameters, refs);
+        }
+        TypeKind::Union(t) => {
+            members(&t.members, refs);
+            template_parameters(&t.template_parameters, refs);
+        }
+        TypeKind::Enumeration(t) => refs.push(t.ty),
+        TypeKind::Array(t) => refs.push(t.ty),
+        TypeKind::Function(t) => {
+            refs.push(t.return_type);
+            refs.extend(t.parameters.iter().map(|param| param.ty));
+        }
+        TypeKind::PointerToMember(t) => {
+            refs.push(t.ty);
+            refs.push(t.containing_ty);
+        }
+        TypeKind::Modifier(t) => refs.push(t.ty),
+        TypeKind::Subrange(t) => refs.push(t.ty),
+    }
+}
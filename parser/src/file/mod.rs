@@ -3,6 +3,7 @@ use std::default::Default;
 use std::fs;
 use std::mem;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 mod dwarf;
@@ -65,6 +66,21 @@ where
             DebugInfo::Dwarf(dwarf) => dwarf.get_register_name(machine, register),
         }
     }
+
+    fn missing_split_units(&self) -> &[u64] {
+        match self {
+            DebugInfo::Dwarf(dwarf) => dwarf.missing_split_units(),
+        }
+    }
+
+    fn load_declarations(&mut self, names: &[String]) -> Result<Vec<Unit<'input>>>
+    where
+        Endian: Send + Sync,
+    {
+        match self {
+            DebugInfo::Dwarf(dwarf) => dwarf.load_declarations(names),
+        }
+    }
 }
 
 pub(crate) struct Arena {
@@ -118,6 +134,125 @@ impl Arena {
     }
 }
 
+fn map_file(path: &Path) -> Result<memmap::Mmap> {
+    let handle = match fs::File::open(path) {
+        Ok(handle) => handle,
+        Err(e) => {
+            return Err(format!("open failed: {}", e).into());
+        }
+    };
+
+    match unsafe { memmap::Mmap::map(&handle) } {
+        Ok(map) => Ok(map),
+        Err(e) => Err(format!("memmap failed: {}", e).into()),
+    }
+}
+
+const DEFAULT_DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// Find the separate debug file for the file `path` with contents `data`.
+///
+/// Return `None` if the file has its own debuginfo, or if no debug file is found.
+fn find_debug_file(data: &[u8], path: &str, debug_dirs: &[String]) -> Result<Option<PathBuf>> {
+    let object = object::File::parse(data)?;
+    if object.section_by_name(".debug_info").is_some() {
+        return Ok(None);
+    }
+    let debug_dirs = with_default_debug_dir(debug_dirs);
+
+    if let Some(build_id) = object.build_id()? {
+        if let Some(debug_path) = find_build_id_file(build_id, &debug_dirs) {
+            return Ok(Some(debug_path));
+        }
+    }
+
+    if let Some((name, crc)) = object.gnu_debuglink()? {
+        let name = String::from_utf8_lossy(name);
+        // Resolve symlinks such as `/usr/bin/cc`, the debug link is relative
+        // to the real file.
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut debug_paths = vec![
+            dir.join(name.as_ref()),
+            dir.join(".debug").join(name.as_ref()),
+        ];
+        for debug_dir in &debug_dirs {
+            let dir = dir.strip_prefix("/").unwrap_or(dir);
+            debug_paths.push(debug_dir.join(dir).join(name.as_ref()));
+        }
+        for debug_path in debug_paths {
+            // The debug link may name the file itself.
+            if !debug_path.is_file() || fs::canonicalize(&debug_path).ok().as_ref() == Some(&path) {
+                continue;
+            }
+            let map = match map_file(&debug_path) {
+                Ok(map) => map,
+                Err(e) => {
+                    warn!("failed to read debug link {}: {}", debug_path.display(), e);
+                    continue;
+                }
+            };
+            if crc32fast::hash(&map) == crc {
+                return Ok(Some(debug_path));
+            }
+            debug!("CRC mismatch of debug link {}", debug_path.display());
+        }
+    }
+    warn!("missing debuginfo and debug file of {}", path);
+    Ok(None)
+}
+
+/// Find the supplementary file for the debug file `path` with contents `data`.
+fn find_sup_file(data: &[u8], path: &Path, debug_dirs: &[String]) -> Result<Option<PathBuf>> {
+    let object = object::File::parse(data)?;
+    let (name, build_id) = match object.gnu_debugaltlink()? {
+        Some(link) => link,
+        None => return Ok(None),
+    };
+    let name = String::from_utf8_lossy(name);
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let sup_path = dir.join(name.as_ref());
+    if sup_path.is_file() {
+        let map = map_file(&sup_path)?;
+        if object::File::parse(&*map)?.build_id()? == Some(build_id) {
+            return Ok(Some(sup_path));
+        }
+        debug!("build ID mismatch of {}", sup_path.display());
+    }
+    if let Some(sup_path) = find_build_id_file(build_id, &with_default_debug_dir(debug_dirs)) {
+        return Ok(Some(sup_path));
+    }
+    warn!("missing supplementary file {} of {}", name, path.display());
+    Ok(None)
+}
+
+fn with_default_debug_dir(debug_dirs: &[String]) -> Vec<&Path> {
+    debug_dirs
+        .iter()
+        .map(Path::new)
+        .chain(Some(Path::new(DEFAULT_DEBUG_DIR)))
+        .collect()
+}
+
+/// Find `.build-id/xx/yyyy.debug` in `debug_dirs`.
+fn find_build_id_file(build_id: &[u8], debug_dirs: &[&Path]) -> Option<PathBuf> {
+    if build_id.len() < 2 {
+        return None;
+    }
+    let build_id: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+    for dir in debug_dirs {
+        let debug_path = dir
+            .join(".build-id")
+            .join(&build_id[..2])
+            .join(format!("{}.debug", &build_id[2..]));
+        if debug_path.is_file() {
+            return Some(debug_path);
+        }
+    }
+    None
+}
+
 pub use object::Architecture;
 
 /// The context needed for a parsed file.
@@ -127,21 +262,35 @@ pub struct FileContext {
     // Self-referential, not actually `static.
     file: File<'static>,
     _map: memmap::Mmap,
+    _debug_map: Option<memmap::Mmap>,
+    _sup_map: Option<memmap::Mmap>,
     _arena: Box<Arena>,
 }
 
 impl FileContext {
-    fn new<F>(map: memmap::Mmap, f: F) -> Result<FileContext>
+    fn new<F>(
+        map: memmap::Mmap,
+        debug_map: Option<memmap::Mmap>,
+        sup_map: Option<memmap::Mmap>,
+        f: F,
+    ) -> Result<FileContext>
     where
-        F: for<'a> FnOnce(&'a [u8], &'a Arena) -> Result<File<'a>>,
+        F: for<'a> FnOnce(
+            &'a [u8],
+            Option<&'a [u8]>,
+            Option<&'a [u8]>,
+            &'a Arena,
+        ) -> Result<File<'a>>,
     {
         let arena = Box::new(Arena::new());
-        let file = f(&map, &arena)?;
+        let file = f(&map, debug_map.as_deref(), sup_map.as_deref(), &arena)?;
         Ok(FileContext {
-            // `file` only borrows from `map` and `arena`, which we are preserving
-            // without moving.
+            // `file` only borrows from `map`, `debug_map`, `sup_map` and `arena`,
+            // which we are preserving without moving.
             file: unsafe { mem::transmute::<File<'_>, File<'static>>(file) },
             _map: map,
+            _debug_map: debug_map,
+            _sup_map: sup_map,
             _arena: arena,
         })
     }
@@ -150,6 +299,18 @@ impl FileContext {
     pub fn file<'a>(&'a self) -> &'a File<'a> {
         unsafe { mem::transmute::<&'a File<'static>, &'a File<'a>>(&self.file) }
     }
+
+    /// Parse the units which define the declarations with the given names,
+    /// e.g. `::ns::Foo`, if the file is parsed by `File::parse_lazy`.
+    ///
+    /// The units these types depend on are parsed too. Return the number of
+    /// parsed units.
+    pub fn load_declarations(&mut self, names: &[String]) -> Result<usize> {
+        let units = self.file.debug_info.load_declarations(names)?;
+        let count = units.len();
+        self.file.units.extend(units);
+        Ok(count)
+    }
 }
 
 /// The parsed debuginfo for a single file.
@@ -188,34 +349,119 @@ impl<'input> File<'input> {
     }
 
     /// Parse the file with the given path.
+    ///
+    /// If the file has been stripped, the debuginfo is read from the separate
+    /// debug file, see `parse_with_debug_dirs`.
     pub fn parse(path: String) -> Result<FileContext> {
-        let handle = match fs::File::open(&path) {
-            Ok(handle) => handle,
-            Err(e) => {
-                return Err(format!("open failed: {}", e).into());
+        File::parse_with_debug_dirs(path, &[])
+    }
+
+    /// Parse the file with the given path, and look for its separate debug file
+    /// in `debug_dirs` too.
+    ///
+    /// If the file has no `.debug_info` section, the debuginfo is read from a
+    /// separate debug file, which is found by the build ID of the file in
+    /// `.build-id/xx/yyyy.debug` under `debug_dirs` and `/usr/lib/debug`, or by
+    /// the `.gnu_debuglink` section of the file. The debug link is looked up in
+    /// the directory of the file, its `.debug` subdirectory, and the same
+    /// directory under `debug_dirs` and `/usr/lib/debug`, and the CRC of the
+    /// debug file must match. Symbols and segments are still read from the file.
+    ///
+    /// The supplementary file named by `.gnu_debugaltlink`, as created by `dwz`,
+    /// is found in the same way by its build ID, or by its path which is
+    /// relative to the debug file.
+    pub fn parse_with_debug_dirs(path: String, debug_dirs: &[String]) -> Result<FileContext> {
+        File::parse_file(path, debug_dirs, None)
+    }
+
+    /// Like `parse_with_debug_dirs`, but only parse the units which define the
+    /// types whose names are accepted by `filter`, and the units these types
+    /// depend on.
+    ///
+    /// `filter` is called with unqualified names, e.g. `Foo` for `::ns::Foo`.
+    /// The units are found by `.debug_names` or `.gdb_index`, or by reading
+    /// the names of the types defined by all units if the file has neither
+    /// or if it uses split units or a supplementary file. More units can be
+    /// parsed later by `FileContext::load_declarations`.
+    ///
+    /// Symbols aren't matched with the functions and variables of the parsed
+    /// units.
+    pub fn parse_lazy(
+        path: String,
+        debug_dirs: &[String],
+        filter: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<FileContext> {
+        File::parse_file(path, debug_dirs, Some(filter))
+    }
+
+    fn parse_file(
+        path: String,
+        debug_dirs: &[String],
+        filter: Option<&mut dyn FnMut(&str) -> bool>,
+    ) -> Result<FileContext> {
+        let map = map_file(Path::new(&path))?;
+        let mut debug_path = PathBuf::from(&path);
+        let debug_map = match find_debug_file(&map, &path, debug_dirs)? {
+            Some(path) => {
+                debug!("separate debug file: {}", path.display());
+                let debug_map = map_file(&path)?;
+                debug_path = path;
+                Some(debug_map)
             }
+            None => None,
         };
-
-        let map = match unsafe { memmap::Mmap::map(&handle) } {
-            Ok(map) => map,
-            Err(e) => {
-                return Err(format!("memmap failed: {}", e).into());
+        let sup_data = debug_map.as_deref().unwrap_or(&map);
+        let sup_map = match find_sup_file(sup_data, &debug_path, debug_dirs)? {
+            Some(path) => {
+                debug!("supplementary file: {}", path.display());
+                Some(map_file(&path)?)
             }
+            None => None,
         };
 
-        // TODO: split DWARF
         // TODO: PDB
-        FileContext::new(map, |data, strings| {
-            let object = object::File::parse(data)?;
-            File::parse_object(&object, &object, path, strings)
-        })
+        FileContext::new(
+            map,
+            debug_map,
+            sup_map,
+            |data, debug_data, sup_data, strings| {
+                let object = object::File::parse(data)?;
+                let sup_object = match sup_data {
+                    Some(sup_data) => Some(object::File::parse(sup_data)?),
+                    None => None,
+                };
+                match debug_data {
+                    Some(debug_data) => {
+                        let debug_object = object::File::parse(debug_data)?;
+                        File::parse_object(
+                            &object,
+                            &debug_object,
+                            sup_object.as_ref(),
+                            path,
+                            strings,
+                            filter,
+                        )
+                    }
+                    None => File::parse_object(
+                        &object,
+                        &object,
+                        sup_object.as_ref(),
+                        path,
+                        strings,
+                        filter,
+                    ),
+                }
+            },
+        )
     }
 
     fn parse_object(
         object: &object::File<'input>,
         debug_object: &object::File<'input>,
+        sup_object: Option<&object::File<'input>>,
         path: String,
         arena: &'input Arena,
+        filter: Option<&mut dyn FnMut(&str) -> bool>,
     ) -> Result<File<'input>> {
         let machine = object.architecture();
         let mut segments = Vec::new();
@@ -306,7 +552,13 @@ impl<'input> File<'input> {
             gimli::RunTimeEndian::Big
         };
 
-        let (units, debug_info) = dwarf::parse(endian, debug_object, arena)?;
+        let lazy = filter.is_some();
+        let (units, debug_info) = match filter {
+            Some(filter) => {
+                dwarf::parse_lazy(endian, debug_object, sup_object, &path, arena, filter)?
+            }
+            None => dwarf::parse(endian, debug_object, sup_object, &path, arena)?,
+        };
         let mut file = File {
             path,
             machine,
@@ -317,7 +569,9 @@ impl<'input> File<'input> {
             units,
             debug_info,
         };
-        file.normalize();
+        if !lazy {
+            file.normalize();
+        }
         Ok(file)
     }
 
@@ -502,6 +756,16 @@ impl<'input> File<'input> {
         &self.units
     }
 
+    /// The `dwo_id`s of the split units which are not found, neither in the
+    /// `.dwp` file nor in their `.dwo` files.
+    ///
+    /// The skeleton units of these are parsed instead, so the types defined
+    /// by them are missing.
+    #[inline]
+    pub fn missing_split_units(&self) -> &[u64] {
+        self.debug_info.missing_split_units()
+    }
+
     /// A list of address ranges covered by the compilation units.
     ///
     /// This includes both `Unit::ranges` and `Unit::unknown_ranges`.
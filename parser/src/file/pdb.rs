@@ -528,6 +528,7 @@ fn parse_field_list<'input>(
                 enumerators.push(Enumerator {
                     name: Some(enumerate.name.as_bytes()),
                     value: Some(value),
+                    udata_value: Some(value as u64),
                 });
             }
             _ => {
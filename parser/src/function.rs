@@ -57,6 +57,18 @@ impl Default for FunctionOffset {
     }
 }
 
+/// The virtuality of a member function.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Virtuality {
+    /// Not a virtual function.
+    #[default]
+    None,
+    /// A virtual function.
+    Virtual,
+    /// A pure virtual function.
+    PureVirtual,
+}
+
 /// A function.
 #[derive(Debug, Default)]
 pub struct Function<'input> {
@@ -72,7 +84,11 @@ pub struct Function<'input> {
     pub(crate) ranges: Vec<Range>,
     pub(crate) inline: bool,
     pub(crate) declaration: bool,
+    pub(crate) artificial: bool,
+    pub(crate) virtuality: Virtuality,
+    pub(crate) vtable_elem_location: Option<u64>,
     pub(crate) parameters: Vec<ParameterType<'input>>,
+    pub(crate) variadic: bool,
     pub(crate) return_type: TypeOffset,
 }
 
@@ -171,12 +187,56 @@ impl<'input> Function<'input> {
         self.declaration
     }
 
+    /// Return true if this function was implicitly declared by the compiler,
+    /// such as an implicit constructor.
+    #[inline]
+    pub fn is_artificial(&self) -> bool {
+        self.artificial
+    }
+
+    /// The virtuality of the function.
+    #[inline]
+    pub fn virtuality(&self) -> Virtuality {
+        self.virtuality
+    }
+
+    /// Return true if this is a virtual or pure virtual function.
+    #[inline]
+    pub fn is_virtual(&self) -> bool {
+        self.virtuality != Virtuality::None
+    }
+
+    /// The index of the slot for this function in the vtable of its class.
+    ///
+    /// Returns `None` if the producer did not record it, such as for virtual destructors
+    /// emitted by GCC.
+    #[inline]
+    pub fn vtable_elem_location(&self) -> Option<u64> {
+        self.vtable_elem_location
+    }
+
     /// The function parameter types.
+    ///
+    /// For member functions, this includes the artificial `this` parameter.
     #[inline]
     pub fn parameters(&self) -> &[ParameterType<'input>] {
         &self.parameters
     }
 
+    /// Return true if the function takes a variable number of arguments, i.e. `...`.
+    #[inline]
+    pub fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    /// The debuginfo offset of the return type of the function.
+    ///
+    /// This is none if the function returns void.
+    #[inline]
+    pub fn return_type_offset(&self) -> TypeOffset {
+        self.return_type
+    }
+
     /// The return type.
     ///
     /// Returns `None` if the return type is invalid.
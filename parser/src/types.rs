@@ -5,7 +5,7 @@ use std::sync::Arc;
 use std::usize;
 
 use crate::file::FileHash;
-use crate::function::ParameterOffset;
+use crate::function::{Function, FunctionOffset, ParameterOffset, Virtuality};
 use crate::namespace::Namespace;
 use crate::source::Source;
 use crate::{Id, Size};
@@ -247,8 +247,9 @@ impl TypeOffset {
         self != Self::none()
     }
 
+    /// Return the offset of the type, or `None` if the type is unknown or `void`.
     #[inline]
-    pub(crate) fn get(self) -> Option<usize> {
+    pub fn get(self) -> Option<usize> {
         if self.is_none() {
             None
         } else {
@@ -644,6 +645,7 @@ impl Default for BaseTypeEncoding {
 pub struct BaseType<'input> {
     pub(crate) name: Option<&'input str>,
     pub(crate) byte_size: Size,
+    pub(crate) alignment: Size,
     pub(crate) encoding: BaseTypeEncoding,
     pub(crate) endianity: Endianity,
 }
@@ -661,6 +663,14 @@ impl<'input> BaseType<'input> {
         self.byte_size.get()
     }
 
+    /// The alignment in bytes of this type, as recorded by `DW_AT_alignment`.
+    ///
+    /// Returns `None` if the producer did not record it, i.e. the alignment is the natural one.
+    #[inline]
+    pub fn alignment(&self) -> Option<u64> {
+        self.alignment.get()
+    }
+
     /// How the base type is encoded an interpreted.
     #[inline]
     pub fn encoding(&self) -> BaseTypeEncoding {
@@ -741,10 +751,14 @@ pub struct StructType<'input> {
     pub(crate) name: Option<&'input str>,
     pub(crate) source: Source<'input>,
     pub(crate) byte_size: Size,
+    pub(crate) alignment: Size,
     pub(crate) declaration: bool,
     pub(crate) members: Vec<Member<'input>>,
     pub(crate) variant_parts: Vec<VariantPart<'input>>,
     pub(crate) inherits: Vec<Inherit>,
+    pub(crate) template_parameters: Vec<TemplateParameter<'input>>,
+    pub(crate) methods: Vec<FunctionOffset>,
+    pub(crate) pass_by_reference: Option<bool>,
 }
 
 impl<'input> StructType<'input> {
@@ -784,12 +798,28 @@ impl<'input> StructType<'input> {
         self.byte_size.get()
     }
 
+    /// The alignment in bytes of this type, as recorded by `DW_AT_alignment`.
+    ///
+    /// Returns `None` if the producer did not record it, i.e. the alignment is the natural one.
+    #[inline]
+    pub fn alignment(&self) -> Option<u64> {
+        self.alignment.get()
+    }
+
     /// Return true if this is a declaration.
     #[inline]
     pub fn is_declaration(&self) -> bool {
         self.declaration
     }
 
+    /// Whether values of this type are passed by reference, as recorded by `DW_AT_calling_convention`.
+    ///
+    /// Returns `None` if the producer did not record it.
+    #[inline]
+    pub fn is_pass_by_reference(&self) -> Option<bool> {
+        self.pass_by_reference
+    }
+
     /// Return true if this is an anonymous type, or defined within an anonymous type.
     pub fn is_anon(&self) -> bool {
         self.name.is_none() || Namespace::is_anon_type(&self.namespace)
@@ -813,7 +843,25 @@ impl<'input> StructType<'input> {
         &self.inherits
     }
 
+    /// The template parameters of this type, in declaration order.
+    #[inline]
+    pub fn template_parameters(&self) -> &[TemplateParameter<'input>] {
+        &self.template_parameters
+    }
+
+    /// The member functions declared in this type, in declaration order.
+    pub fn methods<'a>(
+        &'a self,
+        hash: &'a FileHash<'input>,
+    ) -> impl Iterator<Item = &'a Function<'input>> + 'a {
+        self.methods
+            .iter()
+            .filter_map(move |offset| Function::from_offset(hash, *offset))
+    }
+
     /// The layout of members of this type.
+    ///
+    /// Virtual bases are not included, see `Inherit::is_virtual`.
     pub fn layout<'me>(&'me self, hash: &FileHash) -> Vec<Layout<'input, 'me>> {
         layout(
             &*self.members,
@@ -843,8 +891,11 @@ pub struct UnionType<'input> {
     pub(crate) name: Option<&'input str>,
     pub(crate) source: Source<'input>,
     pub(crate) byte_size: Size,
+    pub(crate) alignment: Size,
     pub(crate) declaration: bool,
     pub(crate) members: Vec<Member<'input>>,
+    pub(crate) template_parameters: Vec<TemplateParameter<'input>>,
+    pub(crate) pass_by_reference: Option<bool>,
 }
 
 impl<'input> UnionType<'input> {
@@ -878,12 +929,28 @@ impl<'input> UnionType<'input> {
         self.byte_size.get()
     }
 
+    /// The alignment in bytes of this type, as recorded by `DW_AT_alignment`.
+    ///
+    /// Returns `None` if the producer did not record it, i.e. the alignment is the natural one.
+    #[inline]
+    pub fn alignment(&self) -> Option<u64> {
+        self.alignment.get()
+    }
+
     /// Return true if this is a declaration.
     #[inline]
     pub fn is_declaration(&self) -> bool {
         self.declaration
     }
 
+    /// Whether values of this type are passed by reference, as recorded by `DW_AT_calling_convention`.
+    ///
+    /// Returns `None` if the producer did not record it.
+    #[inline]
+    pub fn is_pass_by_reference(&self) -> Option<bool> {
+        self.pass_by_reference
+    }
+
     /// Return true if this is an anonymous type, or defined within an anonymous type.
     pub fn is_anon(&self) -> bool {
         self.name.is_none() || Namespace::is_anon_type(&self.namespace)
@@ -895,6 +962,12 @@ impl<'input> UnionType<'input> {
         &self.members
     }
 
+    /// The template parameters of this type, in declaration order.
+    #[inline]
+    pub fn template_parameters(&self) -> &[TemplateParameter<'input>] {
+        &self.template_parameters
+    }
+
     /// Compare the identifying information of two types.
     ///
     /// Unions are considered equal if their names are equal.
@@ -980,6 +1053,9 @@ pub struct Variant<'input> {
     pub(crate) discr_value: Option<u64>,
     pub(crate) name: Option<&'input str>,
     pub(crate) members: Vec<Member<'input>>,
+    // Rust records the template parameters of an enum on the struct of each variant,
+    // these are moved to the enum once it is parsed.
+    pub(crate) template_parameters: Vec<TemplateParameter<'input>>,
 }
 
 impl<'input> Variant<'input> {
@@ -1099,6 +1175,8 @@ pub struct Member<'input> {
     // Defaults to 0, so always present.
     pub(crate) bit_offset: u64,
     pub(crate) bit_size: Size,
+    pub(crate) alignment: Size,
+    pub(crate) artificial: bool,
 }
 
 impl<'input> Member<'input> {
@@ -1134,6 +1212,29 @@ impl<'input> Member<'input> {
         }
     }
 
+    /// The alignment in bytes of this member, as recorded by `DW_AT_alignment`, such as `alignas(64) int i;`.
+    ///
+    /// Returns `None` if the producer did not record it, i.e. the alignment is the natural one.
+    #[inline]
+    pub fn alignment(&self) -> Option<u64> {
+        self.alignment.get()
+    }
+
+    /// Return true if this member was implicitly declared by the compiler,
+    /// such as the vtable pointer `_vptr.Base`.
+    #[inline]
+    pub fn is_artificial(&self) -> bool {
+        self.artificial
+    }
+
+    /// Return true if this member is a bit field, i.e. it has an explicit bit size.
+    ///
+    /// Note that the bit size of a bit field may be a multiple of 8, such as `long l: 32`.
+    #[inline]
+    pub fn is_bitfield(&self) -> bool {
+        self.bit_size.is_some()
+    }
+
     /// Return true if this member defines an inline type.
     pub fn is_inline(&self, hash: &FileHash) -> bool {
         match self.name() {
@@ -1157,7 +1258,10 @@ impl<'input> Member<'input> {
 pub struct Inherit {
     pub(crate) ty: TypeOffset,
     // Defaults to 0, so always present.
+    // Always 0 for virtual bases, since their offset is only known at runtime.
     pub(crate) bit_offset: u64,
+    pub(crate) virtuality: Virtuality,
+    pub(crate) vbase_offset_offset: Option<i64>,
 }
 
 impl Inherit {
@@ -1182,6 +1286,86 @@ impl Inherit {
     pub fn bit_size(&self, hash: &FileHash) -> Option<u64> {
         self.ty(hash).and_then(|v| v.byte_size(hash).map(|v| v * 8))
     }
+
+    /// Return true if this is a virtual base, such as `B` in `struct D : virtual B`.
+    ///
+    /// The offset of a virtual base depends on the most derived type, so `bit_offset` is
+    /// meaningless for it, and it is not included in `StructType::layout`.
+    #[inline]
+    pub fn is_virtual(&self) -> bool {
+        self.virtuality != Virtuality::None
+    }
+
+    /// The offset in bytes, relative to the address pointed to by the vtable pointer of the
+    /// derived type, of the vtable entry which holds the offset of this virtual base.
+    ///
+    /// The virtual base of an object at `p` is at `p + *(ptrdiff_t *)(*(char **)p + offset)`.
+    /// This is decoded from the `DW_AT_data_member_location` expression, and is `None` for
+    /// non-virtual bases or if the expression is not in the form of the Itanium C++ ABI.
+    #[inline]
+    pub fn vbase_offset_offset(&self) -> Option<i64> {
+        self.vbase_offset_offset
+    }
+}
+
+/// A template parameter of a struct or union.
+#[derive(Debug, Clone)]
+pub enum TemplateParameter<'input> {
+    /// A template type parameter, e.g. `T` in `template <typename T>`.
+    Type {
+        /// The name of the parameter.
+        name: Option<&'input str>,
+        /// The type argument of this instantiation.
+        ty: TypeOffset,
+        /// Whether the argument is the default argument of the parameter.
+        default: bool,
+    },
+    /// A template value parameter, e.g. `N` in `template <int N>`.
+    Value {
+        /// The name of the parameter.
+        name: Option<&'input str>,
+        /// The type of the parameter.
+        ty: TypeOffset,
+        /// The raw bits of the value argument, if it is a constant.
+        ///
+        /// Use the parameter type to determine the signedness.
+        value: Option<u64>,
+        /// Whether the argument is the default argument of the parameter.
+        default: bool,
+    },
+    /// A template parameter pack, e.g. `Ts` in `template <typename... Ts>`.
+    Pack {
+        /// The name of the parameter pack.
+        name: Option<&'input str>,
+        /// The arguments of this instantiation.
+        params: Vec<TemplateParameter<'input>>,
+    },
+}
+
+impl<'input> TemplateParameter<'input> {
+    /// The name of the parameter.
+    #[inline]
+    pub fn name(&self) -> Option<&'input str> {
+        match self {
+            TemplateParameter::Type { name, .. }
+            | TemplateParameter::Value { name, .. }
+            | TemplateParameter::Pack { name, .. } => *name,
+        }
+    }
+
+    /// Whether the argument is the default argument of the parameter.
+    ///
+    /// This is recorded by `DW_AT_default_value`, so it is always false for
+    /// compilers which don't emit it. A parameter pack has no default.
+    #[inline]
+    pub fn is_default(&self) -> bool {
+        match self {
+            TemplateParameter::Type { default, .. } | TemplateParameter::Value { default, .. } => {
+                *default
+            }
+            TemplateParameter::Pack { .. } => false,
+        }
+    }
 }
 
 /// The layout of an item (member or padding) within a struct.
@@ -1227,11 +1411,16 @@ fn layout<'input, 'item>(
             item: LayoutItem::Member(member),
         })
         .collect();
-    members.extend(inherits.iter().map(|inherit| Layout {
-        bit_offset: inherit.bit_offset() - base_bit_offset,
-        bit_size: inherit.bit_size(hash).into(),
-        item: LayoutItem::Inherit(inherit),
-    }));
+    members.extend(
+        inherits
+            .iter()
+            .filter(|v| !v.is_virtual())
+            .map(|inherit| Layout {
+                bit_offset: inherit.bit_offset() - base_bit_offset,
+                bit_size: inherit.bit_size(hash).into(),
+                item: LayoutItem::Inherit(inherit),
+            }),
+    );
     members.extend(variant_parts.iter().map(|variant_part| Layout {
         bit_offset: variant_part.bit_offset() - base_bit_offset,
         bit_size: variant_part.bit_size(hash).into(),
@@ -1352,6 +1541,7 @@ impl<'input> EnumerationType<'input> {
 pub struct Enumerator<'input> {
     pub(crate) name: Option<&'input str>,
     pub(crate) value: Option<i64>,
+    pub(crate) udata_value: Option<u64>,
 }
 
 impl<'input> Enumerator<'input> {
@@ -1366,6 +1556,15 @@ impl<'input> Enumerator<'input> {
     pub fn value(&self) -> Option<i64> {
         self.value
     }
+
+    /// The value of the enumerator, interpreted as unsigned.
+    ///
+    /// This should be used instead of `value` if the underlying type of the enumeration is
+    /// unsigned, since fixed size forms such as `DW_FORM_data1` are sign extended by `value`.
+    #[inline]
+    pub fn udata_value(&self) -> Option<u64> {
+        self.udata_value
+    }
 }
 
 /// A type for an array of elements.
@@ -1518,6 +1717,7 @@ pub struct FunctionType<'input> {
     pub(crate) parameters: Vec<ParameterType<'input>>,
     pub(crate) return_type: TypeOffset,
     pub(crate) byte_size: Size,
+    pub(crate) variadic: bool,
 }
 
 impl<'input> FunctionType<'input> {
@@ -1533,6 +1733,20 @@ impl<'input> FunctionType<'input> {
         Type::from_offset(hash, self.return_type)
     }
 
+    /// The debuginfo offset of the return type of the function.
+    ///
+    /// This is none if the function returns void.
+    #[inline]
+    pub fn return_type_offset(&self) -> TypeOffset {
+        self.return_type
+    }
+
+    /// Return true if the function takes a variable number of arguments, i.e. `...`.
+    #[inline]
+    pub fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
     /// The size in bytes of an instance of this type.
     #[inline]
     pub fn byte_size(&self) -> Option<u64> {
@@ -1564,6 +1778,11 @@ impl<'input> FunctionType<'input> {
             return ord;
         }
 
+        let ord = a.variadic.cmp(&b.variadic);
+        if ord != cmp::Ordering::Equal {
+            return ord;
+        }
+
         match (a.return_type(hash_a), b.return_type(hash_b)) {
             (Some(ref ty_a), Some(ref ty_b)) => {
                 let ord = Type::cmp_id(hash_a, ty_a, hash_b, ty_b);
@@ -1605,6 +1824,12 @@ impl<'input> ParameterType<'input> {
         Type::from_offset(hash, self.ty)
     }
 
+    /// The debuginfo offset of the type of the parameter.
+    #[inline]
+    pub fn type_offset(&self) -> TypeOffset {
+        self.ty
+    }
+
     /// Compare the identifying information of two types.
     ///
     /// Parameters are considered equal if they have the same types.